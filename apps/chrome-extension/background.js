@@ -1,5 +1,11 @@
 // Background script for WasteDay Browser Tracker
-const ENDPOINT = 'http://127.0.0.1:5606/api/ingest/browsing';
+// デスクトップアプリは 5606 が使用中なら次の空きポートで待ち受ける（最大10個先まで）
+const DEFAULT_PORT = 5606;
+const PORT_RANGE = 10;
+const BATCH_PATH = '/api/ingest/browsing/batch';
+const POLICY_PATH = '/api/policy';
+const BATCH_SIZE = 200;
+const MAX_QUEUE_SIZE = 5000;
 
 class WasteDayTracker {
   constructor() {
@@ -7,6 +13,10 @@ class WasteDayTracker {
     this.pulseSeconds = 30;
     this.paused = false;
     this.browserFocused = true; // Chromeがフォアグラウンドかどうか
+    this.queue = null; // 送信待ちの BrowserData（storage と同期）
+    this.baseUrl = null; // 探索済みのデスクトップアプリのURL
+    this.flushing = false;
+    this.warnedTabs = new Map(); // tabId -> 警告済みのドメイン（同じページで何度も出さない）
     this.init();
   }
 
@@ -29,6 +39,7 @@ class WasteDayTracker {
 
     // タブの閉じるを監視
     chrome.tabs.onRemoved.addListener((tabId) => {
+      this.warnedTabs.delete(tabId);
       this.handleTabRemoved(tabId);
     });
 
@@ -52,6 +63,11 @@ class WasteDayTracker {
         return true;
       }
 
+      if (message.type === 'resolve_server') {
+        this.resolveServer().then((baseUrl) => sendResponse({ success: !!baseUrl, baseUrl }));
+        return true;
+      }
+
       if (message.type === 'get_popup_data') {
         const now = new Date();
         const sessions = [];
@@ -102,18 +118,26 @@ class WasteDayTracker {
       if (alarm.name !== 'heartbeat') return;
       if (!this.browserFocused) return; // 非フォーカス中は加算しない
       const currentTime = new Date().toISOString();
+      if (this.paused) return;
       for (const [tabId, session] of this.activeTabs.entries()) {
         const duration = this.calculateDuration(session.startTime);
         const bounded = Math.min(duration, this.pulseSeconds);
-        await this.sendBrowserData({
+        // 直近 pulse 秒の区間を送る（デスクトップアプリ側で同じタブ・URL の前の区間に結合される）
+        await this.enqueue({
           url: session.url,
           domain: session.domain,
           title: session.title,
-          timestamp: session.startTime,
+          timestamp: new Date(Date.now() - bounded * 1000).toISOString(),
           duration: bounded,
           tab_id: tabId
         });
       }
+      // 未送信分もあわせて1リクエストで送る
+      await this.flushQueue();
+      // 閲覧中に上限を超えた場合に備えて表示中のタブを再判定
+      for (const [tabId, session] of this.activeTabs.entries()) {
+        await this.applyPolicy(tabId, session.url, session.title);
+      }
     });
   }
 
@@ -148,6 +172,7 @@ class WasteDayTracker {
       });
 
       console.log(`Tab ${tabId} updated: ${domain}`);
+      await this.applyPolicy(tabId, tab.url, tab.title);
     } catch (error) {
       console.error('Error handling tab update:', error);
     }
@@ -184,6 +209,7 @@ class WasteDayTracker {
         });
 
         console.log(`Tab ${tabId} activated: ${domain}`);
+        await this.applyPolicy(tabId, tab.url, tab.title);
       }
     } catch (error) {
       console.error('Error handling tab activation:', error);
@@ -274,25 +300,172 @@ class WasteDayTracker {
     if (this.paused) {
       return;
     }
+    await this.enqueue(browserData);
+    await this.flushQueue();
+  }
+
+  // 送信待ちキュー（デスクトップアプリ未起動中もストレージに保持し、接続時にまとめて送る）
+  async loadQueue() {
+    if (this.queue) return this.queue;
+    const { pendingBrowserData } = await chrome.storage.local.get(['pendingBrowserData']);
+    this.queue = Array.isArray(pendingBrowserData) ? pendingBrowserData : [];
+    return this.queue;
+  }
+
+  async saveQueue() {
+    await chrome.storage.local.set({ pendingBrowserData: this.queue });
+  }
+
+  // 待ち受けポートを探索（/api/health が WasteDay と名乗るものを採用し、見つかったポートを保存）
+  async resolveServer() {
+    if (this.baseUrl) return this.baseUrl;
+    const { serverPort } = await chrome.storage.local.get(['serverPort']);
+    const candidates = [];
+    if (serverPort) candidates.push(serverPort);
+    for (let p = DEFAULT_PORT; p < DEFAULT_PORT + PORT_RANGE; p++) {
+      if (p !== serverPort) candidates.push(p);
+    }
+    for (const port of candidates) {
+      const baseUrl = `http://127.0.0.1:${port}`;
+      const controller = new AbortController();
+      const timer = setTimeout(() => controller.abort(), 1000);
+      try {
+        const res = await fetch(`${baseUrl}/api/health`, { signal: controller.signal });
+        const body = res.ok ? await res.json().catch(() => null) : null;
+        if (body && body.app === 'wasteday') {
+          this.baseUrl = baseUrl;
+          chrome.storage.local.set({ serverPort: port });
+          return baseUrl;
+        }
+      } catch (_) {
+        // 次の候補へ
+      } finally {
+        clearTimeout(timer);
+      }
+    }
+    return null;
+  }
+
+  async enqueue(browserData) {
+    const queue = await this.loadQueue();
+    // 同じレコード（開始時刻・ドメイン・タブ）は最新の経過秒だけ残す
+    const key = `${browserData.timestamp}-${browserData.domain}-${browserData.tab_id ?? 0}`;
+    const index = queue.findIndex(d => `${d.timestamp}-${d.domain}-${d.tab_id ?? 0}` === key);
+    if (index >= 0) {
+      queue[index] = browserData;
+    } else {
+      queue.push(browserData);
+    }
+    if (queue.length > MAX_QUEUE_SIZE) {
+      queue.splice(0, queue.length - MAX_QUEUE_SIZE);
+    }
+    await this.saveQueue();
+  }
+
+  async flushQueue() {
+    if (this.flushing) return;
+    this.flushing = true;
     try {
-      // HTTP 経由でデスクトップアプリに送信
-      const res = await fetch(ENDPOINT, {
-        method: 'POST',
-        headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify(browserData),
-        // サービスワーカーからのCORSはデフォルトでno-cors不可。明示は不要だが参考に残す。
-        // mode: 'cors',
-      });
-      if (!res.ok) {
-        console.error('HTTP send failed:', res.status, res.statusText);
+      // ペアリングで受け取ったトークンがなければ送信しない（サーバ側で拒否される）
+      const { ingestToken } = await chrome.storage.local.get(['ingestToken']);
+      if (!ingestToken) {
         return;
       }
-      console.log('Browser data sent via HTTP');
-      const now = Date.now();
-      chrome.storage.local.set({ lastDataSent: now, lastConnectionTime: now, isConnected: true });
+      await this.loadQueue();
+      if (this.queue.length === 0) {
+        return;
+      }
+      const baseUrl = await this.resolveServer();
+      if (!baseUrl) {
+        return;
+      }
+      while (this.queue.length > 0) {
+        const batch = this.queue.slice(0, BATCH_SIZE);
+        // HTTP 経由でデスクトップアプリに送信
+        const res = await fetch(`${baseUrl}${BATCH_PATH}`, {
+          method: 'POST',
+          headers: {
+            'Content-Type': 'application/json',
+            'Authorization': `Bearer ${ingestToken}`
+          },
+          body: JSON.stringify(batch),
+        });
+        if (res.status === 401 || res.status === 403) {
+          // トークンが失効/取り消された: 再ペアリングが必要（キューは保持）
+          console.warn('Ingest token rejected; pairing required');
+          chrome.storage.local.remove(['ingestToken']);
+          return;
+        }
+        if (!res.ok) {
+          console.error('HTTP send failed:', res.status, res.statusText);
+          return;
+        }
+        const result = await res.json();
+        // rejected は再送しても通らないので破棄する
+        for (const r of result.results || []) {
+          if (r.status === 'rejected') {
+            console.warn('Browser data rejected:', r.error, batch[r.index]);
+          }
+        }
+        // 送信中に同じレコードが更新された場合は新しい方を残す
+        this.queue = this.queue.filter(d => !batch.includes(d));
+        await this.saveQueue();
+        console.log(`Browser data sent via HTTP (${result.accepted} accepted, ${result.rejected} rejected)`);
+        const now = Date.now();
+        chrome.storage.local.set({ lastDataSent: now, lastConnectionTime: now, isConnected: true });
+      }
     } catch (error) {
+      // 接続できない場合はキューに残して次回送信（ポートが変わった可能性があるので再探索）
       console.error('Error sending browser data:', error);
+      this.baseUrl = null;
+    } finally {
+      this.flushing = false;
+    }
+  }
+
+  // デスクトップアプリに判定を問い合わせる（上限・集中時間の状態はアプリ側が持つ）。接続できなければ null
+  async fetchPolicy(url, title) {
+    const { ingestToken } = await chrome.storage.local.get(['ingestToken']);
+    if (!ingestToken) return null;
+    const baseUrl = await this.resolveServer();
+    if (!baseUrl) return null;
+    try {
+      const res = await fetch(`${baseUrl}${POLICY_PATH}`, {
+        method: 'POST',
+        headers: {
+          'Content-Type': 'application/json',
+          'Authorization': `Bearer ${ingestToken}`
+        },
+        body: JSON.stringify({ domain: this.extractDomain(url), url, title }),
+      });
+      return res.ok ? await res.json() : null;
+    } catch (error) {
+      console.error('Error fetching policy:', error);
+      this.baseUrl = null;
+      return null;
+    }
+  }
+
+  // block はブロックページへ遷移、warn はページ上にバナーを出す（一時停止中は何もしない）
+  async applyPolicy(tabId, url, title) {
+    if (this.paused) return;
+    const decision = await this.fetchPolicy(url, title);
+    if (!decision || decision.action === 'allow') {
+      this.warnedTabs.delete(tabId);
+      return;
     }
+    if (decision.action === 'block') {
+      const params = new URLSearchParams({ url, message: decision.message || '' });
+      await this.handleTabRemoved(tabId);
+      chrome.tabs.update(tabId, { url: chrome.runtime.getURL(`blocked.html?${params}`) });
+      return;
+    }
+    if (this.warnedTabs.get(tabId) === decision.domain) return;
+    this.warnedTabs.set(tabId, decision.domain);
+    chrome.tabs.sendMessage(tabId, { type: 'policy_warning', message: decision.message }, () => {
+      // content script が無いページ（読み込み中など）は無視
+      void chrome.runtime.lastError;
+    });
   }
 
   isValidUrl(url) {
@@ -0,0 +1,4 @@
+// ブロックページ: background.js から渡された理由と元のURLを表示する
+const params = new URLSearchParams(location.search);
+document.getElementById('message').textContent = params.get('message') || 'WasteDay の設定によりブロックされました';
+document.getElementById('url').textContent = params.get('url') || '';
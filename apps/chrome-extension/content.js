@@ -21,6 +21,13 @@ class ContentTracker {
       this.handleBlur();
     });
 
+    // デスクトップアプリの判定で warn になったら警告を表示
+    chrome.runtime.onMessage.addListener((message) => {
+      if (message.type === 'policy_warning') {
+        this.showWarning(message.message);
+      }
+    });
+
     // ページの読み込み完了時
     if (document.readyState === 'complete') {
       this.handlePageLoad();
@@ -68,6 +75,20 @@ class ContentTracker {
     // ここでは特に何もしない
   }
 
+  showWarning(text) {
+    const id = 'wasteday-policy-warning';
+    document.getElementById(id)?.remove();
+    const banner = document.createElement('div');
+    banner.id = id;
+    banner.textContent = `WasteDay: ${text || '今日の上限に近づいています'}`;
+    banner.style.cssText = 'position:fixed;top:0;left:0;right:0;z-index:2147483647;padding:10px 16px;' +
+      'background:#b45309;color:#fff;font:14px sans-serif;text-align:center;cursor:pointer;';
+    banner.title = 'クリックで閉じる';
+    banner.addEventListener('click', () => banner.remove());
+    (document.body || document.documentElement).appendChild(banner);
+    setTimeout(() => banner.remove(), 15000);
+  }
+
   sendPageData(duration) {
     try {
       // background script へメッセージ送信。失効時は例外が出る可能性がある。
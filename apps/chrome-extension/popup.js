@@ -5,8 +5,8 @@ class PopupController {
   }
 
   init() {
-    this.healthEndpoint = 'http://127.0.0.1:5606/api/health';
-    // no buttons
+    document.getElementById('pairButton').addEventListener('click', () => this.pair());
+    this.updatePairing();
 
     // 初期状態の更新
     this.updateStatus();
@@ -23,6 +23,53 @@ class PopupController {
 
   // removed testConnection
 
+  async updatePairing() {
+    const { ingestToken } = await chrome.storage.local.get(['ingestToken']);
+    document.getElementById('pairing').style.display = ingestToken ? 'none' : 'block';
+  }
+
+  // デスクトップアプリの設定画面に表示されたペアリングコードでトークンを取得
+  async pair() {
+    const input = document.getElementById('pairCode');
+    const message = document.getElementById('pairMessage');
+    const code = input.value.trim();
+    if (!code) return;
+    try {
+      const baseUrl = await this.resolveServer();
+      if (!baseUrl) {
+        message.textContent = 'Desktop app is not reachable';
+        return;
+      }
+      const res = await fetch(`${baseUrl}/api/pair`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ code, name: navigator.userAgent.includes('Edg/') ? 'Edge' : 'Chrome' })
+      });
+      const body = await res.json().catch(() => ({}));
+      if (!res.ok || !body.token) {
+        message.textContent = body.error || 'Pairing failed';
+        return;
+      }
+      await chrome.storage.local.set({ ingestToken: body.token });
+      input.value = '';
+      message.textContent = '';
+      this.updatePairing();
+    } catch (error) {
+      console.error('Pairing error:', error);
+      message.textContent = 'Desktop app is not reachable';
+    }
+  }
+
+  // 待ち受けポートの探索は background に任せる（ポートのフォールバックに追従）
+  async resolveServer() {
+    try {
+      const response = await chrome.runtime.sendMessage({ type: 'resolve_server' });
+      return response && response.success ? response.baseUrl : null;
+    } catch (_) {
+      return null;
+    }
+  }
+
   showStatus(isConnected, message) {
     const statusElement = document.getElementById('status');
     statusElement.textContent = message;
@@ -37,13 +84,15 @@ class PopupController {
       const isConnected = result.isConnected;
       
       // Try lightweight health check first
+      const baseUrl = await this.resolveServer();
       const controller = new AbortController();
       const timer = setTimeout(() => controller.abort(), 1500);
       try {
-        const res = await fetch(this.healthEndpoint, { method: 'GET', signal: controller.signal });
+        const res = baseUrl ? await fetch(`${baseUrl}/api/health`, { method: 'GET', signal: controller.signal }) : null;
         clearTimeout(timer);
-        if (res.ok) {
-          this.showStatus(true, 'Connected to desktop app');
+        if (res && res.ok) {
+          const { port } = await res.json().catch(() => ({}));
+          this.showStatus(true, port ? `Connected to desktop app (port ${port})` : 'Connected to desktop app');
           chrome.storage.local.set({ lastConnectionTime: Date.now(), isConnected: true });
           return;
         }
@@ -0,0 +1,320 @@
+// 浪費/生産的/未分類秒の時間・日・週・月単位集計（SQL 側で計算）
+// セッションはローカル時刻の1時間境界で分割してからバケットに振り分けるため、
+// 日/週/月をまたぐセッションも正しく按分される
+// category_totals は同じデータソースを階層カテゴリ（categories）ごとに合計する（ツリーへの積み上げは categories::rollup）
+// source=unified はブラウザの時間を閲覧記録で置き換えた重複のない区間（timeline）を使う
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::{Connection, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DataSource {
+    #[default]
+    App,
+    Browser,
+    /// app + browser（ブラウザが前面の時間は両方に数える）
+    All,
+    /// 統合タイムライン（ブラウザの時間をドメイン別の内訳に置き換え、重複しない）
+    Unified,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AggregateQuery {
+    pub since: String,
+    pub until: String,
+    pub granularity: Granularity,
+    #[serde(default)]
+    pub source: DataSource,
+    /// idle 状態の sessions も含めるか（既定: 含める。従来のダッシュボード集計と同じ）
+    pub include_idle: Option<bool>,
+    /// 週の開始曜日（0=日曜, 1=月曜）。既定は日曜
+    pub week_start: Option<u8>,
+    /// 指定時は固定オフセットで集計。未指定なら OS のローカルタイムゾーン（夏時間も考慮）
+    pub utc_offset_minutes: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AggregateBucket {
+    /// ローカル時刻でのバケット開始（hour: YYYY-MM-DDTHH:00:00, day/week/month: YYYY-MM-DD）
+    pub bucket: String,
+    pub waste_seconds: i64,
+    pub productive_seconds: i64,
+    pub unclassified_seconds: i64,
+}
+
+// waste_categories.label -> 集計区分
+const CLASS_CASE: &str = "CASE WHEN label IS NULL THEN 'unclassified' WHEN label = 'waste' THEN 'waste' ELSE 'productive' END";
+
+// label を解決した waste_categories 行の階層カテゴリ（categories.id）
+const BROWSER_NODE: &str = "CASE WHEN dc.id IS NOT NULL THEN dc.category_id WHEN bc.id IS NOT NULL THEN bc.category_id ELSE wd.category_id END";
+
+// 保持期間を過ぎて日別集計にまとめた分は、その日のローカル 0 時から続くセッションとして扱う
+// （日/週/月の合計は保たれるが、時間単位の内訳は失われる）
+const SUMMARY_DAY_START: &str = "julianday(ds.day || ' 00:00:00', :from_local)";
+
+fn app_source_sql() -> String {
+    format!(
+        "SELECT julianday(s.start_time) AS t0,
+                julianday(s.start_time) + s.duration_seconds / 86400.0 AS t1,
+                {class} AS class, s.node AS node
+         FROM (
+           SELECT s.start_time, s.duration_seconds, wc.label AS label, wc.category_id AS node
+           FROM sessions s
+           LEFT JOIN waste_categories wc ON wc.id = s.category_id AND wc.is_active = 1
+           WHERE s.start_time >= :lookback AND s.start_time < :until
+             AND (:include_idle = 1 OR s.user_state IS NOT 'idle')
+         ) s
+         UNION ALL
+         SELECT {day_start} AS t0, {day_start} + min(ds.duration_seconds, 86400) / 86400.0 AS t1, {class} AS class, ds.node AS node
+         FROM (
+           SELECT ds.day, ds.duration_seconds, wc.label AS label, wc.category_id AS node
+           FROM daily_app_summaries ds
+           LEFT JOIN waste_categories wc ON wc.type = 'app' AND wc.identifier = ds.identifier AND wc.is_active = 1
+           WHERE ds.day >= date(:lookback) AND ds.day <= date(:until, '+1 day')
+             AND (:include_idle = 1 OR ds.user_state IS NOT 'idle')
+         ) ds",
+        class = CLASS_CASE,
+        day_start = SUMMARY_DAY_START
+    )
+}
+
+fn browser_source_sql() -> String {
+    // ドメイン設定 → 記録時のカテゴリ → domain 型カテゴリ の順に解決
+    format!(
+        "SELECT julianday(b.start_time) AS t0,
+                julianday(b.start_time) + COALESCE(b.duration_seconds, 0) / 86400.0 AS t1,
+                {class} AS class, b.node AS node
+         FROM (
+           SELECT b.start_time, b.duration_seconds, COALESCE(dc.label, bc.label, wd.label) AS label, {node} AS node
+           FROM browsing_sessions b
+           LEFT JOIN domains d ON d.domain = b.domain AND d.is_active = 1
+           LEFT JOIN waste_categories dc ON dc.id = d.category_id AND dc.is_active = 1
+           LEFT JOIN waste_categories bc ON bc.id = b.category_id AND bc.is_active = 1
+           LEFT JOIN waste_categories wd ON wd.type = 'domain' AND wd.identifier = b.domain AND wd.is_active = 1
+           WHERE b.start_time >= :lookback AND b.start_time < :until
+         ) b
+         UNION ALL
+         SELECT {day_start} AS t0, {day_start} + min(ds.duration_seconds, 86400) / 86400.0 AS t1, {class} AS class, ds.node AS node
+         FROM (
+           SELECT ds.day, ds.duration_seconds, COALESCE(dc.label, bc.label, wd.label) AS label, {node} AS node
+           FROM daily_domain_summaries ds
+           LEFT JOIN domains d ON d.domain = ds.domain AND d.is_active = 1
+           LEFT JOIN waste_categories dc ON dc.id = d.category_id AND dc.is_active = 1
+           LEFT JOIN waste_categories bc ON bc.id = ds.category_id AND bc.is_active = 1
+           LEFT JOIN waste_categories wd ON wd.type = 'domain' AND wd.identifier = ds.domain AND wd.is_active = 1
+           WHERE ds.day >= date(:lookback) AND ds.day <= date(:until, '+1 day')
+         ) ds",
+        class = CLASS_CASE,
+        node = BROWSER_NODE,
+        day_start = SUMMARY_DAY_START
+    )
+}
+
+fn bucket_expr(granularity: Granularity) -> &'static str {
+    match granularity {
+        Granularity::Hour => "strftime('%Y-%m-%dT%H:00:00', seg_start, :to_local)",
+        Granularity::Day => "strftime('%Y-%m-%d', seg_start, :to_local)",
+        Granularity::Week => "date(seg_start, :to_local, '-6 days', :weekday)",
+        Granularity::Month => "strftime('%Y-%m-01', seg_start, :to_local)",
+    }
+}
+
+fn parse_time(value: &str, field: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("invalid {}: {}", field, e))
+}
+
+fn format_time(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// since/until の検証と、SQL に渡す範囲・タイムゾーン関連の値
+struct Range {
+    since: String,
+    until: String,
+    lookback: String,
+    to_local: String,
+    from_local: String,
+}
+
+impl Range {
+    fn new(since: &str, until: &str, utc_offset_minutes: Option<i32>) -> Result<Range, String> {
+        let since = parse_time(since, "since")?;
+        let until = parse_time(until, "until")?;
+        if until <= since {
+            return Err("until must be after since".to_string());
+        }
+        // 範囲開始前に始まって範囲内まで続くセッションも拾う
+        let lookback = since - Duration::days(1);
+        let (to_local, from_local) = match utc_offset_minutes {
+            Some(m) => (format!("{:+} minutes", m), format!("{:+} minutes", -m)),
+            None => ("localtime".to_string(), "utc".to_string()),
+        };
+        Ok(Range {
+            since: format_time(&since),
+            until: format_time(&until),
+            lookback: format_time(&lookback),
+            to_local,
+            from_local,
+        })
+    }
+}
+
+fn sources_sql(source: DataSource) -> String {
+    match source {
+        DataSource::App => app_source_sql(),
+        DataSource::Browser => browser_source_sql(),
+        DataSource::All => format!("{} UNION ALL {}", app_source_sql(), browser_source_sql()),
+        DataSource::Unified => "SELECT t0, t1, class, node FROM temp.unified_spans".to_string(),
+    }
+}
+
+// unified は集計前に期間内の区間を一時テーブルへ書き出す
+fn prepare_source(conn: &Connection, source: DataSource, range: &Range, include_idle: bool) -> AppResult<()> {
+    if source != DataSource::Unified {
+        return Ok(());
+    }
+    let query = crate::timeline::TimelineQuery { since: range.since.clone(), until: range.until.clone(), include_idle: Some(include_idle) };
+    crate::timeline::fill_unified_spans(conn, &query)
+}
+
+// 集計単位/データソースによって使わないパラメータは渡さない
+fn used_params<'a>(
+    stmt: &rusqlite::Statement,
+    candidates: &[(&'a str, &'a dyn ToSql)],
+) -> rusqlite::Result<Vec<(&'a str, &'a dyn ToSql)>> {
+    let mut params = Vec::new();
+    for &(name, value) in candidates {
+        if stmt.parameter_index(name)?.is_some() {
+            params.push((name, value));
+        }
+    }
+    Ok(params)
+}
+
+pub fn aggregate(conn: &Connection, query: &AggregateQuery) -> AppResult<Vec<AggregateBucket>> {
+    let range = Range::new(&query.since, &query.until, query.utc_offset_minutes)?;
+    let week_start = query.week_start.unwrap_or(0);
+    if week_start > 6 {
+        return Err(AppError::validation("week_start must be between 0 and 6").with_detail("field", "week_start"));
+    }
+
+    // 次のローカル1時間境界（夏時間の巻き戻しで境界が進まない場合は +1h）
+    let next_boundary = "(SELECT CASE WHEN nb > {t} THEN nb ELSE {t} + 1.0 / 24 END FROM (SELECT julianday(strftime('%Y-%m-%d %H:00:00', {t}, :to_local), '+1 hour', :from_local) AS nb))";
+    let sql = format!(
+        "WITH RECURSIVE
+           src(t0, t1, class, node) AS ({sources}),
+           seg(t0, t1, class) AS (
+             SELECT max(t0, julianday(:since)), min(t1, julianday(:until)), class
+             FROM src
+             WHERE t1 > julianday(:since) AND t0 < julianday(:until)
+           ),
+           split(seg_start, seg_end, end_all, class) AS (
+             SELECT t0, min(t1, {first}), t1, class FROM seg
+             UNION ALL
+             SELECT seg_end, min(end_all, {next}), end_all, class FROM split WHERE seg_end < end_all
+           )
+         SELECT {bucket} AS bucket,
+                CAST(round(SUM(CASE WHEN class = 'waste' THEN seg_end - seg_start ELSE 0 END) * 86400) AS INTEGER),
+                CAST(round(SUM(CASE WHEN class = 'productive' THEN seg_end - seg_start ELSE 0 END) * 86400) AS INTEGER),
+                CAST(round(SUM(CASE WHEN class = 'unclassified' THEN seg_end - seg_start ELSE 0 END) * 86400) AS INTEGER)
+         FROM split
+         WHERE seg_end > seg_start
+         GROUP BY bucket
+         ORDER BY bucket ASC",
+        sources = sources_sql(query.source),
+        first = next_boundary.replace("{t}", "t0"),
+        next = next_boundary.replace("{t}", "seg_end"),
+        bucket = bucket_expr(query.granularity),
+    );
+
+    let include_idle = query.include_idle.unwrap_or(true);
+    prepare_source(conn, query.source, &range, include_idle)?;
+    let mut stmt = conn.prepare(&sql)?;
+    let weekday = format!("weekday {}", week_start);
+    let candidates: [(&str, &dyn ToSql); 7] = [
+        (":since", &range.since),
+        (":until", &range.until),
+        (":lookback", &range.lookback),
+        (":include_idle", &include_idle),
+        (":to_local", &range.to_local),
+        (":from_local", &range.from_local),
+        (":weekday", &weekday),
+    ];
+    let params = used_params(&stmt, &candidates)?;
+    let rows = stmt
+        .query_map(
+            params.as_slice(),
+            |row| {
+                Ok(AggregateBucket {
+                    bucket: row.get(0)?,
+                    waste_seconds: row.get(1)?,
+                    productive_seconds: row.get(2)?,
+                    unclassified_seconds: row.get(3)?,
+                })
+            },
+        )?;
+    let mut out = Vec::new();
+    for r in rows {
+        out.push(r?);
+    }
+    Ok(out)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CategoryTotalsQuery {
+    pub since: String,
+    pub until: String,
+    #[serde(default)]
+    pub source: DataSource,
+    /// idle 状態の sessions も含めるか（既定: 含める）
+    pub include_idle: Option<bool>,
+    pub utc_offset_minutes: Option<i32>,
+}
+
+/// 階層カテゴリ（categories.id）ごとの秒数。category_id が None の行はカテゴリ未割り当て/未分類
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CategoryTotal {
+    pub category_id: Option<i64>,
+    pub seconds: i64,
+}
+
+/// 期間内の秒数を割り当てられたカテゴリごとに合計する（範囲外にはみ出した分は切り捨て）
+pub fn category_totals(conn: &Connection, query: &CategoryTotalsQuery) -> AppResult<Vec<CategoryTotal>> {
+    let range = Range::new(&query.since, &query.until, query.utc_offset_minutes)?;
+    let sql = format!(
+        "WITH src(t0, t1, class, node) AS ({sources})
+         SELECT node, CAST(round(SUM(min(t1, julianday(:until)) - max(t0, julianday(:since))) * 86400) AS INTEGER)
+         FROM src
+         WHERE t1 > julianday(:since) AND t0 < julianday(:until)
+         GROUP BY node
+         ORDER BY node ASC",
+        sources = sources_sql(query.source),
+    );
+    let include_idle = query.include_idle.unwrap_or(true);
+    prepare_source(conn, query.source, &range, include_idle)?;
+    let mut stmt = conn.prepare(&sql)?;
+    let candidates: [(&str, &dyn ToSql); 5] = [
+        (":since", &range.since),
+        (":until", &range.until),
+        (":lookback", &range.lookback),
+        (":include_idle", &include_idle),
+        (":from_local", &range.from_local),
+    ];
+    let params = used_params(&stmt, &candidates)?;
+    let rows = stmt.query_map(params.as_slice(), |row| Ok(CategoryTotal { category_id: row.get(0)?, seconds: row.get(1)? }))?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
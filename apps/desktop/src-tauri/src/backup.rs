@@ -0,0 +1,346 @@
+// DB のバックアップ/リストア: 共有接続から SQLite のオンラインバックアップAPIでスナップショットを作り、
+// リストアは検証・マイグレーション済みのコピーを作ってからファイルを差し替える
+// 自動バックアップの設定は user_settings（backup_config）に JSON で保存する
+use chrono::{DateTime, Local, NaiveDateTime};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::migrations;
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+
+const CONFIG_SETTING_KEY: &str = "backup_config";
+const FILE_STAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const CREATED_AT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+// ファイル名の接頭辞（自動バックアップだけが保持数による削除の対象）
+const MANUAL_PREFIX: &str = "wasteday-backup-";
+const AUTO_PREFIX: &str = "wasteday-auto-";
+const PRE_RESTORE_PREFIX: &str = "wasteday-pre-restore-";
+// バックアップ1ステップでコピーするページ数
+const PAGES_PER_STEP: std::os::raw::c_int = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    /// 残す自動バックアップの数
+    pub keep: u32,
+    /// 保存先（未指定ならアプリデータディレクトリの backups）
+    pub directory: Option<String>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { enabled: true, interval_hours: 24, keep: 7, directory: None }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持、directory は空文字で既定に戻す）
+#[derive(Deserialize, Debug, Default)]
+pub struct BackupConfigPatch {
+    pub enabled: Option<bool>,
+    pub interval_hours: Option<u32>,
+    pub keep: Option<u32>,
+    pub directory: Option<String>,
+}
+
+impl BackupConfig {
+    pub fn apply(&self, patch: BackupConfigPatch) -> Result<BackupConfig, String> {
+        let directory = match patch.directory {
+            Some(d) if d.trim().is_empty() => None,
+            Some(d) => Some(d.trim().to_string()),
+            None => self.directory.clone(),
+        };
+        let next = BackupConfig {
+            enabled: patch.enabled.unwrap_or(self.enabled),
+            interval_hours: patch.interval_hours.unwrap_or(self.interval_hours),
+            keep: patch.keep.unwrap_or(self.keep),
+            directory,
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=24 * 30).contains(&self.interval_hours) {
+            return Err("interval_hours must be between 1 and 720".to_string());
+        }
+        if !(1..=100).contains(&self.keep) {
+            return Err("keep must be between 1 and 100".to_string());
+        }
+        if let Some(dir) = &self.directory {
+            if !Path::new(dir).is_absolute() {
+                return Err("directory must be an absolute path".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> BackupConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, BackupConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+
+    pub fn directory_or(&self, default_dir: &Path) -> PathBuf {
+        self.directory.as_ref().map(PathBuf::from).unwrap_or_else(|| default_dir.to_path_buf())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    Manual,
+    Automatic,
+    PreRestore,
+}
+
+impl BackupKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            BackupKind::Manual => MANUAL_PREFIX,
+            BackupKind::Automatic => AUTO_PREFIX,
+            BackupKind::PreRestore => PRE_RESTORE_PREFIX,
+        }
+    }
+
+    pub fn file_name(self, at: DateTime<Local>) -> String {
+        format!("{}{}.db", self.prefix(), at.format(FILE_STAMP_FORMAT))
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BackupInfo {
+    pub path: String,
+    pub kind: BackupKind,
+    pub size_bytes: u64,
+    /// 作成時刻（ローカル時刻）
+    pub created_at: String,
+}
+
+/// Tauri の State として保持する（DB の場所と自動バックアップの設定）
+pub struct BackupState {
+    pub db_path: PathBuf,
+    pub default_dir: PathBuf,
+    pub config: std::sync::Mutex<BackupConfig>,
+}
+
+impl BackupState {
+    pub fn new(db_path: PathBuf, default_dir: PathBuf, config: BackupConfig) -> Self {
+        Self { db_path, default_dir, config: std::sync::Mutex::new(config) }
+    }
+
+    pub fn directory(&self) -> PathBuf {
+        let config = self.config.lock().map(|c| c.clone()).unwrap_or_default();
+        config.directory_or(&self.default_dir)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RestoreResult {
+    pub restored_from: String,
+    /// バックアップ時点のスキーマバージョン
+    pub backup_schema_version: i64,
+    /// マイグレーション後のスキーマバージョン
+    pub schema_version: i64,
+    /// 差し替え前の DB の退避先
+    pub pre_restore_backup: String,
+}
+
+/// 共有接続からファイルへバックアップする（一時ファイルに書いてからリネーム）
+/// 呼び出し側が接続のロックを持っている間に取るため、記録中でも一貫したスナップショットになる
+pub fn backup_to(conn: &Connection, dest: &Path, kind: BackupKind, now: DateTime<Local>) -> AppResult<BackupInfo> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = partial_path(dest);
+    let _ = fs::remove_file(&partial);
+    let result = (|| -> AppResult<()> {
+        let mut dst = Connection::open(&partial)?;
+        {
+            let backup = Backup::new(conn, &mut dst)?;
+            backup.run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)?;
+        }
+        dst.close().map_err(|(_, e)| e)?;
+        Ok(fs::rename(&partial, dest)?)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(BackupInfo {
+        path: dest.to_string_lossy().into_owned(),
+        kind,
+        size_bytes: fs::metadata(dest).map(|m| m.len()).unwrap_or(0),
+        created_at: now.format(CREATED_AT_FORMAT).to_string(),
+    })
+}
+
+/// 種類に応じた名前でディレクトリにバックアップを作る
+pub fn create_backup(conn: &Connection, dir: &Path, kind: BackupKind, now: DateTime<Local>) -> AppResult<BackupInfo> {
+    backup_to(conn, &dir.join(kind.file_name(now)), kind, now)
+}
+
+/// バックアップファイルを検証し、スキーマバージョンを返す
+/// 新しいバージョンのアプリで作られたもの（未知のマイグレーションを含むもの）は拒否する
+// バックアップの中身の問題は DbCorrupt（対象の DB が壊れている）として返す
+pub fn validate_backup(path: &Path) -> AppResult<i64> {
+    if !path.is_file() {
+        return Err(AppError::not_found("backup file", path.display().to_string()));
+    }
+    let corrupt = |message: String| AppError::new(ErrorCode::DbCorrupt, message);
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| corrupt(format!("not a SQLite database: {}", e)))?;
+    let check: String = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| corrupt(format!("not a valid WasteDay backup: {}", e)))?;
+    if check != "ok" {
+        return Err(corrupt(format!("backup is corrupted: {}", check)));
+    }
+    let has_sessions: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sessions')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_sessions {
+        return Err(corrupt("not a WasteDay backup: sessions table is missing".to_string()));
+    }
+    let version = migrations::current_version(&conn)?;
+    if version > migrations::latest_version() {
+        return Err(AppError::conflict(format!(
+            "backup schema version {} is newer than this app supports ({})",
+            version,
+            migrations::latest_version()
+        ))
+        .with_detail("found", version)
+        .with_detail("supported", migrations::latest_version()));
+    }
+    Ok(version)
+}
+
+/// バックアップから DB を復元する
+/// 検証 → 現在の DB を退避 → 隣にコピーしてマイグレーション → 接続を閉じてリネームで差し替え → 開き直す の順で行い、
+/// 差し替え前に失敗した場合は現在の DB に一切触れない
+pub fn restore(conn: &mut Connection, db_path: &Path, source: &Path, backup_dir: &Path, now: DateTime<Local>) -> AppResult<RestoreResult> {
+    let backup_schema_version = validate_backup(source)?;
+    let pre_restore = create_backup(conn, backup_dir, BackupKind::PreRestore, now)?;
+
+    let staged = db_path.with_extension("db.restore");
+    let _ = fs::remove_file(&staged);
+    let staged_version = (|| -> AppResult<i64> {
+        fs::copy(source, &staged)?;
+        let mut staged_conn = Connection::open(&staged)?;
+        let version = migrations::migrate(&mut staged_conn)?;
+        staged_conn.close().map_err(|(_, e)| e)?;
+        Ok(version)
+    })();
+    let schema_version = match staged_version {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = fs::remove_file(&staged);
+            return Err(AppError { message: format!("failed to prepare restore: {}", e), ..e });
+        }
+    };
+
+    // 差し替え中は一時的にインメモリ接続を置いておく（ロックは呼び出し側が保持）
+    let placeholder = Connection::open_in_memory()?;
+    let current = std::mem::replace(conn, placeholder);
+    if let Err((current, e)) = current.close() {
+        *conn = current;
+        let _ = fs::remove_file(&staged);
+        return Err(AppError { message: format!("failed to close database: {}", e), ..e.into() });
+    }
+    let swapped = fs::rename(&staged, db_path);
+    // 差し替えに失敗しても元のファイルを開き直す
+    *conn = reopen(db_path).map_err(|e| AppError { message: format!("failed to reopen database: {}", e), ..e })?;
+    if let Err(e) = swapped {
+        let _ = fs::remove_file(&staged);
+        return Err(AppError { message: format!("failed to replace database file: {}", e), ..e.into() });
+    }
+
+    Ok(RestoreResult {
+        restored_from: source.to_string_lossy().into_owned(),
+        backup_schema_version,
+        schema_version,
+        pre_restore_backup: pre_restore.path,
+    })
+}
+
+// 起動時と同じく migrate を通して開く（接続ごとの設定 PRAGMA foreign_keys もここで有効になる）
+fn reopen(db_path: &Path) -> AppResult<Connection> {
+    let mut conn = Connection::open(db_path)?;
+    migrations::migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// ディレクトリ内のバックアップを新しい順に返す
+pub fn list_backups(dir: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut backups: Vec<BackupInfo> = entries.filter_map(|e| e.ok()).filter_map(|e| describe(&e.path())).collect();
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    backups
+}
+
+/// 前回の自動バックアップから interval_hours 経っていればバックアップし、古いものを削除する
+pub fn run_scheduled(conn: &Connection, dir: &Path, config: &BackupConfig, now: DateTime<Local>) -> AppResult<Option<BackupInfo>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let latest = list_backups(dir)
+        .into_iter()
+        .filter(|b| b.kind == BackupKind::Automatic)
+        .filter_map(|b| parse_stamp(&b.created_at))
+        .max();
+    let due = latest.is_none_or(|t| now.naive_local() - t >= chrono::Duration::hours(config.interval_hours as i64));
+    if !due {
+        return Ok(None);
+    }
+    let info = create_backup(conn, dir, BackupKind::Automatic, now)?;
+    prune(dir, config.keep as usize)?;
+    Ok(Some(info))
+}
+
+/// 自動バックアップを新しいものから keep 件だけ残す（手動・リストア前のものは消さない）
+pub fn prune(dir: &Path, keep: usize) -> AppResult<usize> {
+    let stale: Vec<BackupInfo> = list_backups(dir)
+        .into_iter()
+        .filter(|b| b.kind == BackupKind::Automatic)
+        .skip(keep)
+        .collect();
+    for b in &stale {
+        fs::remove_file(&b.path)?;
+    }
+    Ok(stale.len())
+}
+
+fn describe(path: &Path) -> Option<BackupInfo> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(".db")?;
+    let (kind, stamp) = [BackupKind::Manual, BackupKind::Automatic, BackupKind::PreRestore]
+        .into_iter()
+        .find_map(|k| stem.strip_prefix(k.prefix()).map(|s| (k, s)))?;
+    let created = NaiveDateTime::parse_from_str(stamp, FILE_STAMP_FORMAT).ok()?;
+    let size_bytes = fs::metadata(path).ok()?.len();
+    Some(BackupInfo {
+        path: path.to_string_lossy().into_owned(),
+        kind,
+        size_bytes,
+        created_at: created.format(CREATED_AT_FORMAT).to_string(),
+    })
+}
+
+fn parse_stamp(created_at: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(created_at, CREATED_AT_FORMAT).ok()
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".partial");
+    path.with_file_name(name)
+}
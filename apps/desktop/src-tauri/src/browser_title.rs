@@ -0,0 +1,202 @@
+// 拡張機能なしでのブラウザの分類（縮退モード）: 前面ウィンドウのタイトルからページタイトルとサイトを取り出す
+// ブラウザごとのタイトルの形式（"ページ - Google Chrome" など）は正規表現で設定し、名前付きグループ title（必須）/ site（任意）で取り出す
+// サイトは domains（分類済みの行）と照合する: タイトル中のドメイン表記、または " - " などで区切った部分がサイト名（youtube.com なら youtube）と一致するもの
+// 設定は user_settings（browser_title_config）に JSON で保存する
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+
+const CONFIG_SETTING_KEY: &str = "browser_title_config";
+// ページタイトル内のサイト名の区切り
+const SEPARATORS: [&str; 6] = [" - ", " | ", " — ", " – ", " · ", " : "];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BrowserTitlePattern {
+    /// 対象のアプリの識別子（小文字）
+    pub identifiers: Vec<String>,
+    /// ウィンドウタイトル全体に対する正規表現（title グループ必須、site グループ任意）
+    pub pattern: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BrowserTitleConfig {
+    /// 無効ならタイトルからの分類を行わない
+    pub enabled: bool,
+    /// 先に一致したものを使う
+    pub patterns: Vec<BrowserTitlePattern>,
+}
+
+fn pattern(identifiers: &[&str], pattern: &str) -> BrowserTitlePattern {
+    BrowserTitlePattern { identifiers: identifiers.iter().map(|s| s.to_string()).collect(), pattern: pattern.to_string() }
+}
+
+impl Default for BrowserTitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: vec![
+                pattern(&["chrome.exe", "chrome", "google-chrome", "chromium"], r"^(?P<title>.+?) - (?:Google Chrome|Chromium)$"),
+                // Edge は "Microsoft" と "Edge" の間にゼロ幅スペースが入る
+                pattern(&["msedge.exe", "msedge", "microsoft-edge"], r"^(?P<title>.+?)(?: and \d+ more pages?)? - Microsoft\x{200B}? Edge$"),
+                pattern(&["firefox.exe", "firefox"], r"^(?P<title>.+?) (?:—|-) Mozilla Firefox(?: Private Browsing)?$"),
+                pattern(&["brave.exe", "brave"], r"^(?P<title>.+?) - Brave$"),
+            ],
+        }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct BrowserTitleConfigPatch {
+    pub enabled: Option<bool>,
+    pub patterns: Option<Vec<BrowserTitlePattern>>,
+}
+
+fn compile(p: &BrowserTitlePattern) -> Result<Regex, String> {
+    let re = Regex::new(&p.pattern).map_err(|e| format!("invalid title pattern: {}", e))?;
+    if !re.capture_names().any(|n| n == Some("title")) {
+        return Err(format!("title pattern must have a (?P<title>...) group: {}", p.pattern));
+    }
+    Ok(re)
+}
+
+impl BrowserTitleConfig {
+    pub fn apply(&self, patch: BrowserTitleConfigPatch) -> Result<BrowserTitleConfig, String> {
+        let patterns = patch.patterns.unwrap_or_else(|| self.patterns.clone());
+        let next = BrowserTitleConfig {
+            enabled: patch.enabled.unwrap_or(self.enabled),
+            patterns: patterns
+                .into_iter()
+                .map(|p| BrowserTitlePattern {
+                    identifiers: p.identifiers.iter().map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()).collect(),
+                    pattern: p.pattern,
+                })
+                .collect(),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for p in &self.patterns {
+            if p.identifiers.is_empty() {
+                return Err("each title pattern needs at least one identifier".to_string());
+            }
+            compile(p)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> BrowserTitleConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, BrowserTitleConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+/// ウィンドウタイトルから取り出した内容
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTitle {
+    pub page_title: String,
+    /// domains と照合できたサイト（照合できなければ None）
+    pub site: Option<String>,
+}
+
+// サイト名: 登録ドメインの先頭のラベル（www.youtube.com / youtube.co.jp → youtube）
+fn site_name(domain: &str) -> String {
+    let labels: Vec<&str> = domain.trim_start_matches("www.").split('.').collect();
+    let n = labels.len();
+    let index = if n >= 3 && labels[n - 2].len() <= 3 { n - 3 } else { n.saturating_sub(2) };
+    labels[index].to_string()
+}
+
+/// 設定とサイト照合用の domains を読み込んだもの（サンプラーや分類でまとめて使う）
+#[derive(Debug, Default)]
+pub struct BrowserTitles {
+    patterns: Vec<(Vec<String>, Regex)>,
+    // (domain, サイト名)。ドメインの短い順
+    sites: Vec<(String, String)>,
+    domain_token: Option<Regex>,
+}
+
+impl BrowserTitles {
+    pub fn load(conn: &Connection) -> rusqlite::Result<BrowserTitles> {
+        let config = BrowserTitleConfig::load(conn);
+        if !config.enabled {
+            return Ok(BrowserTitles::default());
+        }
+        let mut stmt = conn.prepare(
+            "SELECT domain FROM domains WHERE is_active = 1 AND category_id IS NOT NULL ORDER BY length(domain), domain",
+        )?;
+        let sites = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .map(|d| {
+                let d = d.to_lowercase();
+                let name = site_name(&d);
+                (d, name)
+            })
+            .collect();
+        Ok(BrowserTitles::new(&config, sites))
+    }
+
+    fn new(config: &BrowserTitleConfig, sites: Vec<(String, String)>) -> BrowserTitles {
+        let patterns = config
+            .patterns
+            .iter()
+            .filter_map(|p| compile(p).ok().map(|re| (p.identifiers.clone(), re)))
+            .collect();
+        let domain_token = Regex::new(r"(?:[a-z0-9-]+\.)+[a-z]{2,}").ok();
+        BrowserTitles { patterns, sites, domain_token }
+    }
+
+    pub fn is_browser(&self, identifier: &str) -> bool {
+        let identifier = identifier.to_lowercase();
+        self.patterns.iter().any(|(ids, _)| ids.contains(&identifier))
+    }
+
+    /// 既知のブラウザのタイトルならページタイトルとサイトを返す（形式が合わなければ None）
+    pub fn parse(&self, identifier: &str, window_title: &str) -> Option<ParsedTitle> {
+        let identifier = identifier.to_lowercase();
+        let caps = self
+            .patterns
+            .iter()
+            .filter(|(ids, _)| ids.contains(&identifier))
+            .find_map(|(_, re)| re.captures(window_title.trim()))?;
+        let page_title = caps.name("title")?.as_str().trim().to_string();
+        let site = caps
+            .name("site")
+            .map(|m| m.as_str().trim().trim_start_matches("www.").to_lowercase())
+            .filter(|s| !s.is_empty())
+            .or_else(|| self.match_site(&page_title));
+        Some(ParsedTitle { page_title, site })
+    }
+
+    // タイトル中のドメイン表記 → 区切った部分（後ろから）とサイト名の一致、の順で domains と照合する
+    fn match_site(&self, page_title: &str) -> Option<String> {
+        let lower = page_title.to_lowercase();
+        if let Some(re) = &self.domain_token {
+            for token in re.find_iter(&lower).map(|m| m.as_str().trim_start_matches("www.")) {
+                if let Some((domain, _)) = self.sites.iter().find(|(d, _)| token == d || token.ends_with(&format!(".{}", d))) {
+                    return Some(domain.clone());
+                }
+            }
+        }
+        let mut segments = vec![lower.as_str()];
+        for sep in SEPARATORS {
+            segments = segments.into_iter().flat_map(|s| s.split(sep)).collect();
+        }
+        segments
+            .iter()
+            .rev()
+            .map(|s| s.trim())
+            .find_map(|s| self.sites.iter().find(|(d, name)| s == name || s == d))
+            .map(|(d, _)| d.clone())
+    }
+}
@@ -0,0 +1,351 @@
+// 1日あたりの利用上限: カテゴリ（子孫を含む）/ アプリ（識別子）/ ドメイン（サブドメインを含む）ごとに設定し、
+// 今日（ローカル日付の 0 時から現在まで）の統合タイムラインの利用時間と比べる。idle のセッションは数えない
+// しきい値（既定 50/80/100%）を超えたら1日1回だけ通知する。設定は user_settings（budget_config）に JSON で保存する
+use chrono::{DateTime, Local, NaiveTime, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::categories;
+use crate::error::{AppError, AppResult};
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+use crate::timeline::{self, TimelineQuery};
+
+const CONFIG_SETTING_KEY: &str = "budget_config";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BudgetConfig {
+    /// 無効なら通知しない（残り時間の問い合わせは常にできる）
+    pub enabled: bool,
+    /// 通知するしきい値（上限に対する %、昇順）
+    pub thresholds: Vec<u32>,
+    /// トレイ（OS）の通知を出すか。false ならイベントのみ
+    pub notify: bool,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self { enabled: true, thresholds: vec![50, 80, 100], notify: true }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct BudgetConfigPatch {
+    pub enabled: Option<bool>,
+    pub thresholds: Option<Vec<u32>>,
+    pub notify: Option<bool>,
+}
+
+impl BudgetConfig {
+    pub fn apply(&self, patch: BudgetConfigPatch) -> Result<BudgetConfig, String> {
+        let mut thresholds = patch.thresholds.unwrap_or_else(|| self.thresholds.clone());
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        let next = BudgetConfig {
+            enabled: patch.enabled.unwrap_or(self.enabled),
+            thresholds,
+            notify: patch.notify.unwrap_or(self.notify),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.thresholds.iter().any(|t| !(1..=1000).contains(t)) {
+            return Err("thresholds must be between 1 and 1000".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> BudgetConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, BudgetConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetTarget {
+    /// target は categories.id
+    Category,
+    /// target は識別子（chrome.exe など）
+    App,
+    /// target はドメイン（サブドメインも含めて数える）
+    Domain,
+}
+
+impl BudgetTarget {
+    fn as_str(self) -> &'static str {
+        match self {
+            BudgetTarget::Category => "category",
+            BudgetTarget::App => "app",
+            BudgetTarget::Domain => "domain",
+        }
+    }
+
+    fn parse(value: &str) -> Option<BudgetTarget> {
+        match value {
+            "category" => Some(BudgetTarget::Category),
+            "app" => Some(BudgetTarget::App),
+            "domain" => Some(BudgetTarget::Domain),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Budget {
+    pub id: i64,
+    pub target_type: BudgetTarget,
+    pub target: String,
+    pub daily_limit_seconds: i64,
+    pub is_active: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewBudget {
+    pub target_type: BudgetTarget,
+    pub target: String,
+    pub daily_limit_seconds: i64,
+    /// 既定は有効
+    pub is_active: Option<bool>,
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct BudgetPatch {
+    pub daily_limit_seconds: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+/// 今日の利用状況
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BudgetStatus {
+    pub budget_id: i64,
+    pub target_type: BudgetTarget,
+    pub target: String,
+    /// 表示名（カテゴリ名 / 識別子 / ドメイン）
+    pub label: String,
+    pub daily_limit_seconds: i64,
+    pub used_seconds: i64,
+    /// 上限を超えていれば 0
+    pub remaining_seconds: i64,
+    pub percent: f64,
+    pub exceeded: bool,
+}
+
+/// 新しく超えたしきい値（1回の判定で同じ上限について複数超えた場合は最大のものだけ）
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BudgetAlert {
+    pub day: String,
+    pub threshold: u32,
+    pub status: BudgetStatus,
+}
+
+// ====== budgets CRUD ======
+
+fn read_budget(row: &rusqlite::Row) -> rusqlite::Result<Budget> {
+    let target_type: String = row.get(1)?;
+    Ok(Budget {
+        id: row.get(0)?,
+        target_type: BudgetTarget::parse(&target_type).unwrap_or(BudgetTarget::App),
+        target: row.get(2)?,
+        daily_limit_seconds: row.get(3)?,
+        is_active: row.get::<_, i64>(4)? != 0,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+const BUDGET_COLUMNS: &str = "id, target_type, target, daily_limit_seconds, is_active, created_at, updated_at";
+
+pub fn list_budgets(conn: &Connection) -> AppResult<Vec<Budget>> {
+    let sql = format!("SELECT {} FROM budgets ORDER BY target_type, target", BUDGET_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], read_budget)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+pub fn get_budget(conn: &Connection, id: i64) -> AppResult<Budget> {
+    let sql = format!("SELECT {} FROM budgets WHERE id = ?1", BUDGET_COLUMNS);
+    conn.query_row(&sql, params![id], read_budget)
+        .optional()?
+        .ok_or_else(|| AppError::not_found("budget", id))
+}
+
+fn validate_limit(seconds: i64) -> AppResult<()> {
+    if !(60..=86400).contains(&seconds) {
+        return Err(AppError::validation("daily_limit_seconds must be between 60 and 86400").with_detail("field", "daily_limit_seconds"));
+    }
+    Ok(())
+}
+
+fn normalize_target(conn: &Connection, target_type: BudgetTarget, target: &str) -> AppResult<String> {
+    let target = target.trim().to_lowercase();
+    if target.is_empty() {
+        return Err(AppError::validation("target is required").with_detail("field", "target"));
+    }
+    if target_type == BudgetTarget::Category {
+        let id: i64 = target
+            .parse()
+            .map_err(|_| AppError::validation(format!("invalid category id: {}", target)).with_detail("field", "target"))?;
+        categories::get_category(conn, id)?;
+    }
+    Ok(target)
+}
+
+pub fn create_budget(conn: &Connection, budget: NewBudget) -> AppResult<Budget> {
+    validate_limit(budget.daily_limit_seconds)?;
+    let target = normalize_target(conn, budget.target_type, &budget.target)?;
+    conn.execute(
+        "INSERT INTO budgets(target_type, target, daily_limit_seconds, is_active) VALUES(?1, ?2, ?3, ?4)",
+        params![
+            budget.target_type.as_str(),
+            target,
+            budget.daily_limit_seconds,
+            if budget.is_active.unwrap_or(true) { 1 } else { 0 }
+        ],
+    )
+    .map_err(|e| AppError::from(e).on_conflict(|| format!("a budget for {} {} already exists", budget.target_type.as_str(), target)))?;
+    get_budget(conn, conn.last_insert_rowid())
+}
+
+pub fn update_budget(conn: &Connection, id: i64, patch: BudgetPatch) -> AppResult<Budget> {
+    let current = get_budget(conn, id)?;
+    let limit = patch.daily_limit_seconds.unwrap_or(current.daily_limit_seconds);
+    validate_limit(limit)?;
+    conn.execute(
+        "UPDATE budgets SET daily_limit_seconds = ?2, is_active = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+        params![id, limit, if patch.is_active.unwrap_or(current.is_active) { 1 } else { 0 }],
+    )?;
+    // 上限を変えたら今日の通知をやり直す
+    if limit != current.daily_limit_seconds {
+        conn.execute("DELETE FROM budget_notifications WHERE budget_id = ?1", params![id])?;
+    }
+    get_budget(conn, id)
+}
+
+pub fn delete_budget(conn: &Connection, id: i64) -> AppResult<()> {
+    let deleted = conn.execute("DELETE FROM budgets WHERE id = ?1", params![id])?;
+    if deleted == 0 {
+        return Err(AppError::not_found("budget", id));
+    }
+    Ok(())
+}
+
+// ====== usage ======
+
+// 今日のローカル 0 時（UTC）と、ローカル日付（YYYY-MM-DD）
+fn today(now: DateTime<Local>) -> (DateTime<Utc>, String) {
+    let day = now.date_naive();
+    let midnight = Local.from_local_datetime(&day.and_time(NaiveTime::MIN)).earliest().unwrap_or(now);
+    (midnight.with_timezone(&Utc), day.format("%Y-%m-%d").to_string())
+}
+
+fn format_time(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// ドメインそのものか、そのサブドメイン
+pub(crate) fn matches_domain(domain: &str, target: &str) -> bool {
+    domain == target || domain.strip_suffix(target).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// 有効な上限について今日の利用状況を返す
+pub fn status(conn: &Connection, now: DateTime<Local>) -> AppResult<Vec<BudgetStatus>> {
+    let budgets: Vec<Budget> = list_budgets(conn)?.into_iter().filter(|b| b.is_active).collect();
+    if budgets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (since, _) = today(now);
+    let until = now.with_timezone(&Utc);
+
+    // どの種類も閲覧記録と重複しない統合タイムライン（idle を除く）から数える
+    let entries = if until > since {
+        let query = TimelineQuery { since: format_time(&since), until: format_time(&until), include_idle: Some(false) };
+        timeline::unified_timeline(conn, &query)?.entries
+    } else {
+        Vec::new()
+    };
+    let category_names = categories::list_categories(conn)?;
+    // カテゴリはツリーに積み上げた合計（子孫を含む）
+    let parents: HashMap<i64, Option<i64>> = category_names.iter().map(|c| (c.id, c.parent_id)).collect();
+    let mut category_ms: HashMap<i64, i64> = HashMap::new();
+    for e in &entries {
+        let mut node = e.category_node;
+        for _ in 0..=parents.len() {
+            let Some(id) = node.filter(|id| parents.contains_key(id)) else { break };
+            *category_ms.entry(id).or_default() += e.duration_ms();
+            node = parents[&id];
+        }
+    }
+    // ミリ秒で合計してから秒に丸める
+    let secs = |ms: i64| (ms as f64 / 1000.0).round() as i64;
+
+    let mut out = Vec::with_capacity(budgets.len());
+    for b in budgets {
+        let (used_seconds, label) = match b.target_type {
+            BudgetTarget::Category => {
+                let id = b.target.parse::<i64>().ok();
+                let used = id.and_then(|id| category_ms.get(&id)).map(|&ms| secs(ms)).unwrap_or(0);
+                let name = category_names.iter().find(|c| Some(c.id) == id).map(|c| c.name.clone()).unwrap_or_else(|| b.target.clone());
+                (used, name)
+            }
+            BudgetTarget::App => {
+                let ms = entries.iter().filter(|e| e.identifier.as_deref() == Some(b.target.as_str())).map(|e| e.duration_ms()).sum();
+                (secs(ms), b.target.clone())
+            }
+            BudgetTarget::Domain => {
+                let ms = entries.iter().filter(|e| e.domain.as_deref().is_some_and(|d| matches_domain(d, &b.target))).map(|e| e.duration_ms()).sum();
+                (secs(ms), b.target.clone())
+            }
+        };
+        let percent = used_seconds as f64 * 100.0 / b.daily_limit_seconds as f64;
+        out.push(BudgetStatus {
+            budget_id: b.id,
+            target_type: b.target_type,
+            target: b.target,
+            label,
+            daily_limit_seconds: b.daily_limit_seconds,
+            used_seconds,
+            remaining_seconds: (b.daily_limit_seconds - used_seconds).max(0),
+            percent: (percent * 10.0).round() / 10.0,
+            exceeded: used_seconds >= b.daily_limit_seconds,
+        });
+    }
+    Ok(out)
+}
+
+/// 今日新しく超えたしきい値を記録して返す（前日以前の通知記録は削除する）
+pub fn check(conn: &Connection, config: &BudgetConfig, now: DateTime<Local>) -> AppResult<Vec<BudgetAlert>> {
+    let (_, day) = today(now);
+    conn.execute("DELETE FROM budget_notifications WHERE day < ?1", params![day])?;
+    if !config.enabled || config.thresholds.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut alerts = Vec::new();
+    for s in status(conn, now)? {
+        let mut crossed = None;
+        for &threshold in config.thresholds.iter().filter(|&&t| s.used_seconds * 100 >= s.daily_limit_seconds * t as i64) {
+            let inserted = conn
+                .execute(
+                    "INSERT OR IGNORE INTO budget_notifications(budget_id, day, threshold) VALUES(?1, ?2, ?3)",
+                    params![s.budget_id, day, threshold],
+                )?;
+            if inserted > 0 {
+                crossed = Some(threshold);
+            }
+        }
+        if let Some(threshold) = crossed {
+            alerts.push(BudgetAlert { day: day.clone(), threshold, status: s });
+        }
+    }
+    Ok(alerts)
+}
@@ -0,0 +1,320 @@
+// 階層カテゴリ（Work / Learning / Social / Video / Gaming など）: 色・親子関係・浪費の重み（0.0〜1.0）を持つ
+// アプリ/ドメインは waste_categories.category_id でカテゴリに割り当てる。割り当て済みの行の label は
+// カテゴリの実効重み（未設定なら親から継承）から決める（0.5 以上で waste）ので、従来の浪費/生産的集計とも一致する
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::aggregate::{self, CategoryTotalsQuery};
+use crate::error::{AppError, AppResult};
+
+// これ以上の重みなら label = waste
+const WASTE_THRESHOLD: f64 = 0.5;
+// 親をたどる上限（壊れたデータで循環していても止まるように）
+const MAX_DEPTH: usize = 32;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Category {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub color: Option<String>,
+    /// 未設定（None）なら親から継承
+    pub waste_weight: Option<f64>,
+    /// 継承を解決した重み（トップレベルで未設定なら 0.0）
+    pub effective_waste_weight: f64,
+    /// トップレベルが 0
+    pub depth: usize,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewCategory {
+    pub name: String,
+    pub parent_id: Option<i64>,
+    /// `#rgb` か `#rrggbb`
+    pub color: Option<String>,
+    pub waste_weight: Option<f64>,
+}
+
+// 部分更新用（未指定の項目は現在値を維持）。parent_id=0 でトップレベルへ、color="" で色を外す、waste_weight が負なら継承に戻す
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CategoryPatch {
+    pub name: Option<String>,
+    pub parent_id: Option<i64>,
+    pub color: Option<String>,
+    pub waste_weight: Option<f64>,
+}
+
+/// カテゴリごとの集計（total は子孫を含む）。category_id が None の行はカテゴリ未割り当て/未分類
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CategoryRollup {
+    pub category_id: Option<i64>,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub color: Option<String>,
+    pub depth: usize,
+    pub own_seconds: i64,
+    pub total_seconds: i64,
+    /// 秒数 × 実効重み（子孫を含む）
+    pub weighted_waste_seconds: i64,
+}
+
+fn read_category(row: &rusqlite::Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        parent_id: row.get(2)?,
+        color: row.get(3)?,
+        waste_weight: row.get(4)?,
+        effective_waste_weight: 0.0,
+        depth: 0,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+/// 全カテゴリをツリー順（親の直後に子、兄弟は名前順）で返す
+pub fn list_categories(conn: &Connection) -> AppResult<Vec<Category>> {
+    let mut stmt =
+        conn.prepare("SELECT id, name, parent_id, color, waste_weight, created_at, updated_at FROM categories ORDER BY name COLLATE NOCASE, id")?;
+    let all = stmt.query_map([], read_category)?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let ids: HashMap<i64, usize> = all.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let mut children: HashMap<Option<i64>, Vec<usize>> = HashMap::new();
+    for (i, c) in all.iter().enumerate() {
+        // 親が見つからない行はトップレベル扱い
+        let parent = c.parent_id.filter(|p| ids.contains_key(p));
+        children.entry(parent).or_default().push(i);
+    }
+
+    let mut out = Vec::with_capacity(all.len());
+    // (index, depth, 親の実効重み)
+    let mut stack: Vec<(usize, usize, f64)> = children.get(&None).map(|v| v.iter().rev().map(|&i| (i, 0, 0.0)).collect()).unwrap_or_default();
+    while let Some((i, depth, inherited)) = stack.pop() {
+        let mut c = all[i].clone();
+        c.depth = depth;
+        c.effective_waste_weight = c.waste_weight.unwrap_or(inherited);
+        if depth < MAX_DEPTH {
+            if let Some(kids) = children.get(&Some(c.id)) {
+                stack.extend(kids.iter().rev().map(|&k| (k, depth + 1, c.effective_waste_weight)));
+            }
+        }
+        out.push(c);
+    }
+    Ok(out)
+}
+
+pub fn get_category(conn: &Connection, id: i64) -> AppResult<Category> {
+    list_categories(conn)?
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::not_found("category", id))
+}
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::validation("name is required").with_detail("field", "name"));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_color(color: Option<String>) -> AppResult<Option<String>> {
+    let Some(color) = color.map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if !(hex.len() == 3 || hex.len() == 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::validation(format!("invalid color: {}", color)).with_detail("field", "color"));
+    }
+    Ok(Some(color))
+}
+
+fn validate_weight(weight: Option<f64>) -> AppResult<Option<f64>> {
+    match weight {
+        Some(w) if !(0.0..=1.0).contains(&w) => {
+            Err(AppError::validation("waste_weight must be between 0 and 1").with_detail("field", "waste_weight"))
+        }
+        _ => Ok(weight),
+    }
+}
+
+// 親が存在し、自分自身や子孫でないこと（循環を作らない）
+fn validate_parent(conn: &Connection, id: Option<i64>, parent_id: Option<i64>) -> AppResult<()> {
+    let Some(parent_id) = parent_id else { return Ok(()) };
+    let mut cursor = Some(parent_id);
+    for _ in 0..MAX_DEPTH {
+        let Some(current) = cursor else { return Ok(()) };
+        if Some(current) == id {
+            return Err(AppError::validation("a category cannot be moved under itself or its descendants").with_detail("field", "parent_id"));
+        }
+        cursor = conn
+            .query_row("SELECT parent_id FROM categories WHERE id = ?1", params![current], |row| row.get::<_, Option<i64>>(0))
+            .optional()?
+            .ok_or_else(|| AppError::not_found("parent category", current))?;
+    }
+    Err(AppError::validation("category tree is too deep").with_detail("field", "parent_id"))
+}
+
+// 同じ親の下で名前が重ならないこと
+fn ensure_unique_name(conn: &Connection, id: Option<i64>, parent_id: Option<i64>, name: &str) -> AppResult<()> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM categories WHERE parent_id IS ?1 AND name = ?2 COLLATE NOCASE AND id IS NOT ?3",
+            params![parent_id, name, id],
+            |_| Ok(()),
+        )
+        .optional()?;
+    if exists.is_some() {
+        return Err(AppError::conflict(format!("category already exists: {}", name)).with_detail("name", name));
+    }
+    Ok(())
+}
+
+pub fn create_category(conn: &Connection, category: NewCategory) -> AppResult<Category> {
+    let name = validate_name(&category.name)?;
+    let color = validate_color(category.color)?;
+    let waste_weight = validate_weight(category.waste_weight)?;
+    validate_parent(conn, None, category.parent_id)?;
+    ensure_unique_name(conn, None, category.parent_id, &name)?;
+    conn.execute(
+        "INSERT INTO categories(name, parent_id, color, waste_weight) VALUES(?1, ?2, ?3, ?4)",
+        params![name, category.parent_id, color, waste_weight],
+    )?;
+    get_category(conn, conn.last_insert_rowid())
+}
+
+/// 名前/親/色/重みを変更する。親や重みが変わると割り当て済みのアプリ/ドメインの label も更新する
+pub fn update_category(conn: &mut Connection, id: i64, patch: CategoryPatch) -> AppResult<Category> {
+    let current = get_category(conn, id)?;
+    let name = match patch.name {
+        Some(n) => validate_name(&n)?,
+        None => current.name,
+    };
+    let parent_id = match patch.parent_id {
+        Some(0) => None,
+        Some(p) => Some(p),
+        None => current.parent_id,
+    };
+    let color = match patch.color {
+        Some(c) => validate_color(Some(c))?,
+        None => current.color,
+    };
+    let waste_weight = match patch.waste_weight {
+        Some(w) if w < 0.0 => None,
+        Some(w) => validate_weight(Some(w))?,
+        None => current.waste_weight,
+    };
+    validate_parent(conn, Some(id), parent_id)?;
+    ensure_unique_name(conn, Some(id), parent_id, &name)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE categories SET name = ?2, parent_id = ?3, color = ?4, waste_weight = ?5,
+           updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE id = ?1",
+        params![id, name, parent_id, color, waste_weight],
+    )?;
+    sync_labels(&tx)?;
+    tx.commit()?;
+    get_category(conn, id)
+}
+
+/// カテゴリを削除する。子カテゴリと割り当て済みのアプリ/ドメインは削除したカテゴリの親に付け替え、このカテゴリの上限は削除する
+pub fn delete_category(conn: &mut Connection, id: i64) -> AppResult<()> {
+    let current = get_category(conn, id)?;
+    let tx = conn.transaction()?;
+    tx.execute("UPDATE categories SET parent_id = ?2 WHERE parent_id = ?1", params![id, current.parent_id])?;
+    tx.execute("UPDATE waste_categories SET category_id = ?2 WHERE category_id = ?1", params![id, current.parent_id])?;
+    tx.execute("DELETE FROM budgets WHERE target_type = 'category' AND target = ?1", params![id.to_string()])?;
+    tx.execute("DELETE FROM categories WHERE id = ?1", params![id])?;
+    sync_labels(&tx)?;
+    Ok(tx.commit()?)
+}
+
+/// アプリ/ドメイン（waste_categories の行）をカテゴリに割り当てる。None なら割り当てを外す（label はそのまま）
+pub fn assign_category(conn: &Connection, waste_category_id: i64, category_id: Option<i64>) -> AppResult<()> {
+    if let Some(category_id) = category_id {
+        get_category(conn, category_id)?;
+    }
+    let updated = conn
+        .execute(
+            "UPDATE waste_categories SET category_id = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+            params![waste_category_id, category_id],
+        )?;
+    if updated == 0 {
+        return Err(AppError::not_found("waste category", waste_category_id));
+    }
+    sync_labels(conn)?;
+    Ok(())
+}
+
+/// 割り当て済みの waste_categories.label をカテゴリの実効重みに合わせる
+pub fn sync_labels(conn: &Connection) -> AppResult<usize> {
+    let mut updated = 0;
+    for c in list_categories(conn)? {
+        let label = if c.effective_waste_weight >= WASTE_THRESHOLD { "waste" } else { "productive" };
+        updated += conn
+            .execute(
+                "UPDATE waste_categories SET label = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+                 WHERE category_id = ?1 AND label IS NOT ?2",
+                params![c.id, label],
+            )?;
+    }
+    Ok(updated)
+}
+
+/// 期間内の秒数をカテゴリツリーに積み上げる（ツリー順、最後にカテゴリ未割り当て/未分類の行）
+pub fn rollup(conn: &Connection, query: &CategoryTotalsQuery) -> AppResult<Vec<CategoryRollup>> {
+    let categories = list_categories(conn)?;
+    let index: HashMap<i64, usize> = categories.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let mut own = vec![0i64; categories.len()];
+    let mut unassigned = 0i64;
+    for t in aggregate::category_totals(conn, query)? {
+        match t.category_id.and_then(|id| index.get(&id)) {
+            Some(&i) => own[i] += t.seconds,
+            None => unassigned += t.seconds,
+        }
+    }
+
+    let mut total = own.clone();
+    let mut weighted: Vec<f64> = own.iter().zip(&categories).map(|(&s, c)| s as f64 * c.effective_waste_weight).collect();
+    // ツリー順の逆（子 → 親）に足し込む
+    for i in (0..categories.len()).rev() {
+        if let Some(&p) = categories[i].parent_id.and_then(|p| index.get(&p)) {
+            total[p] += total[i];
+            weighted[p] += weighted[i];
+        }
+    }
+
+    let mut out: Vec<CategoryRollup> = categories
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| CategoryRollup {
+            category_id: Some(c.id),
+            name: c.name,
+            parent_id: c.parent_id,
+            color: c.color,
+            depth: c.depth,
+            own_seconds: own[i],
+            total_seconds: total[i],
+            weighted_waste_seconds: weighted[i].round() as i64,
+        })
+        .collect();
+    if unassigned > 0 {
+        out.push(CategoryRollup {
+            category_id: None,
+            name: "Uncategorized".to_string(),
+            parent_id: None,
+            color: None,
+            depth: 0,
+            own_seconds: unassigned,
+            total_seconds: unassigned,
+            weighted_waste_seconds: 0,
+        });
+    }
+    Ok(out)
+}
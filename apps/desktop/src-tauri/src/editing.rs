@@ -0,0 +1,305 @@
+// セッションの手修正と手入力: 分割・結合・識別子/カテゴリ/分類の変更、sessions / browsing_sessions への手入力
+// 変更した行はすべて is_manual=1 にする（レポートで計測値と区別でき、サンプラー/取り込みも上書きしない）
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::import::host_of;
+use crate::rules::{classify_browsing_with, classify_session_with, ensure_category, RuleSet};
+
+// 結合できる前後のセッションの隙間（これ以上離れていれば別のセッションとみなす）
+pub const MAX_MERGE_GAP_SECONDS: i64 = 5 * 60;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EditedSession {
+    pub id: String,
+    pub start_time: String,
+    pub duration_seconds: i64,
+    pub session_key: String,
+    pub category: Option<String>,
+    pub identifier: Option<String>,
+    pub user_state: Option<String>,
+    pub window_title: Option<String>,
+    pub is_manual: bool,
+    /// 分類結果（waste_categories.id）
+    pub category_id: Option<i64>,
+}
+
+/// 識別子/カテゴリ/状態/タイトル/分類の変更（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct SessionPatch {
+    pub identifier: Option<String>,
+    pub category: Option<String>,
+    pub user_state: Option<String>,
+    pub window_title: Option<String>,
+    /// 分類（waste_categories.id）を手で選ぶ。未指定なら識別子かタイトルを変えたときだけルールで分類し直す
+    pub category_id: Option<i64>,
+}
+
+/// 手入力するアプリのセッション（end_time か duration_seconds のどちらかが必要）
+#[derive(Deserialize, Debug, Clone)]
+pub struct ManualSessionEntry {
+    pub start_time: String,
+    pub end_time: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub identifier: String,
+    /// 既定は app
+    pub category: Option<String>,
+    /// 既定は active
+    pub user_state: Option<String>,
+    pub window_title: Option<String>,
+    /// 分類（waste_categories.id）。未指定ならルールで決める
+    pub category_id: Option<i64>,
+}
+
+/// 手入力する閲覧セッション（domain 未指定なら url のホスト名）
+#[derive(Deserialize, Debug, Clone)]
+pub struct ManualBrowsingEntry {
+    pub start_time: String,
+    pub end_time: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub url: String,
+    pub domain: Option<String>,
+    pub title: Option<String>,
+    /// 分類（waste_categories.id）。未指定ならルールで決める
+    pub category_id: Option<i64>,
+}
+
+fn parse_time(value: &str, field: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("invalid {}: {}", field, e))
+}
+
+// JS の toISOString() と同じ形式（ミリ秒 + Z）
+fn format_time(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn session_key(category: &str, identifier: &str, user_state: &str) -> String {
+    format!("category={};identifier={};user_state={}", category, identifier.to_lowercase(), user_state)
+}
+
+fn entry_range(start_time: &str, end_time: &Option<String>, duration_seconds: Option<i64>) -> Result<(DateTime<Utc>, i64), String> {
+    let start = parse_time(start_time, "start_time")?;
+    let duration = match (end_time, duration_seconds) {
+        (Some(end), _) => (parse_time(end, "end_time")? - start).num_seconds(),
+        (None, Some(d)) => d,
+        (None, None) => return Err("end_time or duration_seconds is required".to_string()),
+    };
+    if duration <= 0 {
+        return Err("duration must be positive".to_string());
+    }
+    Ok((start, duration))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn read_session(conn: &Connection, id: &str) -> rusqlite::Result<Option<EditedSession>> {
+    conn.query_row(
+        "SELECT id, start_time, duration_seconds, session_key, category, identifier, user_state, window_title, is_manual, category_id
+         FROM sessions WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(EditedSession {
+                id: row.get(0)?,
+                start_time: row.get(1)?,
+                duration_seconds: row.get(2)?,
+                session_key: row.get(3)?,
+                category: row.get(4)?,
+                identifier: row.get(5)?,
+                user_state: row.get(6)?,
+                window_title: row.get(7)?,
+                is_manual: row.get(8)?,
+                category_id: row.get(9)?,
+            })
+        },
+    )
+    .optional()
+}
+
+fn require_session(conn: &Connection, id: &str) -> AppResult<EditedSession> {
+    read_session(conn, id)?.ok_or_else(|| AppError::not_found("session", id))
+}
+
+fn insert_session(conn: &Connection, s: &EditedSession) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state, window_title, is_manual, category_id)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1, ?9)",
+        params![s.id, s.start_time, s.duration_seconds, s.session_key, s.category, s.identifier, s.user_state, s.window_title, s.category_id],
+    )
+    .map_err(|e| AppError::from(e).on_conflict(|| format!("a session with id {} already exists", s.id)))?;
+    Ok(())
+}
+
+/// セッションを at で2つに分ける。前半は元の id のまま、後半は `${at}-${session_key}` で作る
+pub fn split_session(conn: &mut Connection, id: &str, at: &str) -> AppResult<(EditedSession, EditedSession)> {
+    let tx = conn.transaction()?;
+    let original = require_session(&tx, id)?;
+    let start = parse_time(&original.start_time, "start_time")?;
+    let at = parse_time(at, "at")?;
+    let first_seconds = (at - start).num_seconds();
+    if first_seconds <= 0 || first_seconds >= original.duration_seconds {
+        return Err(AppError::validation("split time must be inside the session"));
+    }
+    tx.execute(
+        "UPDATE sessions SET duration_seconds = ?2, is_manual = 1, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+        params![id, first_seconds],
+    )?;
+    let at_s = format_time(&at);
+    let second = EditedSession {
+        id: format!("{}-{}", at_s, original.session_key),
+        start_time: at_s,
+        duration_seconds: original.duration_seconds - first_seconds,
+        is_manual: true,
+        ..original.clone()
+    };
+    insert_session(&tx, &second)?;
+    let first = require_session(&tx, id)?;
+    tx.commit()?;
+    Ok((first, second))
+}
+
+/// 同じ session_key の連続したセッションを1つにまとめる（最初のセッションの id を残す）
+/// 間に別のセッションが挟まっているもの、MAX_MERGE_GAP_SECONDS より離れているものは結合しない
+pub fn merge_sessions(conn: &mut Connection, ids: &[String]) -> AppResult<EditedSession> {
+    if ids.len() < 2 {
+        return Err(AppError::validation("at least two sessions are required"));
+    }
+    let tx = conn.transaction()?;
+    let mut sessions = Vec::with_capacity(ids.len());
+    for id in ids {
+        sessions.push(require_session(&tx, id)?);
+    }
+    sessions.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+    sessions.dedup_by(|a, b| a.id == b.id);
+    let first = sessions[0].clone();
+    if sessions.iter().any(|s| s.session_key != first.session_key) {
+        return Err(AppError::validation("only sessions with the same identifier and state can be merged"));
+    }
+
+    let start = parse_time(&first.start_time, "start_time")?;
+    let mut end = start;
+    for s in &sessions {
+        let s_start = parse_time(&s.start_time, "start_time")?;
+        if (s_start - end).num_seconds() > MAX_MERGE_GAP_SECONDS {
+            return Err(AppError::validation(format!("session {} is not adjacent to the previous one", s.id)));
+        }
+        end = end.max(s_start + chrono::Duration::seconds(s.duration_seconds));
+    }
+    let last_start = &sessions[sessions.len() - 1].start_time;
+    let placeholders = vec!["?"; sessions.len()].join(", ");
+    let mut binds: Vec<String> = vec![first.start_time.clone(), last_start.clone()];
+    binds.extend(sessions.iter().map(|s| s.id.clone()));
+    let between: i64 = tx
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM sessions WHERE start_time > ? AND start_time < ? AND id NOT IN ({})",
+                placeholders
+            ),
+            rusqlite::params_from_iter(binds.iter()),
+            |row| row.get(0),
+        )?;
+    if between > 0 {
+        return Err(AppError::validation("sessions are not adjacent: other sessions lie between them"));
+    }
+
+    tx.execute(
+        "UPDATE sessions SET duration_seconds = ?2, is_manual = 1, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+        params![first.id, (end - start).num_seconds()],
+    )?;
+    for s in &sessions[1..] {
+        tx.execute("DELETE FROM sessions WHERE id = ?1", params![s.id])?;
+    }
+    let merged = require_session(&tx, &first.id)?;
+    tx.commit()?;
+    Ok(merged)
+}
+
+// 手で選んだ分類があればそれを、なければルールで決めた分類を使う
+fn chosen_category(conn: &Connection, chosen: Option<i64>, classify: impl FnOnce() -> rusqlite::Result<Option<i64>>) -> AppResult<Option<i64>> {
+    match chosen {
+        Some(category_id) => {
+            ensure_category(conn, category_id).map_err(|e| e.with_detail("field", "category_id"))?;
+            Ok(Some(category_id))
+        }
+        None => Ok(classify()?),
+    }
+}
+
+/// 識別子・カテゴリ・分類を書き換える（session_key も組み直す。id は変えない）
+pub fn update_session(conn: &Connection, rules: &RuleSet, id: &str, patch: SessionPatch) -> AppResult<EditedSession> {
+    let current = require_session(conn, id)?;
+    let identifier = non_empty(patch.identifier)
+        .map(|v| v.to_lowercase())
+        .or(current.identifier.clone())
+        .ok_or_else(|| AppError::validation("identifier is required").with_detail("field", "identifier"))?;
+    let category = non_empty(patch.category).or(current.category).unwrap_or_else(|| "app".to_string());
+    let user_state = non_empty(patch.user_state).or(current.user_state).unwrap_or_else(|| "active".to_string());
+    let window_title = patch.window_title.or(current.window_title.clone());
+    // 識別子もタイトルも変わらなければ、前に選んだ分類を残す
+    let unchanged = current.identifier.as_deref() == Some(identifier.as_str()) && current.window_title == window_title;
+    let category_id = match patch.category_id {
+        None if unchanged => current.category_id,
+        chosen => chosen_category(conn, chosen, || classify_session_with(conn, rules, Some(&identifier), window_title.as_deref()))?,
+    };
+    conn.execute(
+        "UPDATE sessions SET session_key = ?2, category = ?3, identifier = ?4, user_state = ?5, window_title = ?6,
+           category_id = ?7, is_manual = 1, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE id = ?1",
+        params![id, session_key(&category, &identifier, &user_state), category, identifier, user_state, window_title, category_id],
+    )?;
+    require_session(conn, id)
+}
+
+pub fn add_manual_session(conn: &Connection, rules: &RuleSet, entry: ManualSessionEntry) -> AppResult<EditedSession> {
+    let (start, duration_seconds) = entry_range(&entry.start_time, &entry.end_time, entry.duration_seconds)?;
+    let identifier = non_empty(Some(entry.identifier))
+        .ok_or_else(|| AppError::validation("identifier is required").with_detail("field", "identifier"))?
+        .to_lowercase();
+    let category = non_empty(entry.category).unwrap_or_else(|| "app".to_string());
+    let user_state = non_empty(entry.user_state).unwrap_or_else(|| "active".to_string());
+    let key = session_key(&category, &identifier, &user_state);
+    let start_time = format_time(&start);
+    let window_title = non_empty(entry.window_title);
+    let category_id =
+        chosen_category(conn, entry.category_id, || classify_session_with(conn, rules, Some(&identifier), window_title.as_deref()))?;
+    let session = EditedSession {
+        id: format!("{}-{}", start_time, key),
+        start_time,
+        duration_seconds,
+        session_key: key,
+        category: Some(category),
+        identifier: Some(identifier),
+        user_state: Some(user_state),
+        window_title,
+        is_manual: true,
+        category_id,
+    };
+    insert_session(conn, &session)?;
+    Ok(session)
+}
+
+/// 閲覧セッションを手入力し、id を返す（id は `${start_time}-${domain}-manual`）
+pub fn add_manual_browsing_session(conn: &Connection, rules: &RuleSet, entry: ManualBrowsingEntry) -> AppResult<String> {
+    let (start, duration_seconds) = entry_range(&entry.start_time, &entry.end_time, entry.duration_seconds)?;
+    let domain = non_empty(entry.domain)
+        .map(|d| d.to_lowercase())
+        .or_else(|| host_of(&entry.url))
+        .ok_or_else(|| AppError::validation("domain is required").with_detail("field", "domain"))?;
+    let start_time = format_time(&start);
+    let id = format!("{}-{}-manual", start_time, domain);
+    let title = non_empty(entry.title);
+    let category_id =
+        chosen_category(conn, entry.category_id, || classify_browsing_with(conn, rules, &domain, &entry.url, title.as_deref()))?;
+    conn.execute(
+        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id, is_manual)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, 1)",
+        params![id, domain, entry.url, title, start_time, duration_seconds, category_id],
+    )
+    .map_err(|e| AppError::from(e).on_conflict(|| format!("a browsing session with id {} already exists", id)))?;
+    Ok(id)
+}
@@ -0,0 +1,204 @@
+// コマンド共通のエラー型: UI が種類（code）で処理を分けられるよう、種類・詳細・表示用メッセージを JSON で返す
+// {"code": "NotFound", "message": "rule not found: 3", "localized": {"ja": "...", "en": "..."}, "details": {"entity": "rule", "id": 3}}
+// message は従来の文字列エラーと同じ英語の詳細（ログ用）、localized は code ごとの表示用メッセージ
+use rusqlite::ErrorCode as SqliteErrorCode;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::ingest_auth::AuthError;
+use crate::migrations::MigrationError;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// 指定した id などのデータがない
+    NotFound,
+    /// 一意制約・外部キーの違反など、既存のデータと衝突する
+    Conflict,
+    /// 入力値・設定値が不正
+    Validation,
+    /// DB が使用中（SQLite のロック待ちのタイムアウト）。時間をおけば成功する
+    DbLocked,
+    /// DB ファイルが壊れている、または SQLite のファイルではない
+    DbCorrupt,
+    /// ファイルの読み書きに失敗した
+    Io,
+    /// 上記以外（想定外のエラー）
+    Internal,
+}
+
+/// 表示用メッセージ（UI の言語で選ぶ）
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalizedMessage {
+    pub ja: &'static str,
+    pub en: &'static str,
+}
+
+impl ErrorCode {
+    pub fn localized(self) -> LocalizedMessage {
+        let (ja, en) = match self {
+            ErrorCode::NotFound => ("指定したデータが見つかりません", "The requested item was not found."),
+            ErrorCode::Conflict => ("既存のデータと競合しています", "The change conflicts with existing data."),
+            ErrorCode::Validation => ("入力内容が正しくありません", "The input is invalid."),
+            ErrorCode::DbLocked => (
+                "データベースが使用中です。しばらくしてからもう一度お試しください",
+                "The database is busy. Please try again in a moment.",
+            ),
+            ErrorCode::DbCorrupt => (
+                "データベースが壊れている可能性があります。バックアップから復元してください",
+                "The database may be corrupted. Please restore it from a backup.",
+            ),
+            ErrorCode::Io => ("ファイルの読み書きに失敗しました", "Failed to read or write a file."),
+            ErrorCode::Internal => ("予期しないエラーが発生しました", "An unexpected error occurred."),
+        };
+        LocalizedMessage { ja, en }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppError {
+    pub code: ErrorCode,
+    /// 英語の詳細（ログ用）
+    pub message: String,
+    /// 対象の種類・id・項目名など（UI での表示の切り替え用）
+    pub details: Map<String, Value>,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> AppError {
+        AppError { code, message: message.into(), details: Map::new() }
+    }
+
+    /// `{entity} not found: {id}`（details に entity と id を入れる）
+    pub fn not_found(entity: &str, id: impl Into<Value>) -> AppError {
+        let id = id.into();
+        let shown = match &id {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        AppError::new(ErrorCode::NotFound, format!("{} not found: {}", entity, shown))
+            .with_detail("entity", entity)
+            .with_detail("id", id)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> AppError {
+        AppError::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> AppError {
+        AppError::new(ErrorCode::Validation, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> AppError {
+        AppError::new(ErrorCode::Internal, message)
+    }
+
+    /// Mutex が壊れた（ロック中に panic した）場合。再試行では直らないので DbLocked ではなく Internal
+    pub fn db_poisoned() -> AppError {
+        AppError::internal("db lock poisoned; restart the app")
+    }
+
+    pub fn with_detail(mut self, key: &str, value: impl Into<Value>) -> AppError {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// 一意制約の違反（Conflict）ならメッセージを分かりやすいものに差し替える
+    pub fn on_conflict(self, message: impl FnOnce() -> String) -> AppError {
+        match self.code {
+            ErrorCode::Conflict => AppError { message: message(), ..self },
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Wire<'a> {
+            code: ErrorCode,
+            message: &'a str,
+            localized: LocalizedMessage,
+            details: &'a Map<String, Value>,
+        }
+        Wire { code: self.code, message: &self.message, localized: self.code.localized(), details: &self.details }.serialize(serializer)
+    }
+}
+
+// 文字列のエラーは入力チェック（設定の validate など）から返るもの
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::validation(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::validation(message)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        let message = e.to_string();
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::new(ErrorCode::NotFound, message),
+            rusqlite::Error::SqliteFailure(failure, _) => {
+                let code = match failure.code {
+                    SqliteErrorCode::ConstraintViolation => ErrorCode::Conflict,
+                    SqliteErrorCode::DatabaseBusy | SqliteErrorCode::DatabaseLocked => ErrorCode::DbLocked,
+                    SqliteErrorCode::DatabaseCorrupt | SqliteErrorCode::NotADatabase => ErrorCode::DbCorrupt,
+                    SqliteErrorCode::CannotOpen
+                    | SqliteErrorCode::SystemIoFailure
+                    | SqliteErrorCode::DiskFull
+                    | SqliteErrorCode::ReadOnly
+                    | SqliteErrorCode::PermissionDenied => ErrorCode::Io,
+                    _ => ErrorCode::Internal,
+                };
+                AppError::new(code, message).with_detail("sqlite_code", failure.extended_code)
+            }
+            _ => AppError::internal(message),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::new(ErrorCode::Io, e.to_string()).with_detail("kind", format!("{:?}", e.kind()))
+    }
+}
+
+impl From<MigrationError> for AppError {
+    fn from(e: MigrationError) -> Self {
+        let message = e.to_string();
+        match e {
+            MigrationError::Sqlite(inner) => AppError { message, ..AppError::from(inner) },
+            MigrationError::NewerSchema { found, supported } => AppError::conflict(message)
+                .with_detail("found", found)
+                .with_detail("supported", supported),
+            MigrationError::InvalidOrder { .. } => AppError::internal(message),
+        }
+    }
+}
+
+impl From<AuthError> for AppError {
+    fn from(e: AuthError) -> Self {
+        let code = match e {
+            AuthError::InvalidPairingCode | AuthError::MissingToken | AuthError::InvalidToken | AuthError::OriginNotAllowed => {
+                ErrorCode::Validation
+            }
+            AuthError::Db(_) => ErrorCode::Internal,
+        };
+        AppError::new(code, e.message())
+    }
+}
@@ -0,0 +1,319 @@
+// ローカルDBのエクスポート（テーブルごとのCSV / 単一JSON / NDJSON）
+// 行は読みながら書き出し（全件をメモリに載せない）、一時ファイルに書いてから置き換える
+// sessions / browsing_sessions にはカテゴリのラベルを解決した列を付ける
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::migrations;
+
+pub const EXPORT_FORMAT_NAME: &str = "wasteday-export";
+pub const EXPORT_FORMAT_VERSION: i64 = 1;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportTable {
+    Sessions,
+    BrowsingSessions,
+    WasteCategories,
+    Domains,
+    UserSettings,
+}
+
+impl ExportTable {
+    pub const ALL: [ExportTable; 5] = [
+        ExportTable::Sessions,
+        ExportTable::BrowsingSessions,
+        ExportTable::WasteCategories,
+        ExportTable::Domains,
+        ExportTable::UserSettings,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportTable::Sessions => "sessions",
+            ExportTable::BrowsingSessions => "browsing_sessions",
+            ExportTable::WasteCategories => "waste_categories",
+            ExportTable::Domains => "domains",
+            ExportTable::UserSettings => "user_settings",
+        }
+    }
+
+    // 期間指定は記録系テーブル（開始時刻）にのみ適用。マスタ系は常に全件
+    fn query(&self) -> &'static str {
+        match self {
+            ExportTable::Sessions => {
+                "SELECT s.id, s.start_time,
+                        strftime('%Y-%m-%dT%H:%M:%fZ', s.start_time, '+' || s.duration_seconds || ' seconds') AS end_time,
+                        s.duration_seconds, s.session_key, s.category, s.identifier, s.user_state, s.window_title,
+                        s.is_manual, s.category_id, wc.label AS category_label
+                 FROM sessions s
+                 LEFT JOIN waste_categories wc ON wc.id = s.category_id AND wc.is_active = 1
+                 WHERE (:since IS NULL OR s.start_time >= :since) AND (:until IS NULL OR s.start_time < :until)
+                 ORDER BY s.start_time ASC, s.id ASC"
+            }
+            ExportTable::BrowsingSessions => {
+                "SELECT b.id, b.domain, b.url, b.title, b.start_time, b.duration_seconds, b.tab_id, b.is_manual, b.category_id,
+                        COALESCE(dc.label, bc.label, wd.label) AS category_label
+                 FROM browsing_sessions b
+                 LEFT JOIN domains d ON d.domain = b.domain AND d.is_active = 1
+                 LEFT JOIN waste_categories dc ON dc.id = d.category_id AND dc.is_active = 1
+                 LEFT JOIN waste_categories bc ON bc.id = b.category_id AND bc.is_active = 1
+                 LEFT JOIN waste_categories wd ON wd.type = 'domain' AND wd.identifier = b.domain AND wd.is_active = 1
+                 WHERE (:since IS NULL OR b.start_time >= :since) AND (:until IS NULL OR b.start_time < :until)
+                 ORDER BY b.start_time ASC, b.id ASC"
+            }
+            ExportTable::WasteCategories => {
+                "SELECT id, type, identifier, label, is_active, created_at, updated_at FROM waste_categories ORDER BY id ASC"
+            }
+            ExportTable::Domains => {
+                "SELECT d.id, d.domain, d.category_id, wc.label AS category_label, d.is_active, d.created_at, d.updated_at
+                 FROM domains d
+                 LEFT JOIN waste_categories wc ON wc.id = d.category_id
+                 ORDER BY d.id ASC"
+            }
+            ExportTable::UserSettings => "SELECT key, value, updated_at FROM user_settings ORDER BY key ASC",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportRequest {
+    pub format: ExportFormat,
+    /// 出力先（CSV はディレクトリ、JSON/NDJSON はファイル）。未指定ならダウンロードフォルダに作成
+    pub path: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// 未指定なら全テーブル
+    pub tables: Option<Vec<ExportTable>>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TableCount {
+    pub table: String,
+    pub rows: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExportResult {
+    pub files: Vec<String>,
+    pub tables: Vec<TableCount>,
+}
+
+fn normalize_time(value: &Option<String>, field: &str) -> Result<Option<String>, String> {
+    value
+        .as_ref()
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v)
+                .map(|t| t.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true))
+                .map_err(|e| format!("invalid {}: {}", field, e))
+        })
+        .transpose()
+}
+
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => json!(i),
+        ValueRef::Real(f) => json!(f),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+        // BLOB 列は持っていないが、来た場合は16進文字列にする
+        ValueRef::Blob(b) => Value::String(b.iter().map(|x| format!("{:02x}", x)).collect()),
+    }
+}
+
+// 1テーブル分の行を列名付きで順に渡す
+fn for_each_row<F>(conn: &Connection, table: ExportTable, since: &Option<String>, until: &Option<String>, mut f: F) -> AppResult<usize>
+where
+    F: FnMut(&[String], Vec<Value>) -> std::io::Result<()>,
+{
+    let mut stmt = conn.prepare(table.query())?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let candidates: [(&str, &dyn ToSql); 2] = [(":since", since), (":until", until)];
+    let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+    for (name, value) in candidates {
+        if stmt.parameter_index(name)?.is_some() {
+            params.push((name, value));
+        }
+    }
+    let mut rows = stmt.query(params.as_slice())?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            values.push(to_json(row.get_ref(i)?));
+        }
+        f(&columns, values)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn table_columns(conn: &Connection, table: ExportTable) -> AppResult<Vec<String>> {
+    let stmt = conn.prepare(table.query())?;
+    Ok(stmt.column_names().iter().map(|c| c.to_string()).collect())
+}
+
+// 表計算ソフトが数式として解釈する先頭文字。文字列の値なら先頭に ' を付けて出力する（import で外す）
+pub const CSV_FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+fn csv_field(value: &Value) -> String {
+    let raw = match value {
+        Value::Null => return String::new(),
+        Value::String(s) if s.starts_with(CSV_FORMULA_PREFIXES) => format!("'{}", s),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if raw.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", raw.replace('"', "\"\""))
+    } else {
+        raw
+    }
+}
+
+fn row_object(columns: &[String], values: Vec<Value>) -> Value {
+    Value::Object(columns.iter().cloned().zip(values).collect())
+}
+
+// 一時ファイルに書き、成功したら置き換える（途中で失敗しても既存ファイルを壊さない）
+fn write_atomically<F>(path: &Path, write: F) -> AppResult<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> AppResult<()>,
+{
+    let tmp = path.with_extension("partial");
+    let result = File::create(&tmp).map_err(AppError::from).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.flush()?;
+        Ok(out.get_ref().sync_all()?)
+    });
+    match result {
+        Ok(()) => Ok(fs::rename(&tmp, path)?),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+// 例: ~/Downloads/wasteday-export-20250101-093000.json（CSV はディレクトリ）
+fn default_path(format: ExportFormat) -> AppResult<PathBuf> {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| AppError::new(ErrorCode::Io, "could not determine an output directory"))?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let name = match format {
+        ExportFormat::Csv => format!("wasteday-export-{}", stamp),
+        ExportFormat::Json => format!("wasteday-export-{}.json", stamp),
+        ExportFormat::Ndjson => format!("wasteday-export-{}.ndjson", stamp),
+    };
+    Ok(dir.join(name))
+}
+
+fn metadata(conn: &Connection, since: &Option<String>, until: &Option<String>) -> AppResult<Value> {
+    Ok(json!({
+        "format": EXPORT_FORMAT_NAME,
+        "version": EXPORT_FORMAT_VERSION,
+        "schema_version": migrations::current_version(conn)?,
+        "exported_at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "since": since,
+        "until": until,
+    }))
+}
+
+pub fn export(conn: &Connection, request: &ExportRequest) -> AppResult<ExportResult> {
+    let since = normalize_time(&request.since, "since")?;
+    let until = normalize_time(&request.until, "until")?;
+    if let (Some(s), Some(u)) = (&since, &until) {
+        if u <= s {
+            return Err(AppError::validation("until must be after since").with_detail("field", "until"));
+        }
+    }
+    let mut tables = request.tables.clone().unwrap_or_else(|| ExportTable::ALL.to_vec());
+    tables.sort();
+    tables.dedup();
+    if tables.is_empty() {
+        return Err(AppError::validation("no tables selected").with_detail("field", "tables"));
+    }
+    let path = match &request.path {
+        Some(p) if !p.trim().is_empty() => PathBuf::from(p),
+        _ => default_path(request.format)?,
+    };
+
+    let mut counts = Vec::new();
+    let mut files = Vec::new();
+    match request.format {
+        ExportFormat::Csv => {
+            fs::create_dir_all(&path)?;
+            for table in &tables {
+                let file = path.join(format!("{}.csv", table.name()));
+                let mut rows = 0;
+                write_atomically(&file, |out| {
+                    // Excel で文字化けしないよう BOM を付ける
+                    out.write_all("\u{feff}".as_bytes())?;
+                    let header = table_columns(conn, *table)?;
+                    write!(out, "{}\r\n", header.join(","))?;
+                    rows = for_each_row(conn, *table, &since, &until, |_, values| {
+                        let line: Vec<String> = values.iter().map(csv_field).collect();
+                        write!(out, "{}\r\n", line.join(","))
+                    })?;
+                    Ok(())
+                })?;
+                counts.push(TableCount { table: table.name().to_string(), rows });
+                files.push(file.to_string_lossy().into_owned());
+            }
+        }
+        ExportFormat::Json => {
+            write_atomically(&path, |out| {
+                let meta = metadata(conn, &since, &until)?;
+                let meta = meta.to_string();
+                // メタ情報の末尾 `}` の代わりに tables を続けて書く
+                write!(out, "{},\"tables\":{{", &meta[..meta.len() - 1])?;
+                for (i, table) in tables.iter().enumerate() {
+                    write!(out, "{}\"{}\":[", if i > 0 { "," } else { "" }, table.name())?;
+                    let mut first = true;
+                    let rows = for_each_row(conn, *table, &since, &until, |columns, values| {
+                        write!(out, "{}{}", if first { "" } else { "," }, row_object(columns, values))?;
+                        first = false;
+                        Ok(())
+                    })?;
+                    write!(out, "]")?;
+                    counts.push(TableCount { table: table.name().to_string(), rows });
+                }
+                Ok(writeln!(out, "}}}}")?)
+            })?;
+            files.push(path.to_string_lossy().into_owned());
+        }
+        ExportFormat::Ndjson => {
+            // 1行目がメタ情報、以降は {"table": ..., "row": {...}} を1行ずつ
+            write_atomically(&path, |out| {
+                let mut meta = metadata(conn, &since, &until)?;
+                meta["type"] = json!("meta");
+                writeln!(out, "{}", meta)?;
+                for table in &tables {
+                    let rows = for_each_row(conn, *table, &since, &until, |columns, values| {
+                        writeln!(out, "{}", json!({ "table": table.name(), "row": row_object(columns, values) }))
+                    })?;
+                    counts.push(TableCount { table: table.name().to_string(), rows });
+                }
+                Ok(())
+            })?;
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(ExportResult { files, tables: counts })
+}
@@ -0,0 +1,689 @@
+// 外部データの取り込み: WasteDay 自身のエクスポート（JSON/NDJSON/CSV）、ActivityWatch のバケットJSON、汎用CSV
+// どの形式も sessions / browsing_sessions の行に変換し、id で重複を判定して1トランザクションで保存する
+// id を持たない行は記録時と同じ規則（`${start_time}-${session_key}` / `${start_time}-${domain}-${tab_id}`）で作るので、
+// 同じファイルを何度取り込んでも重複しない
+use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::error::{AppError, AppResult};
+use crate::export::{CSV_FORMULA_PREFIXES, EXPORT_FORMAT_NAME, EXPORT_FORMAT_VERSION};
+use crate::retention;
+use crate::rules::{classify_browsing_with, classify_session_with, RuleSet};
+
+// 結果に載せるエラーメッセージの上限（件数自体は skipped に数える）
+const MAX_REPORTED_ERRORS: usize = 50;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    /// export_data の出力（JSON / NDJSON / CSV ディレクトリ）
+    Wasteday,
+    /// ActivityWatch のエクスポート（currentwindow / web.tab.current バケット）
+    ActivityWatch,
+    /// ヘッダ付きCSV（列名はエイリアスを許容）
+    Csv,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ImportRequest {
+    /// ファイル（CSV はエクスポートしたディレクトリでも可）
+    pub path: String,
+    /// 未指定なら拡張子と内容から判定
+    pub format: Option<ImportFormat>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableImportCount {
+    pub table: String,
+    pub inserted: usize,
+    pub updated: usize,
+    /// 既存行と同一、変換できなかった、または日別集計にまとめ済みの期間の行
+    pub skipped: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportResult {
+    pub format: ImportFormat,
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub tables: Vec<TableImportCount>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SessionRow {
+    id: String,
+    start_time: String,
+    duration_seconds: i64,
+    session_key: String,
+    category: Option<String>,
+    identifier: Option<String>,
+    user_state: Option<String>,
+    window_title: Option<String>,
+    is_manual: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BrowsingRow {
+    id: String,
+    domain: String,
+    url: String,
+    title: Option<String>,
+    start_time: String,
+    duration_seconds: i64,
+    tab_id: i64,
+    is_manual: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Sessions,
+    BrowsingSessions,
+}
+
+enum Outcome {
+    Inserted,
+    Updated,
+    Unchanged,
+}
+
+pub fn import(conn: &mut Connection, request: &ImportRequest) -> AppResult<ImportResult> {
+    let path = Path::new(&request.path);
+    if !path.exists() {
+        return Err(AppError::not_found("file", path.display().to_string()));
+    }
+    let format = match request.format {
+        Some(f) => f,
+        None => detect_format(path)?,
+    };
+    let tx = conn.transaction()?;
+    let mut importer = Importer::new(&tx)?;
+    match format {
+        ImportFormat::Wasteday if path.is_dir() => import_csv_dir(&mut importer, path)?,
+        ImportFormat::Wasteday if is_ndjson(path) => import_wasteday_ndjson(&mut importer, path)?,
+        ImportFormat::Wasteday => import_wasteday_json(&mut importer, &read_json(path)?)?,
+        ImportFormat::ActivityWatch => import_activitywatch(&mut importer, &read_json(path)?)?,
+        ImportFormat::Csv if path.is_dir() => import_csv_dir(&mut importer, path)?,
+        ImportFormat::Csv => import_csv_file(&mut importer, path)?,
+    }
+    let result = importer.finish(format);
+    tx.commit()?;
+    Ok(result)
+}
+
+fn detect_format(path: &Path) -> AppResult<ImportFormat> {
+    if path.is_dir() {
+        return Ok(ImportFormat::Csv);
+    }
+    if is_ndjson(path) {
+        return Ok(ImportFormat::Wasteday);
+    }
+    if extension(path).as_deref() == Some("csv") {
+        return Ok(ImportFormat::Csv);
+    }
+    let doc = read_json(path)?;
+    if doc.get("format").and_then(Value::as_str) == Some(EXPORT_FORMAT_NAME) {
+        Ok(ImportFormat::Wasteday)
+    } else if doc.get("buckets").is_some() || doc.get("events").is_some() || doc.is_array() {
+        Ok(ImportFormat::ActivityWatch)
+    } else {
+        Err(AppError::validation("unrecognized file format"))
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase())
+}
+
+fn is_ndjson(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("ndjson") | Some("jsonl"))
+}
+
+fn read_json(path: &Path) -> AppResult<Value> {
+    let file = File::open(path)?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| AppError::validation(format!("invalid JSON: {}", e)))
+}
+
+// ====== 行の保存と集計 ======
+struct Importer<'a> {
+    conn: &'a Connection,
+    // 分類ルールは取り込み中に変わらないので1回だけ読み込む
+    rules: RuleSet,
+    // これより前の生データは日別集計にまとめ済みなので取り込まない（二重計上を防ぐ）
+    watermark: Option<String>,
+    sessions: TableImportCount,
+    browsing: TableImportCount,
+    errors: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(conn: &'a Connection) -> AppResult<Self> {
+        Ok(Importer {
+            conn,
+            rules: RuleSet::load(conn)?,
+            watermark: retention::watermark(conn)?,
+            sessions: TableImportCount { table: "sessions".to_string(), ..Default::default() },
+            browsing: TableImportCount { table: "browsing_sessions".to_string(), ..Default::default() },
+            errors: Vec::new(),
+        })
+    }
+
+    fn counts(&mut self, table: Table) -> &mut TableImportCount {
+        match table {
+            Table::Sessions => &mut self.sessions,
+            Table::BrowsingSessions => &mut self.browsing,
+        }
+    }
+
+    fn skip(&mut self, table: Table, context: &str, error: String) {
+        self.counts(table).skipped += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(format!("{}: {}", context, error));
+        }
+    }
+
+    fn record(&mut self, table: Table, outcome: Outcome) {
+        let counts = self.counts(table);
+        match outcome {
+            Outcome::Inserted => counts.inserted += 1,
+            Outcome::Updated => counts.updated += 1,
+            Outcome::Unchanged => counts.skipped += 1,
+        }
+    }
+
+    fn rolled_up(&self, start_time: &str) -> Option<String> {
+        self.watermark
+            .as_deref()
+            .filter(|w| start_time < *w)
+            .map(|w| format!("already rolled up into daily summaries (before {})", w))
+    }
+
+    fn session(&mut self, context: &str, row: Result<SessionRow, String>) -> AppResult<()> {
+        match row.and_then(|r| self.rolled_up(&r.start_time).map_or(Ok(r), Err)) {
+            Ok(row) => {
+                let outcome = upsert_session(self.conn, &self.rules, &row)?;
+                self.record(Table::Sessions, outcome);
+            }
+            Err(e) => self.skip(Table::Sessions, context, e),
+        }
+        Ok(())
+    }
+
+    fn browsing(&mut self, context: &str, row: Result<BrowsingRow, String>) -> AppResult<()> {
+        match row.and_then(|r| self.rolled_up(&r.start_time).map_or(Ok(r), Err)) {
+            Ok(row) => {
+                let outcome = upsert_browsing(self.conn, &self.rules, &row)?;
+                self.record(Table::BrowsingSessions, outcome);
+            }
+            Err(e) => self.skip(Table::BrowsingSessions, context, e),
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, table: Table, context: &str, row: &Map<String, Value>) -> AppResult<()> {
+        match table {
+            Table::Sessions => self.session(context, session_from_row(row)),
+            Table::BrowsingSessions => self.browsing(context, browsing_from_row(row)),
+        }
+    }
+
+    fn finish(self, format: ImportFormat) -> ImportResult {
+        let tables = vec![self.sessions, self.browsing];
+        ImportResult {
+            format,
+            inserted: tables.iter().map(|t| t.inserted).sum(),
+            updated: tables.iter().map(|t| t.updated).sum(),
+            skipped: tables.iter().map(|t| t.skipped).sum(),
+            tables,
+            errors: self.errors,
+        }
+    }
+}
+
+// 既存行と内容が同じなら更新しない（changes() で更新の有無を判定する）。手修正済みの行は上書きせず skipped に数える
+fn upsert_session(conn: &Connection, rules: &RuleSet, row: &SessionRow) -> rusqlite::Result<Outcome> {
+    let exists = conn
+        .query_row("SELECT 1 FROM sessions WHERE id = ?1", params![row.id], |_| Ok(()))
+        .optional()?
+        .is_some();
+    let category_id = classify_session_with(conn, rules, row.identifier.as_deref(), row.window_title.as_deref())?;
+    let changed = conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state, window_title, is_manual, category_id)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(id) DO UPDATE SET start_time=excluded.start_time, duration_seconds=excluded.duration_seconds,
+           session_key=excluded.session_key, category=excluded.category, identifier=excluded.identifier,
+           user_state=excluded.user_state, window_title=COALESCE(excluded.window_title, sessions.window_title),
+           is_manual=excluded.is_manual, category_id=excluded.category_id, updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE sessions.is_manual = 0
+           AND (sessions.start_time IS NOT excluded.start_time
+            OR sessions.duration_seconds IS NOT excluded.duration_seconds
+            OR sessions.session_key IS NOT excluded.session_key
+            OR sessions.is_manual IS NOT excluded.is_manual
+            OR (excluded.window_title IS NOT NULL AND sessions.window_title IS NOT excluded.window_title))",
+        params![
+            row.id,
+            row.start_time,
+            row.duration_seconds,
+            row.session_key,
+            row.category,
+            row.identifier,
+            row.user_state,
+            row.window_title,
+            row.is_manual,
+            category_id
+        ],
+    )?;
+    Ok(outcome(exists, changed))
+}
+
+// category_id は取り込み先の分類ルール/domains から引き直す（元DBの waste_categories の id は使わない）
+fn upsert_browsing(conn: &Connection, rules: &RuleSet, row: &BrowsingRow) -> rusqlite::Result<Outcome> {
+    let exists = conn
+        .query_row("SELECT 1 FROM browsing_sessions WHERE id = ?1", params![row.id], |_| Ok(()))
+        .optional()?
+        .is_some();
+    let category_id = classify_browsing_with(conn, rules, &row.domain, &row.url, row.title.as_deref())?;
+    let changed = conn.execute(
+        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id, is_manual)
+         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT(id) DO UPDATE SET domain=excluded.domain, url=excluded.url, title=excluded.title,
+           start_time=excluded.start_time, duration_seconds=excluded.duration_seconds,
+           category_id=excluded.category_id, tab_id=excluded.tab_id, is_manual=excluded.is_manual,
+           updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE browsing_sessions.is_manual = 0
+           AND (browsing_sessions.domain IS NOT excluded.domain
+            OR browsing_sessions.url IS NOT excluded.url
+            OR browsing_sessions.title IS NOT excluded.title
+            OR browsing_sessions.start_time IS NOT excluded.start_time
+            OR browsing_sessions.duration_seconds IS NOT excluded.duration_seconds
+            OR browsing_sessions.tab_id IS NOT excluded.tab_id
+            OR browsing_sessions.is_manual IS NOT excluded.is_manual)",
+        params![row.id, row.domain, row.url, row.title, row.start_time, row.duration_seconds, category_id, row.tab_id, row.is_manual],
+    )?;
+    Ok(outcome(exists, changed))
+}
+
+fn outcome(exists: bool, changed: usize) -> Outcome {
+    match (exists, changed) {
+        (false, _) => Outcome::Inserted,
+        (true, 0) => Outcome::Unchanged,
+        (true, _) => Outcome::Updated,
+    }
+}
+
+// ====== 行オブジェクト → sessions / browsing_sessions ======
+// エクスポートの行とCSVの行で共通。列名は他ツールのCSVでよく使われる別名も受け付ける
+fn field<'v>(row: &'v Map<String, Value>, names: &[&str]) -> Option<&'v Value> {
+    names.iter().find_map(|n| row.get(*n)).filter(|v| match v {
+        Value::Null => false,
+        Value::String(s) => !s.trim().is_empty(),
+        _ => true,
+    })
+}
+
+fn text(row: &Map<String, Value>, names: &[&str]) -> Option<String> {
+    field(row, names).map(|v| match v {
+        Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn number(row: &Map<String, Value>, names: &[&str]) -> Result<Option<f64>, String> {
+    match field(row, names) {
+        None => Ok(None),
+        Some(Value::Number(n)) => Ok(n.as_f64()),
+        Some(Value::String(s)) => s.trim().parse::<f64>().map(Some).map_err(|_| format!("invalid number: {}", s)),
+        Some(other) => Err(format!("invalid number: {}", other)),
+    }
+}
+
+/// RFC3339 はそのまま UTC に、タイムゾーンなしの日時はローカル時刻として解釈する
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y/%m/%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("invalid timestamp: {}", value))
+}
+
+// 記録時と同じ形式（ミリ秒 + Z）にそろえて id の一致判定をぶれさせない
+fn format_time(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// start と duration（秒）または end から (開始時刻, 秒数) を求める
+fn time_range(row: &Map<String, Value>) -> Result<(DateTime<Utc>, i64), String> {
+    let start = text(row, &["start_time", "start", "started_at", "timestamp"]).ok_or("missing start_time")?;
+    let start = parse_time(&start)?;
+    let duration = match number(row, &["duration_seconds", "duration"])? {
+        Some(d) => d,
+        None => {
+            let end = text(row, &["end_time", "end", "ended_at"]).ok_or("missing duration_seconds or end_time")?;
+            (parse_time(&end)? - start).num_milliseconds() as f64 / 1000.0
+        }
+    };
+    if !duration.is_finite() || duration < 0.0 {
+        return Err(format!("invalid duration: {}", duration));
+    }
+    Ok((start, duration.round() as i64))
+}
+
+// エクスポートの is_manual（0/1）や CSV の true/false を受け付ける。無ければ計測値として扱う
+fn flag(row: &Map<String, Value>, names: &[&str]) -> bool {
+    match field(row, names) {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_f64().is_some_and(|v| v != 0.0),
+        Some(Value::String(s)) => matches!(s.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+        _ => false,
+    }
+}
+
+fn session_key_part<'k>(key: &'k str, name: &str) -> Option<&'k str> {
+    key.split(';').find_map(|p| p.split_once('=').filter(|(k, v)| *k == name && !v.is_empty()).map(|(_, v)| v))
+}
+
+fn session_from_row(row: &Map<String, Value>) -> Result<SessionRow, String> {
+    let (start, duration_seconds) = time_range(row)?;
+    let start_time = format_time(&start);
+    let session_key = match text(row, &["session_key"]) {
+        Some(key) => key,
+        None => {
+            let identifier = text(row, &["identifier", "app", "application", "process"]).ok_or("missing identifier")?;
+            format!(
+                "category={};identifier={};user_state={}",
+                text(row, &["category"]).unwrap_or_else(|| "app".to_string()),
+                identifier.to_lowercase(),
+                text(row, &["user_state"]).unwrap_or_else(|| "active".to_string())
+            )
+        }
+    };
+    let id = text(row, &["id"]).unwrap_or_else(|| format!("{}-{}", start_time, session_key));
+    Ok(SessionRow {
+        id,
+        category: session_key_part(&session_key, "category").map(str::to_string),
+        identifier: session_key_part(&session_key, "identifier").map(|v| v.to_lowercase()),
+        user_state: session_key_part(&session_key, "user_state").map(str::to_string),
+        window_title: text(row, &["window_title", "title"]),
+        is_manual: flag(row, &["is_manual"]),
+        start_time,
+        duration_seconds,
+        session_key,
+    })
+}
+
+pub(crate) fn host_of(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, r)| r)?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+fn browsing_from_row(row: &Map<String, Value>) -> Result<BrowsingRow, String> {
+    let (start, duration_seconds) = time_range(row)?;
+    let start_time = format_time(&start);
+    let url = text(row, &["url"]).unwrap_or_default();
+    let domain = text(row, &["domain", "host"])
+        .map(|d| d.to_lowercase())
+        .or_else(|| host_of(&url))
+        .ok_or("missing domain or url")?;
+    let url = if url.is_empty() { format!("https://{}/", domain) } else { url };
+    let tab_id = number(row, &["tab_id"])?.unwrap_or(0.0) as i64;
+    let id = text(row, &["id"]).unwrap_or_else(|| format!("{}-{}-{}", start_time, domain, tab_id));
+    Ok(BrowsingRow {
+        id,
+        domain,
+        url,
+        title: text(row, &["title"]),
+        start_time,
+        duration_seconds,
+        tab_id,
+        is_manual: flag(row, &["is_manual"]),
+    })
+}
+
+fn table_by_name(name: &str) -> Option<Table> {
+    match name {
+        "sessions" => Some(Table::Sessions),
+        "browsing_sessions" => Some(Table::BrowsingSessions),
+        _ => None,
+    }
+}
+
+// ====== WasteDay のエクスポート ======
+fn check_export_meta(meta: &Value) -> Result<(), String> {
+    if meta.get("format").and_then(Value::as_str) != Some(EXPORT_FORMAT_NAME) {
+        return Err("not a WasteDay export".to_string());
+    }
+    let version = meta.get("version").and_then(Value::as_i64).unwrap_or(0);
+    if version > EXPORT_FORMAT_VERSION {
+        return Err(format!("export format version {} is not supported", version));
+    }
+    Ok(())
+}
+
+// sessions / browsing_sessions 以外のテーブル（カテゴリ・設定）は取り込まない
+fn import_wasteday_json(importer: &mut Importer, doc: &Value) -> AppResult<()> {
+    check_export_meta(doc)?;
+    let tables = doc.get("tables").and_then(Value::as_object).ok_or("missing tables")?;
+    for (name, rows) in tables {
+        let Some(table) = table_by_name(name) else { continue };
+        for (i, row) in rows.as_array().into_iter().flatten().enumerate() {
+            let context = format!("{} row {}", name, i + 1);
+            match row.as_object() {
+                Some(obj) => importer.row(table, &context, obj)?,
+                None => importer.skip(table, &context, "expected an object".to_string()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn import_wasteday_ndjson(importer: &mut Importer, path: &Path) -> AppResult<()> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut lines = reader.lines().enumerate().filter(|(_, l)| l.as_ref().map_or(true, |l| !l.trim().is_empty()));
+    let (_, first) = lines.next().ok_or("empty file")?;
+    let meta: Value = serde_json::from_str(&first?).map_err(|e| format!("line 1: {}", e))?;
+    check_export_meta(&meta)?;
+    for (i, line) in lines {
+        let line = line?;
+        let context = format!("line {}", i + 1);
+        let value: Value = match serde_json::from_str(&line) {
+            Ok(v) => v,
+            Err(e) => return Err(AppError::validation(format!("{}: {}", context, e))),
+        };
+        let Some(table) = value.get("table").and_then(Value::as_str).and_then(table_by_name) else { continue };
+        match value.get("row").and_then(Value::as_object) {
+            Some(obj) => importer.row(table, &context, obj)?,
+            None => importer.skip(table, &context, "missing row".to_string()),
+        }
+    }
+    Ok(())
+}
+
+// ====== ActivityWatch ======
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AwBucketKind {
+    Window,
+    Web,
+    /// 中身を見て判定（バケット情報のないイベント配列）
+    Unknown,
+    Other,
+}
+
+fn aw_bucket_kind(bucket: &Value) -> AwBucketKind {
+    let kind = bucket.get("type").and_then(Value::as_str).unwrap_or("");
+    let id = bucket.get("id").and_then(Value::as_str).unwrap_or("");
+    if kind == "currentwindow" || id.starts_with("aw-watcher-window") {
+        AwBucketKind::Window
+    } else if kind == "web.tab.current" || id.starts_with("aw-watcher-web") {
+        AwBucketKind::Web
+    } else {
+        AwBucketKind::Other
+    }
+}
+
+// 対応形式: {"buckets": {id: bucket}} / {"buckets": [bucket]} / 単一バケット / イベント配列
+fn import_activitywatch(importer: &mut Importer, doc: &Value) -> AppResult<()> {
+    let buckets: Vec<(AwBucketKind, String, &Vec<Value>)> = match doc {
+        Value::Array(events) => vec![(AwBucketKind::Unknown, "events".to_string(), events)],
+        Value::Object(obj) if obj.contains_key("buckets") => {
+            let list: Vec<&Value> = match &obj["buckets"] {
+                Value::Object(map) => map.values().collect(),
+                Value::Array(items) => items.iter().collect(),
+                _ => return Err(AppError::validation("invalid buckets")),
+            };
+            list.into_iter()
+                .filter_map(|b| {
+                    let events = b.get("events")?.as_array()?;
+                    let id = b.get("id").and_then(Value::as_str).unwrap_or("bucket").to_string();
+                    Some((aw_bucket_kind(b), id, events))
+                })
+                .collect()
+        }
+        Value::Object(obj) => {
+            let events = obj.get("events").and_then(Value::as_array).ok_or("missing events")?;
+            let id = obj.get("id").and_then(Value::as_str).unwrap_or("bucket").to_string();
+            vec![(aw_bucket_kind(doc), id, events)]
+        }
+        _ => return Err(AppError::validation("unrecognized ActivityWatch export")),
+    };
+    for (kind, bucket_id, events) in buckets {
+        // afkstatus などは sessions に対応しないので読み飛ばす
+        if kind == AwBucketKind::Other {
+            continue;
+        }
+        for (i, event) in events.iter().enumerate() {
+            let context = format!("{} event {}", bucket_id, i + 1);
+            import_aw_event(importer, kind, &context, event)?;
+        }
+    }
+    Ok(())
+}
+
+fn import_aw_event(importer: &mut Importer, kind: AwBucketKind, context: &str, event: &Value) -> AppResult<()> {
+    let data = event.get("data").and_then(Value::as_object);
+    let kind = match kind {
+        AwBucketKind::Unknown if data.is_some_and(|d| d.contains_key("url")) => AwBucketKind::Web,
+        AwBucketKind::Unknown if data.is_some_and(|d| d.contains_key("app")) => AwBucketKind::Window,
+        other => other,
+    };
+    let table = if kind == AwBucketKind::Web { Table::BrowsingSessions } else { Table::Sessions };
+    let Some(data) = data else {
+        importer.skip(table, context, "missing data".to_string());
+        return Ok(());
+    };
+    // timestamp / duration をイベント直下から、それ以外を data から取る
+    let mut row = data.clone();
+    for key in ["timestamp", "duration"] {
+        if let Some(v) = event.get(key) {
+            row.insert(key.to_string(), v.clone());
+        }
+    }
+    // 長さ 0 のイベント（ハートビートの端数）は記録として意味がないので数えずに捨てる
+    if number(&row, &["duration"]).ok().flatten().is_some_and(|d| d.round() < 1.0) {
+        importer.counts(table).skipped += 1;
+        return Ok(());
+    }
+    match kind {
+        AwBucketKind::Web => importer.browsing(context, browsing_from_row(&row)),
+        AwBucketKind::Window => importer.session(context, session_from_row(&row)),
+        _ => {
+            importer.skip(table, context, "unknown event type".to_string());
+            Ok(())
+        }
+    }
+}
+
+// ====== CSV ======
+// エクスポートしたディレクトリ（sessions.csv / browsing_sessions.csv）
+fn import_csv_dir(importer: &mut Importer, dir: &Path) -> AppResult<()> {
+    let mut found = false;
+    for name in ["sessions.csv", "browsing_sessions.csv"] {
+        let file = dir.join(name);
+        if file.is_file() {
+            import_csv_file(importer, &file)?;
+            found = true;
+        }
+    }
+    if !found {
+        return Err(AppError::validation("no sessions.csv or browsing_sessions.csv in directory"));
+    }
+    Ok(())
+}
+
+// ファイル名がテーブル名ならそれに、そうでなければ domain / url 列の有無でテーブルを決める
+fn import_csv_file(importer: &mut Importer, path: &Path) -> AppResult<()> {
+    let content = fs::read_to_string(path)?;
+    let mut records = parse_csv(content.trim_start_matches('\u{feff}')).into_iter();
+    let header: Vec<String> = records.next().ok_or("empty CSV")?.into_iter().map(|h| h.trim().to_lowercase()).collect();
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let table = table_by_name(stem).unwrap_or(if header.iter().any(|h| h == "domain" || h == "url") {
+        Table::BrowsingSessions
+    } else {
+        Table::Sessions
+    });
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("csv");
+    for (i, record) in records.enumerate() {
+        if record.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        let row: Map<String, Value> = header.iter().cloned().zip(record.into_iter().map(|f| Value::String(unescape_formula(f)))).collect();
+        importer.row(table, &format!("{} row {}", file_name, i + 2), &row)?;
+    }
+    Ok(())
+}
+
+// エクスポート時に数式対策で付けた ' を外す
+fn unescape_formula(field: String) -> String {
+    match field.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(CSV_FORMULA_PREFIXES) => rest.to_string(),
+        _ => field,
+    }
+}
+
+/// RFC 4180 のCSV（引用符内のカンマ・改行・"" エスケープに対応）
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
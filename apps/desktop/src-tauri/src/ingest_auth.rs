@@ -0,0 +1,211 @@
+// 取り込みAPI（127.0.0.1 の HTTP サーバ）のクライアント認証
+// デスクトップアプリが発行したペアリングコードを拡張機能が /api/pair に送るとトークンを払い出す。
+// 以降の取り込みは Authorization: Bearer <token> が必須で、DB にはトークンの SHA-256 だけを保存する
+use chrono::{Duration, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, AppResult};
+
+// ペアリングコードの有効期限と、失効までに許す誤入力回数
+const PAIRING_TTL_MINUTES: i64 = 5;
+const PAIRING_MAX_ATTEMPTS: i64 = 5;
+
+// CORS を許可するブラウザ拡張のオリジン（Webページのオリジンは常に拒否）
+const EXTENSION_SCHEMES: &[&str] = &["chrome-extension://", "moz-extension://", "safari-web-extension://"];
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PairingCode {
+    pub code: String,
+    pub expires_at: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IssuedToken {
+    pub client_id: i64,
+    pub token: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IngestClient {
+    pub id: i64,
+    pub name: String,
+    pub origin: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// Authorization ヘッダがない/形式が違う
+    MissingToken,
+    /// 未登録・失効済みのトークン
+    InvalidToken,
+    /// 拡張機能以外、またはトークンと異なるオリジンからのリクエスト
+    OriginNotAllowed,
+    /// ペアリングコードが違う/期限切れ
+    InvalidPairingCode,
+    Db(String),
+}
+
+impl AuthError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken | AuthError::InvalidPairingCode => 401,
+            AuthError::OriginNotAllowed => 403,
+            AuthError::Db(_) => 500,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AuthError::MissingToken => "missing bearer token".to_string(),
+            AuthError::InvalidToken => "invalid token".to_string(),
+            AuthError::OriginNotAllowed => "origin not allowed".to_string(),
+            AuthError::InvalidPairingCode => "invalid or expired pairing code".to_string(),
+            AuthError::Db(e) => e.clone(),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AuthError {
+    fn from(e: rusqlite::Error) -> Self {
+        AuthError::Db(e.to_string())
+    }
+}
+
+fn now_string() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut buf = [0u8; N];
+    getrandom::getrandom(&mut buf).map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+pub fn is_extension_origin(origin: &str) -> bool {
+    EXTENSION_SCHEMES.iter().any(|s| origin.starts_with(s) && origin.len() > s.len())
+}
+
+/// 新しいペアリングコードを発行する（以前の未使用コードは無効になる）
+pub fn start_pairing(conn: &Connection) -> AppResult<PairingCode> {
+    let raw = random_bytes::<4>().map_err(AppError::internal)?;
+    let code = format!("{:06}", u32::from_le_bytes(raw) % 1_000_000);
+    let expires_at = (Utc::now() + Duration::minutes(PAIRING_TTL_MINUTES)).to_rfc3339_opts(SecondsFormat::Millis, true);
+    conn.execute("DELETE FROM ingest_pairing_codes", [])?;
+    conn.execute(
+        "INSERT INTO ingest_pairing_codes(code, expires_at) VALUES(?1, ?2)",
+        params![code, expires_at],
+    )?;
+    Ok(PairingCode { code, expires_at })
+}
+
+/// ペアリングコードを検証してクライアントを登録し、平文トークンを1度だけ返す
+pub fn complete_pairing(conn: &Connection, code: &str, name: &str, origin: Option<&str>) -> Result<IssuedToken, AuthError> {
+    if let Some(o) = origin {
+        if !is_extension_origin(o) {
+            return Err(AuthError::OriginNotAllowed);
+        }
+    }
+    let now = now_string();
+    let pending: Option<String> = conn
+        .query_row(
+            "SELECT code FROM ingest_pairing_codes WHERE expires_at > ?1 AND attempts < ?2",
+            params![now, PAIRING_MAX_ATTEMPTS],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match pending {
+        Some(expected) if expected == code.trim() => {}
+        Some(_) => {
+            // 総当たり対策: 誤入力を数え、上限に達したコードは使えなくする
+            conn.execute("UPDATE ingest_pairing_codes SET attempts = attempts + 1", [])?;
+            return Err(AuthError::InvalidPairingCode);
+        }
+        None => return Err(AuthError::InvalidPairingCode),
+    }
+
+    let token = to_hex(&random_bytes::<32>().map_err(AuthError::Db)?);
+    let name = if name.trim().is_empty() { "browser extension" } else { name.trim() };
+    conn.execute(
+        "INSERT INTO ingest_clients(name, origin, token_hash) VALUES(?1, ?2, ?3)",
+        params![name, origin, hash_token(&token)],
+    )?;
+    let client_id = conn.last_insert_rowid();
+    conn.execute("DELETE FROM ingest_pairing_codes", [])?;
+    Ok(IssuedToken { client_id, token })
+}
+
+/// Authorization ヘッダと Origin を検証し、クライアントIDを返す
+pub fn authenticate(conn: &Connection, authorization: Option<&str>, origin: Option<&str>) -> Result<i64, AuthError> {
+    let token = authorization
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .ok_or(AuthError::MissingToken)?;
+    let client: Option<(i64, Option<String>)> = conn
+        .query_row(
+            "SELECT id, origin FROM ingest_clients WHERE token_hash = ?1 AND revoked_at IS NULL",
+            params![hash_token(token)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (id, client_origin) = client.ok_or(AuthError::InvalidToken)?;
+    // ブラウザ経由（Origin 付き）の場合はペアリングした拡張機能のオリジンに限る
+    if let Some(o) = origin {
+        if client_origin.as_deref() != Some(o) {
+            return Err(AuthError::OriginNotAllowed);
+        }
+    }
+    conn.execute("UPDATE ingest_clients SET last_used_at = ?1 WHERE id = ?2", params![now_string(), id])?;
+    Ok(id)
+}
+
+/// CORS で許可するオリジン（ペアリング済みで失効していない拡張機能のみ）
+pub fn is_allowed_origin(conn: &Connection, origin: &str) -> bool {
+    if !is_extension_origin(origin) {
+        return false;
+    }
+    conn.query_row(
+        "SELECT 1 FROM ingest_clients WHERE origin = ?1 AND revoked_at IS NULL LIMIT 1",
+        params![origin],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|v| v.is_some())
+    .unwrap_or(false)
+}
+
+pub fn list_clients(conn: &Connection) -> rusqlite::Result<Vec<IngestClient>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, origin, created_at, last_used_at FROM ingest_clients WHERE revoked_at IS NULL ORDER BY id ASC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(IngestClient {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            origin: row.get(2)?,
+            created_at: row.get(3)?,
+            last_used_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn revoke_client(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    let n = conn.execute(
+        "UPDATE ingest_clients SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+        params![now_string(), id],
+    )?;
+    Ok(n > 0)
+}
@@ -0,0 +1,231 @@
+// 取り込みAPIのハンドラ（/api/health, /api/pair, /api/ingest/browsing, /api/ingest/browsing/batch, /api/policy）
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::router::{ApiError, ApiResponse, RequestContext};
+use crate::error::{AppError, AppResult};
+use crate::ingest_auth;
+use crate::policy::{self, PolicyConfig, PolicyQuery};
+use crate::repository::{BrowsingSession, Repository, SqliteRepository};
+use crate::rules::{self, RuleSet};
+
+// バッチ取り込みの上限件数（拡張機能のオフラインキューを数回に分けて送る想定）
+pub const MAX_BATCH_ITEMS: usize = 500;
+// 同じタブ・URL のイベントをこの間隔（秒）以内なら1つのセッションにまとめる（拡張機能の送信間隔 30 秒 + 余裕）
+// ActivityWatch の pulsetime と同じ考え方
+pub const HEARTBEAT_PULSETIME_SECONDS: i64 = 60;
+// 結合先を探す範囲（これより長く続くセッションは想定しない）
+const MERGE_LOOKBACK_HOURS: i64 = 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BrowserData {
+    pub url: String,
+    pub domain: String,
+    pub title: String,
+    pub timestamp: String,
+    pub duration: Option<u64>,
+    pub tab_id: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PairRequest {
+    pub code: String,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchItemStatus {
+    Accepted,
+    Rejected,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub status: BatchItemStatus,
+    pub id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchResult {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+impl BrowserData {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.domain.trim().is_empty() {
+            return Err("domain is required".to_string());
+        }
+        if self.url.trim().is_empty() {
+            return Err("url is required".to_string());
+        }
+        chrono::DateTime::parse_from_rfc3339(&self.timestamp).map_err(|e| format!("invalid timestamp: {}", e))?;
+        Ok(())
+    }
+}
+
+// 結合候補の行（start/end はパース済み）
+struct OpenSession {
+    id: String,
+    start_time: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+fn parse_time(value: &str) -> AppResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| AppError::validation(format!("invalid timestamp: {}", e)).with_detail("field", "timestamp"))
+}
+
+// 同じタブ・URL で、前後 pulsetime を含めて [start, end] と重なる行（開始順、手修正済みの行は除く）
+fn overlapping_sessions(repo: &impl Repository, tab_id: i32, url: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> AppResult<Vec<OpenSession>> {
+    let pulse = Duration::seconds(HEARTBEAT_PULSETIME_SECONDS);
+    let format = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let rows = repo.list_tab_browsing_sessions(tab_id, url, &format(start - Duration::hours(MERGE_LOOKBACK_HOURS)), &format(end + pulse))?;
+    let mut out = Vec::new();
+    for row in rows {
+        let Ok(row_start) = parse_time(&row.start_time) else { continue };
+        let row_end = row_start + Duration::seconds(row.duration_seconds.max(0));
+        if row_end + pulse >= start && row_start - pulse <= end {
+            out.push(OpenSession { id: row.id, start_time: row.start_time, start: row_start, end: row_end });
+        }
+    }
+    Ok(out)
+}
+
+// 分類ルール/domains から category_id を決めて browsing_sessions に保存する。id は `${timestamp}-${domain}-${tab_id}`
+// イベントは [timestamp, timestamp + duration] の区間として扱い、同じタブ・URL の行と pulsetime 以内で重なれば
+// その行の区間を広げる（順不同・重複して届いても結果は同じ）。間をつないだ場合は後ろの行を吸収する。手修正済みの行は上書きせず Conflict を返す
+pub fn upsert_browser_data(conn: &Connection, rules: &RuleSet, b: &BrowserData) -> AppResult<String> {
+    let repo = SqliteRepository::new(conn);
+    let category_id = rules::classify_browsing_with(conn, rules, &b.domain, &b.url, Some(b.title.as_str()))?;
+    let tab_id_val: i32 = b.tab_id.unwrap_or(0) as i32;
+    let start = parse_time(&b.timestamp)?;
+    let end = start + Duration::seconds(b.duration.unwrap_or(0) as i64);
+
+    let merged = overlapping_sessions(&repo, tab_id_val, &b.url, start, end)?;
+    if let Some(first) = merged.first() {
+        let latest_end = merged.iter().map(|m| m.end).max().unwrap_or(first.end);
+        let (start_time, merged_start) = if start < first.start { (b.timestamp.clone(), start) } else { (first.start_time.clone(), first.start) };
+        let merged_end = latest_end.max(end);
+        for other in &merged[1..] {
+            repo.delete_browsing_session(&other.id)?;
+        }
+        // タイトル・分類は最も新しい区間のイベントで更新する（遅れて届いた古いイベントでは戻さない）
+        let latest = (end >= latest_end).then_some((b.title.as_str(), category_id));
+        repo.extend_browsing_session(&first.id, &start_time, (merged_end - merged_start).num_seconds(), latest)?;
+        return Ok(first.id.clone());
+    }
+
+    let record_id = format!("{}-{}-{}", b.timestamp, b.domain, tab_id_val);
+    repo.upsert_browsing_session(&BrowsingSession {
+        id: record_id.clone(),
+        domain: b.domain.clone(),
+        url: b.url.clone(),
+        title: Some(b.title.clone()),
+        start_time: b.timestamp.clone(),
+        duration_seconds: b.duration.unwrap_or(0) as i64,
+        category_id,
+        tab_id: Some(tab_id_val),
+        is_manual: false,
+    })?;
+    // 同じ id の手修正済みの行があると何も書かれない
+    if conn.changes() == 0 {
+        return Err(AppError::conflict(format!("browsing session {} was edited manually and is not overwritten", record_id))
+            .with_detail("id", record_id));
+    }
+    Ok(record_id)
+}
+
+// 1トランザクションでまとめて保存。不正な要素は rejected として返し、残りは保存する
+// 要素ごとにセーブポイントを切り、途中で失敗した要素の変更（結合で消した行など）は巻き戻す
+pub fn ingest_browser_batch(conn: &mut Connection, rules: &RuleSet, items: Vec<serde_json::Value>) -> AppResult<BatchResult> {
+    let mut tx = conn.transaction()?;
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let parsed = serde_json::from_value::<BrowserData>(item)
+            .map_err(|e| e.to_string())
+            .and_then(|b| b.validate().map(|_| b));
+        let outcome = match parsed {
+            Ok(b) => {
+                let sp = tx.savepoint()?;
+                match upsert_browser_data(&sp, rules, &b) {
+                    Ok(id) => {
+                        sp.commit()?;
+                        Ok(id)
+                    }
+                    // commit せずに drop するとセーブポイントまで巻き戻る
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        results.push(match outcome {
+            Ok(id) => BatchItemResult { index, status: BatchItemStatus::Accepted, id: Some(id), error: None },
+            Err(e) => BatchItemResult { index, status: BatchItemStatus::Rejected, id: None, error: Some(e) },
+        });
+    }
+    tx.commit()?;
+    let accepted = results.iter().filter(|r| r.status == BatchItemStatus::Accepted).count();
+    Ok(BatchResult { accepted, rejected: results.len() - accepted, results })
+}
+
+// 拡張機能はポート探索時にこのレスポンスで WasteDay のサーバかを判定する
+pub fn health(ctx: &mut RequestContext) -> Result<ApiResponse, ApiError> {
+    Ok(ApiResponse::ok(&json!({ "status": "ok", "app": "wasteday", "port": ctx.port })))
+}
+
+pub fn pair(ctx: &mut RequestContext) -> Result<ApiResponse, ApiError> {
+    let req: PairRequest = ctx.request.json()?;
+    let issued = ingest_auth::complete_pairing(
+        ctx.conn,
+        &req.code,
+        req.name.as_deref().unwrap_or(""),
+        ctx.request.origin.as_deref(),
+    )?;
+    log::info!("Paired ingest client {}", issued.client_id);
+    Ok(ApiResponse::ok(&issued))
+}
+
+pub fn ingest_browsing(ctx: &mut RequestContext) -> Result<ApiResponse, ApiError> {
+    let data: BrowserData = ctx.request.json()?;
+    data.validate().map_err(ApiError::bad_request)?;
+    let rules = ctx.rules.get(ctx.conn)?;
+    let tx = ctx.conn.transaction()?;
+    let id = upsert_browser_data(&tx, &rules, &data)?;
+    tx.commit()?;
+    Ok(ApiResponse::ok(&json!({ "id": id })))
+}
+
+pub fn ingest_browsing_batch(ctx: &mut RequestContext) -> Result<ApiResponse, ApiError> {
+    let items: Vec<serde_json::Value> = ctx
+        .request
+        .json()
+        .map_err(|_| ApiError::bad_request("expected a JSON array"))?;
+    if items.len() > MAX_BATCH_ITEMS {
+        return Err(ApiError::new(413, format!("batch must contain at most {} items", MAX_BATCH_ITEMS)));
+    }
+    // ルールはバッチごとに1回だけ取る
+    let rules = ctx.rules.get(ctx.conn)?;
+    let result = ingest_browser_batch(ctx.conn, &rules, items)?;
+    Ok(ApiResponse::ok(&result))
+}
+
+// 拡張機能がページを開いたときに問い合わせる（block / warn の表示は拡張機能側で行う）
+pub fn check_policy(ctx: &mut RequestContext) -> Result<ApiResponse, ApiError> {
+    let query: PolicyQuery = ctx.request.json()?;
+    if query.domain.trim().is_empty() {
+        return Err(ApiError::bad_request("domain is required"));
+    }
+    let config = PolicyConfig::load(ctx.conn);
+    let rules = ctx.rules.get(ctx.conn)?;
+    let decision = policy::decide(ctx.conn, &rules, &config, &query, chrono::Local::now())?;
+    Ok(ApiResponse::ok(&decision))
+}
@@ -0,0 +1,250 @@
+// 取り込みHTTPサーバ（ブラウザ拡張からの受信）: 待ち受け設定・起動/停止・ルーティング
+// 設定は user_settings（ingest_server_config）に JSON で保存し、ポートが使用中なら次の空きポートで起動する
+// Tauri に依存しないため、インメモリDBとエフェメラルポートで結合テストできる
+pub mod handlers;
+pub mod router;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tiny_http::{Method, Server};
+
+use crate::error::AppResult;
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+
+use router::{Access, Router};
+
+/// サーバと Tauri のコマンドで共有する接続
+pub type SharedConnection = Arc<Mutex<Connection>>;
+
+pub const SERVER_STATUS_EVENT: &str = "ingest:server_status";
+
+const CONFIG_SETTING_KEY: &str = "ingest_server_config";
+// 指定ポートから数えて試すポート数（拡張機能もこの範囲を探索する）
+pub const PORT_FALLBACK_RANGE: u16 = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IngestServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+}
+
+impl Default for IngestServerConfig {
+    fn default() -> Self {
+        Self { bind_address: "127.0.0.1".to_string(), port: 5606 }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct IngestServerConfigPatch {
+    pub bind_address: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl IngestServerConfig {
+    pub fn apply(&self, patch: IngestServerConfigPatch) -> Result<IngestServerConfig, String> {
+        let next = IngestServerConfig {
+            bind_address: patch.bind_address.map(|a| a.trim().to_string()).unwrap_or_else(|| self.bind_address.clone()),
+            port: patch.port.unwrap_or(self.port),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.ip()?;
+        if self.port < 1024 {
+            return Err("port must be between 1024 and 65535".to_string());
+        }
+        Ok(())
+    }
+
+    fn ip(&self) -> Result<IpAddr, String> {
+        self.bind_address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid bind_address: {}", self.bind_address))
+    }
+
+    pub fn load(conn: &Connection) -> IngestServerConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, IngestServerConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+/// 指定ポートから順に空きポートを探して待ち受ける（port=0 は OS に任せる）
+pub fn bind(config: &IngestServerConfig) -> Result<(Server, u16), String> {
+    let ip = config.ip()?;
+    let attempts = if config.port == 0 { 1 } else { PORT_FALLBACK_RANGE };
+    let mut last_error = String::new();
+    for offset in 0..attempts {
+        let Some(port) = config.port.checked_add(offset) else { break };
+        match Server::http(SocketAddr::new(ip, port)) {
+            Ok(server) => {
+                let bound = server.server_addr().to_ip().map(|a| a.port()).unwrap_or(port);
+                return Ok((server, bound));
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    Err(format!(
+        "no free port in {}-{} on {}: {}",
+        config.port,
+        config.port.saturating_add(attempts - 1),
+        config.bind_address,
+        last_error
+    ))
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route(Method::Get, "/api/health", Access::Public, handlers::health)
+        .route(Method::Post, "/api/pair", Access::Public, handlers::pair)
+        .route(Method::Post, "/api/ingest/browsing", Access::Client, handlers::ingest_browsing)
+        .route(Method::Post, "/api/ingest/browsing/batch", Access::Client, handlers::ingest_browsing_batch)
+        .route(Method::Post, "/api/policy", Access::Client, handlers::check_policy)
+}
+
+/// unblock されるまでリクエストを処理する
+pub fn serve(server: &Server, db: &SharedConnection) {
+    let router = router();
+    let port = server.server_addr().to_ip().map(|a| a.port());
+    for req in server.incoming_requests() {
+        router.handle(req, db, port);
+    }
+}
+
+/// 受付スレッドで稼働中のサーバ
+pub struct RunningServer {
+    pub port: u16,
+    server: Option<Arc<Server>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RunningServer {
+    /// 待ち受けを止め、ソケットが解放されるまで待つ（同じポートで再起動できるように）
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let Some(server) = self.server.take() else { return };
+        let addr = server.server_addr().to_ip();
+        // incoming_requests() のループを抜けさせる
+        server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        // Server の drop で受付スレッドが終了し、ソケットは少し遅れて閉じる
+        drop(server);
+        if let Some(addr) = addr {
+            for _ in 0..40 {
+                if TcpListener::bind(addr).is_ok() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// 設定に従って待ち受けを始め、別スレッドで処理する（port=0 ならエフェメラルポート）
+pub fn start(config: &IngestServerConfig, db: SharedConnection) -> Result<RunningServer, String> {
+    let (server, port) = bind(config)?;
+    let server = Arc::new(server);
+    let worker = server.clone();
+    let thread = std::thread::Builder::new()
+        .name("ingest-server".to_string())
+        .spawn(move || serve(&worker, &db))
+        .map_err(|e| e.to_string())?;
+    Ok(RunningServer { port, server: Some(server), thread: Some(thread) })
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerState {
+    Starting,
+    Running,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IngestServerStatus {
+    pub state: ServerState,
+    pub bind_address: String,
+    pub requested_port: u16,
+    /// 実際に待ち受けているポート（フォールバック時は requested_port と異なる）
+    pub port: Option<u16>,
+    pub error: Option<String>,
+}
+
+pub struct IngestServerState {
+    pub config: Mutex<IngestServerConfig>,
+    status: Mutex<IngestServerStatus>,
+    running: Mutex<Option<RunningServer>>,
+}
+
+impl IngestServerState {
+    pub fn new(config: IngestServerConfig) -> Self {
+        Self {
+            status: Mutex::new(IngestServerStatus {
+                state: ServerState::Starting,
+                bind_address: config.bind_address.clone(),
+                requested_port: config.port,
+                port: None,
+                error: None,
+            }),
+            config: Mutex::new(config),
+            running: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> IngestServerStatus {
+        self.status.lock().map(|s| s.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// 現在の設定で待ち受けを（再）開始し、新しい状態を返す
+    pub fn restart(&self, db: SharedConnection) -> IngestServerStatus {
+        let config = self.config.lock().map(|c| c.clone()).unwrap_or_default();
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(old) = running.take() {
+            old.stop();
+        }
+        let status = match start(&config, db) {
+            Ok(server) => {
+                let port = server.port;
+                *running = Some(server);
+                IngestServerStatus {
+                    state: ServerState::Running,
+                    bind_address: config.bind_address.clone(),
+                    requested_port: config.port,
+                    port: Some(port),
+                    error: None,
+                }
+            }
+            Err(e) => IngestServerStatus {
+                state: ServerState::Failed,
+                bind_address: config.bind_address.clone(),
+                requested_port: config.port,
+                port: None,
+                error: Some(e),
+            },
+        };
+        if let Ok(mut s) = self.status.lock() {
+            *s = status.clone();
+        }
+        status
+    }
+}
@@ -0,0 +1,251 @@
+// 小さなルータと共通ミドルウェア（CORS・トークン認証・JSONエラー）
+// ハンドラは DB ロック済みのコンテキストを受け取り、ApiResponse か ApiError を返すだけにする
+use rusqlite::Connection;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response};
+
+use super::SharedConnection;
+use crate::error::{AppError, ErrorCode};
+use crate::ingest_auth;
+use crate::rules::RuleCache;
+
+// リクエストボディの上限（バッチ 500 件でも収まる大きさ）
+const MAX_BODY_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    pub path: String,
+    pub origin: Option<String>,
+    pub authorization: Option<String>,
+    pub body: String,
+}
+
+impl ApiRequest {
+    fn from_http(req: &mut Request) -> Result<ApiRequest, ApiError> {
+        let mut body = String::new();
+        req.as_reader()
+            .take(MAX_BODY_BYTES + 1)
+            .read_to_string(&mut body)
+            .map_err(|e| ApiError::bad_request(format!("failed to read body: {}", e)))?;
+        if body.len() as u64 > MAX_BODY_BYTES {
+            return Err(ApiError::new(413, "request body too large"));
+        }
+        // クエリ文字列はルーティングに使わない
+        let path = req.url().split('?').next().unwrap_or("").to_string();
+        Ok(ApiRequest {
+            method: req.method().clone(),
+            path,
+            origin: header_value(req, "Origin"),
+            authorization: header_value(req, "Authorization"),
+            body,
+        })
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, ApiError> {
+        serde_json::from_str(&self.body).map_err(|e| ApiError::bad_request(format!("invalid JSON: {}", e)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Option<Value>,
+}
+
+impl ApiResponse {
+    pub fn ok<T: Serialize>(body: &T) -> ApiResponse {
+        ApiResponse { status: 200, body: Some(serde_json::to_value(body).unwrap_or(Value::Null)) }
+    }
+
+    pub fn no_content() -> ApiResponse {
+        ApiResponse { status: 204, body: None }
+    }
+}
+
+/// エラーは常に {"error": "..."} で返す
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: u16, message: impl Into<String>) -> ApiError {
+        ApiError { status, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> ApiError {
+        ApiError::new(400, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> ApiError {
+        ApiError::new(500, message)
+    }
+
+    fn into_response(self) -> ApiResponse {
+        ApiResponse { status: self.status, body: Some(json!({ "error": self.message })) }
+    }
+}
+
+impl From<ingest_auth::AuthError> for ApiError {
+    fn from(e: ingest_auth::AuthError) -> Self {
+        ApiError::new(e.status_code(), e.message())
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        let status = match e.code {
+            ErrorCode::NotFound => 404,
+            ErrorCode::Conflict => 409,
+            ErrorCode::Validation => 400,
+            ErrorCode::DbLocked => 503,
+            _ => 500,
+        };
+        ApiError::new(status, e.message)
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(e: rusqlite::Error) -> Self {
+        ApiError::internal(e.to_string())
+    }
+}
+
+/// ハンドラに渡すコンテキスト（DB はリクエスト単位でロック済み）
+pub struct RequestContext<'a> {
+    pub request: &'a ApiRequest,
+    pub conn: &'a mut Connection,
+    /// 実際に待ち受けているポート
+    pub port: Option<u16>,
+    /// 認証済みクライアント（Access::Client のルートのみ）
+    pub client_id: Option<i64>,
+    /// 分類ルール（リクエストをまたいで使い回す）
+    pub rules: &'a RuleCache,
+}
+
+pub type Handler = fn(&mut RequestContext) -> Result<ApiResponse, ApiError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// 未ペアリングの拡張機能も呼べる（health, pair）。CORS は拡張機能のオリジンなら許可
+    Public,
+    /// Bearer トークン必須。CORS はペアリング済みの拡張機能のオリジンのみ許可
+    Client,
+}
+
+pub struct Route {
+    pub method: Method,
+    pub path: &'static str,
+    pub access: Access,
+    pub handler: Handler,
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    rules: RuleCache,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, method: Method, path: &'static str, access: Access, handler: Handler) -> Self {
+        self.routes.push(Route { method, path, access, handler });
+        self
+    }
+
+    /// ミドルウェア（CORS/認証）を通してハンドラを呼び、(レスポンス, CORS で返すオリジン) を返す
+    pub fn dispatch(&self, request: &ApiRequest, conn: &mut Connection, port: Option<u16>) -> (ApiResponse, Option<String>) {
+        let matching: Vec<&Route> = self.routes.iter().filter(|r| r.path == request.path).collect();
+        let Some(first) = matching.first() else {
+            return (ApiError::new(404, "not found").into_response(), None);
+        };
+
+        // CORS: ブラウザからのリクエスト（Origin 付き）はルートの公開範囲に応じて許可
+        let cors_origin = match &request.origin {
+            Some(origin) => {
+                let allowed = match first.access {
+                    Access::Public => ingest_auth::is_extension_origin(origin),
+                    Access::Client => ingest_auth::is_allowed_origin(conn, origin),
+                };
+                if !allowed {
+                    return (ApiError::new(403, "origin not allowed").into_response(), None);
+                }
+                Some(origin.clone())
+            }
+            None => None,
+        };
+
+        if request.method == Method::Options {
+            return (ApiResponse::no_content(), cors_origin);
+        }
+        let Some(route) = matching.iter().find(|r| r.method == request.method) else {
+            return (ApiError::new(405, "method not allowed").into_response(), cors_origin);
+        };
+
+        let client_id = match route.access {
+            Access::Client => match ingest_auth::authenticate(conn, request.authorization.as_deref(), request.origin.as_deref()) {
+                Ok(id) => Some(id),
+                Err(e) => return (ApiError::from(e).into_response(), cors_origin),
+            },
+            Access::Public => None,
+        };
+
+        let mut ctx = RequestContext { request, conn, port, client_id, rules: &self.rules };
+        let response = (route.handler)(&mut ctx).unwrap_or_else(|e| {
+            if e.status >= 500 {
+                log::error!("{} {} failed: {}", request.method, request.path, e.message);
+            }
+            e.into_response()
+        });
+        (response, cors_origin)
+    }
+
+    /// tiny_http のリクエストを処理して応答する
+    /// ボディの読み込みと応答の書き込みはロックの外で行い、DB はディスパッチの間だけロックする
+    pub fn handle(&self, mut req: Request, db: &SharedConnection, port: Option<u16>) {
+        let (response, cors_origin) = match ApiRequest::from_http(&mut req) {
+            Ok(request) => match db.lock() {
+                Ok(mut conn) => self.dispatch(&request, &mut conn, port),
+                Err(_) => (ApiError::internal("db lock poisoned").into_response(), None),
+            },
+            Err(e) => (e.into_response(), None),
+        };
+        let _ = req.respond(to_http(response, cors_origin.as_deref()));
+    }
+}
+
+fn header_value(req: &Request, name: &str) -> Option<String> {
+    req.headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn to_http(response: ApiResponse, cors_origin: Option<&str>) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut headers: Vec<(&str, &str)> = Vec::new();
+    if response.body.is_some() {
+        headers.push(("Content-Type", "application/json"));
+    }
+    let body = response.body.map(|b| b.to_string()).unwrap_or_default();
+    // 許可したオリジンにだけ CORS ヘッダを付ける（ワイルドカードは使わない）
+    if let Some(origin) = cors_origin {
+        headers.push(("Access-Control-Allow-Origin", origin));
+        headers.push(("Access-Control-Allow-Methods", "GET, POST, OPTIONS"));
+        headers.push(("Access-Control-Allow-Headers", "Content-Type, Authorization"));
+        headers.push(("Vary", "Origin"));
+    }
+    let mut resp = Response::from_string(body).with_status_code(response.status);
+    for (name, value) in headers {
+        if let Ok(h) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            resp.add_header(h);
+        }
+    }
+    resp
+}
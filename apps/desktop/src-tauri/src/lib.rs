@@ -1,86 +1,74 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use serde::{Serialize, Deserialize};
 use log::{info, error};
-use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId};
-use windows::Win32::Foundation::HWND;
-use windows::Win32::UI::Input::KeyboardAndMouse::GetLastInputInfo;
-use windows::Win32::System::SystemInformation::GetTickCount;
-use windows::Win32::Foundation::BOOL;
-use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
-use windows::Win32::System::ProcessStatus::K32GetModuleFileNameExW;
-use windows::Win32::Foundation::CloseHandle;
-use std::path::Path;
-use tauri::{AppHandle, Manager, State, tray::{TrayIconBuilder, TrayIconEvent, TrayIcon}};
+use tauri::{AppHandle, Emitter, Manager, State, tray::{TrayIconBuilder, TrayIconEvent, TrayIcon}};
 use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_updater::UpdaterExt;
-use rusqlite::{Connection, params};
-use std::sync::Mutex;
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
 use std::fs;
-use std::io::Read;
-use tiny_http::{Server, Response, Method};
-
-#[derive(Serialize, Debug, Default)]
-pub struct ForegroundInfo {
-    pub process_id: u32,
-    pub exe: String,
-    pub window_title: String,
-}
-
-#[tauri::command]
-fn get_foreground_info() -> ForegroundInfo {
-    unsafe {
-        let hwnd: HWND = GetForegroundWindow();
-        let mut pid: u32 = 0;
-        let _tid = GetWindowThreadProcessId(hwnd, Some(&mut pid));
-
-        let mut title_buf: [u16; 512] = [0; 512];
-        let len = GetWindowTextW(hwnd, &mut title_buf);
-        let title = String::from_utf16_lossy(&title_buf[..len as usize]);
-
-        // exe 解決
-        let mut exe_name = String::from("unknown.exe");
-        if pid != 0 {
-            if let Ok(hproc) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
-                if !hproc.is_invalid() {
-                    let mut buf: [u16; 260] = [0; 260];
-                    let len = K32GetModuleFileNameExW(hproc, None, &mut buf);
-                    if len > 0 {
-                        let full = String::from_utf16_lossy(&buf[..len as usize]);
-                        if let Some(name) = Path::new(&full).file_name().and_then(|s| s.to_str()) {
-                            exe_name = name.to_string();
-                        } else {
-                            exe_name = full;
-                        }
-                    }
-                    let _ = CloseHandle(hproc);
-                }
-            }
-        }
 
-        ForegroundInfo { process_id: pid, exe: exe_name, window_title: title }
-    }
+use error::{AppError, AppResult, ErrorCode};
+use repository::{BrowsingSession, BrowsingSessionsQuery, Domain, Repository, Session, SessionsQuery, SqliteRepository, WasteCategory};
+
+pub mod aggregate;
+pub mod backup;
+pub mod browser_title;
+pub mod budgets;
+pub mod categories;
+pub mod editing;
+pub mod error;
+pub mod export;
+pub mod import;
+pub mod ingest_auth;
+pub mod ingest_server;
+pub mod migrations;
+pub mod platform;
+pub mod policy;
+pub mod repository;
+pub mod retention;
+pub mod rules;
+pub mod sampler;
+pub mod timeline;
+pub mod trash;
+
+pub use platform::ForegroundInfo;
+
+// OSごとの前面ウィンドウ/アイドル取得実装
+struct Activity(Box<dyn platform::ActivityProvider>);
+
+#[tauri::command]
+fn get_foreground_info(activity: State<Activity>) -> ForegroundInfo {
+    activity.0.foreground_info()
 }
 
 #[tauri::command]
-fn get_idle_seconds() -> u64 {
-    unsafe {
-        #[repr(C)]
-        struct LASTINPUTINFO {
-            cb_size: u32,
-            dw_time: u32,
-        }
+fn get_idle_seconds(activity: State<Activity>) -> u64 {
+    activity.0.idle_seconds()
+}
 
-        let mut li = LASTINPUTINFO { cb_size: std::mem::size_of::<LASTINPUTINFO>() as u32, dw_time: 0 };
-        let ok: BOOL = std::mem::transmute(GetLastInputInfo(std::mem::transmute(&mut li)));
-        if ok.as_bool() {
-            let now = GetTickCount() as u64;
-            let last = li.dw_time as u64;
-            let diff = if now >= last { now - last } else { 0 };
-            diff / 1000
-        } else {
-            0
-        }
-    }
+// ====== sampler commands ======
+#[tauri::command]
+fn sampler_get_status(state: State<sampler::SamplerState>) -> sampler::SamplerStatus {
+    state.status()
+}
+
+#[tauri::command]
+fn sampler_update_config(
+    db: State<Db>,
+    state: State<sampler::SamplerState>,
+    config: sampler::SamplerConfigPatch,
+) -> AppResult<sampler::SamplerConfig> {
+    let mut current = state.config.lock().map_err(|_| AppError::internal("sampler lock poisoned"))?;
+    let next = current.apply(config)?;
+    let conn = db.0.lock().map_err(|_| AppError::db_poisoned())?;
+    next.save(&conn)?;
+    *current = next.clone();
+    Ok(next)
+}
+
+#[tauri::command]
+fn sampler_set_running(state: State<sampler::SamplerState>, running: bool) {
+    state.set_running(running);
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -88,6 +76,7 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_http::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
             // 既存インスタンスがある場合に呼ばれる: ウィンドウを前面化
@@ -97,24 +86,83 @@ pub fn run() {
                 let _ = w.set_focus();
             }
         }))
+        .manage(Activity(platform::default_provider()))
         .invoke_handler(tauri::generate_handler![
             get_foreground_info,
             get_idle_seconds,
+            sampler_get_status,
+            sampler_update_config,
+            sampler_set_running,
             db_get_user_setting,
             db_set_user_setting,
             db_upsert_session,
             db_get_sessions,
             db_delete_session,
+            db_split_session,
+            db_merge_sessions,
+            db_update_session,
+            db_add_manual_session,
+            db_add_manual_browsing_session,
             db_list_waste_categories,
             db_upsert_waste_category,
             db_delete_waste_category,
+            categories_list,
+            categories_create,
+            categories_update,
+            categories_delete,
+            categories_assign,
             db_upsert_browsing_session,
             db_get_browsing_sessions,
             db_delete_browsing_session,
+            db_bulk_delete,
+            trash_list,
+            trash_restore,
+            trash_purge,
+            trash_get_config,
+            trash_update_config,
             db_upsert_domain,
             db_get_domains,
             db_classify_domain,
+            db_reclassify_sessions,
             db_reclassify_browsing_sessions,
+            rules_list,
+            rules_create,
+            rules_update,
+            rules_delete,
+            rules_test,
+            browser_title_get_config,
+            browser_title_update_config,
+            browser_title_parse,
+            db_aggregate_waste,
+            db_aggregate_categories,
+            db_unified_timeline,
+            timeline_get_config,
+            timeline_update_config,
+            export_data,
+            import_data,
+            backup_database,
+            restore_database,
+            backup_list,
+            backup_get_config,
+            backup_update_config,
+            retention_get_config,
+            retention_update_config,
+            retention_run,
+            budgets_list,
+            budgets_create,
+            budgets_update,
+            budgets_delete,
+            budgets_status,
+            budgets_get_config,
+            budgets_update_config,
+            policy_get_config,
+            policy_update_config,
+            policy_check,
+            ingest_start_pairing,
+            ingest_list_clients,
+            ingest_revoke_client,
+            ingest_get_server_status,
+            ingest_update_server_config,
             check_for_updates,
             install_update,
             exit_app
@@ -204,11 +252,20 @@ pub fn run() {
                 }
             };
             
-            if let Err(e) = apply_schema(&mut conn) {
-                error!("Failed to apply database schema: {}", e);
-                return Err(e.into());
+            match migrations::migrate(&mut conn) {
+                Ok(version) => info!("Database initialized successfully (schema version {})", version),
+                Err(e) => {
+                    error!("Failed to migrate database schema: {}", e);
+                    return Err(e.into());
+                }
+            }
+
+            // 保持ポリシーで消した分を少しずつ解放できるようにする（既存DBは初回だけフル VACUUM）
+            match retention::enable_incremental_vacuum(&conn) {
+                Ok(true) => info!("Switched database to incremental auto_vacuum"),
+                Ok(false) => {}
+                Err(e) => error!("Failed to enable incremental auto_vacuum: {}", e),
             }
-            info!("Database initialized successfully");
             
             // 自動起動かどうかを判定
             let args: Vec<String> = std::env::args().collect();
@@ -220,7 +277,20 @@ pub fn run() {
             let is_auto_start = is_auto_start_by_args || is_auto_start_by_env;
             info!("Is auto start: {}", is_auto_start);
             
-            app.manage(Db(Mutex::new(conn)));
+            let sampler_config = sampler::SamplerConfig::load(&conn);
+            let ingest_server_config = ingest_server::IngestServerConfig::load(&conn);
+            let backup_config = backup::BackupConfig::load(&conn);
+            app.manage(Db(Arc::new(Mutex::new(conn))));
+            app.manage(rules::RuleCache::default());
+            app.manage(ingest_server::IngestServerState::new(ingest_server_config));
+            app.manage(backup::BackupState::new(db_path.clone(), app_dir.join("backups"), backup_config));
+            spawn_backup_scheduler(app.handle().clone());
+            spawn_retention_job(app.handle().clone());
+            spawn_budget_checker(app.handle().clone());
+
+            // バックグラウンドのサンプラーを開始（webview の状態に関係なく記録を続ける）
+            app.manage(sampler::SamplerState::new(sampler_config));
+            sampler::spawn(app.handle().clone());
             
             if let Some(window) = app.get_webview_window("main") {
                 info!("Main window found");
@@ -323,119 +393,8 @@ pub fn run() {
                 }
             };
             info!("Application setup completed successfully");
-            // 軽量HTTPサーバ起動（127.0.0.1:5606）: /api/ingest/browsing
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn_blocking(move || {
-                if let Ok(server) = Server::http("127.0.0.1:5606") {
-                    info!("HTTP ingest server started on http://127.0.0.1:5606");
-                    for mut req in server.incoming_requests() {
-                        let url = req.url().to_string();
-                        let method = req.method().clone();
-                        // CORS preflight
-                        if method == Method::Options && url == "/api/ingest/browsing" {
-                            let mut resp = Response::empty(204);
-                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Origin"[..],
-                                &b"*"[..],
-                            ).unwrap());
-                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Methods"[..],
-                                &b"POST, OPTIONS"[..],
-                            ).unwrap());
-                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Headers"[..],
-                                &b"Content-Type"[..],
-                            ).unwrap());
-                            let _ = req.respond(resp);
-                            continue;
-                        }
-                        if method == Method::Get && url == "/api/health" {
-                            let mut resp = Response::from_string("ok").with_status_code(200);
-                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Origin"[..],
-                                &b"*"[..],
-                            ).unwrap());
-                            let _ = req.respond(resp);
-                        } else if method == Method::Post && url == "/api/ingest/browsing" {
-                            let mut body = String::new();
-                            let _ = req.as_reader().read_to_string(&mut body);
-                            match serde_json::from_str::<BrowserData>(&body) {
-                                Ok(b) => {
-                                    // DB保存: domains から category_id を取得し、browsing_sessions を UPSERT
-                                    if let Some(db) = app_handle.try_state::<Db>() {
-                                        if let Ok(conn_guard) = db.0.lock() {
-                                            // 既存の domains からカテゴリ取得
-                                            let mut category_id: Option<i64> = None;
-                                            if let Ok(mut stmt) = conn_guard.prepare("SELECT category_id FROM domains WHERE domain = ?1 AND is_active = 1") {
-                                                if let Ok(val) = stmt.query_row(params![b.domain.clone()], |row| row.get(0)) { category_id = val; }
-                                            }
-
-                                            let tab_id_val: i32 = b.tab_id.unwrap_or(0) as i32;
-                                            let record_id = format!("{}-{}-{}", b.timestamp, b.domain, tab_id_val);
-                                            let _ = conn_guard.execute(
-                                                "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id) \
-                                                 VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
-                                                 ON CONFLICT(id) DO UPDATE SET \
-                                                   domain=excluded.domain, url=excluded.url, title=excluded.title, \
-                                                   duration_seconds=excluded.duration_seconds, category_id=excluded.category_id, \
-                                                   updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
-                                                params![
-                                                    record_id,
-                                                    b.domain,
-                                                    b.url,
-                                                    b.title,
-                                                    b.timestamp,
-                                                    (b.duration.unwrap_or(0) as i64),
-                                                    category_id,
-                                                    tab_id_val,
-                                                ],
-                                            );
-                                            let mut resp = Response::from_string("ok").with_status_code(200);
-                                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                                &b"Access-Control-Allow-Origin"[..],
-                                                &b"*"[..],
-                                            ).unwrap());
-                                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                                &b"Access-Control-Allow-Headers"[..],
-                                                &b"Content-Type"[..],
-                                            ).unwrap());
-                                            let _ = req.respond(resp);
-                                            continue;
-                                        }
-                                    }
-                                    let mut resp = Response::from_string("db error").with_status_code(500);
-                                    let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                        &b"Access-Control-Allow-Origin"[..],
-                                        &b"*"[..],
-                                    ).unwrap());
-                                    let _ = req.respond(resp);
-                                }
-                                Err(_) => {
-                                    let mut resp = Response::from_string("bad request").with_status_code(400);
-                                    let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                        &b"Access-Control-Allow-Origin"[..],
-                                        &b"*"[..],
-                                    ).unwrap());
-                                    let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                        &b"Access-Control-Allow-Headers"[..],
-                                        &b"Content-Type"[..],
-                                    ).unwrap());
-                                    let _ = req.respond(resp);
-                                }
-                            }
-                        } else {
-                            let mut resp = Response::from_string("not found").with_status_code(404);
-                            let _ = resp.add_header(tiny_http::Header::from_bytes(
-                                &b"Access-Control-Allow-Origin"[..],
-                                &b"*"[..],
-                            ).unwrap());
-                            let _ = req.respond(resp);
-                        }
-                    }
-                } else {
-                    error!("Failed to start HTTP ingest server");
-                }
-            });
+            // 取り込みHTTPサーバ起動（既定 127.0.0.1:5606、使用中なら次の空きポート）
+            start_ingest_server(app.handle());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -446,420 +405,719 @@ pub fn run() {
         .expect("error while running tauri application");
 }
 
-struct Db(Mutex<Connection>);
+// ====== ingest server ======
+// 現在の設定で取り込みHTTPサーバを（再）起動し、状態をUIに通知する
+fn start_ingest_server(app: &AppHandle) {
+    let state = app.state::<ingest_server::IngestServerState>();
+    let db = app.state::<Db>();
+    let status = state.restart(db.0.clone());
+    match (status.port, &status.error) {
+        (Some(port), _) => info!("HTTP ingest server started on http://{}:{}", status.bind_address, port),
+        (None, Some(e)) => error!("Failed to start HTTP ingest server: {}", e),
+        (None, None) => {}
+    }
+    if let Err(e) = app.emit(ingest_server::SERVER_STATUS_EVENT, &status) {
+        error!("Failed to emit ingest server status: {}", e);
+    }
+}
+
+struct Db(ingest_server::SharedConnection);
 
 #[tauri::command]
-async fn check_for_updates(app: AppHandle) -> Result<Option<String>, String> {
+async fn check_for_updates(app: AppHandle) -> AppResult<Option<String>> {
     // 開発モードではアップデートチェックを無効化
     if cfg!(debug_assertions) {
         return Ok(None);
     }
     
-    let updater = app.updater().map_err(|e| e.to_string())?;
+    let updater = app.updater().map_err(|e| AppError::internal(e.to_string()))?;
     match updater.check().await {
         Ok(Some(update)) => {
             Ok(Some(update.version.to_string()))
         }
         Ok(None) => Ok(None),
-        Err(e) => Err(e.to_string()),
+        Err(e) => Err(AppError::internal(e.to_string())),
     }
 }
 
 #[tauri::command]
-async fn install_update(app: AppHandle) -> Result<(), String> {
+async fn install_update(app: AppHandle) -> AppResult<()> {
     // 開発モードではアップデートインストールを無効化
     if cfg!(debug_assertions) {
-        return Err("Updates are disabled in development mode".to_string());
+        return Err(AppError::conflict("Updates are disabled in development mode"));
     }
     
-    let updater = app.updater().map_err(|e| e.to_string())?;
+    let updater = app.updater().map_err(|e| AppError::internal(e.to_string()))?;
     match updater.check().await {
         Ok(Some(update)) => {
-            update.download_and_install(|_, _| {}, || {}).await.map_err(|e| e.to_string())?;
+            update.download_and_install(|_, _| {}, || {}).await.map_err(|e| AppError::internal(e.to_string()))?;
             app.exit(0);
             Ok(())
         }
-        Ok(None) => Err("No update available".to_string()),
-        Err(e) => Err(e.to_string()),
+        Ok(None) => Err(AppError::new(ErrorCode::NotFound, "No update available")),
+        Err(e) => Err(AppError::internal(e.to_string())),
     }
 }
 
 #[tauri::command]
-async fn exit_app(app: AppHandle) -> Result<(), String> {
+async fn exit_app(app: AppHandle) -> AppResult<()> {
     app.exit(0);
     Ok(())
 }
 
-fn apply_schema(conn: &mut Connection) -> rusqlite::Result<()> {
-    // Minimal schema (sessions, waste_categories, user_settings, browsing_sessions, domains)
-    let sql = r#"
-    PRAGMA foreign_keys = ON;
-    CREATE TABLE IF NOT EXISTS sessions (
-      id TEXT PRIMARY KEY,
-      start_time TEXT NOT NULL,
-      duration_seconds INTEGER NOT NULL,
-      session_key TEXT NOT NULL,
-      created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
-    );
-    CREATE INDEX IF NOT EXISTS idx_sessions_start_time ON sessions(start_time);
-    CREATE TABLE IF NOT EXISTS waste_categories (
-      id INTEGER PRIMARY KEY AUTOINCREMENT,
-      type TEXT NOT NULL,
-      identifier TEXT NOT NULL,
-      label TEXT NOT NULL,
-      is_active INTEGER NOT NULL DEFAULT 1,
-      created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      UNIQUE(type, identifier)
-    );
-    CREATE TABLE IF NOT EXISTS user_settings (
-      key TEXT PRIMARY KEY,
-      value TEXT NOT NULL,
-      updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
-    );
-    CREATE TABLE IF NOT EXISTS browsing_sessions (
-      id TEXT PRIMARY KEY,
-      domain TEXT NOT NULL,
-      url TEXT NOT NULL,
-      title TEXT,
-      start_time TEXT NOT NULL,
-      duration_seconds INTEGER DEFAULT 0,
-      category_id INTEGER,
-      tab_id INTEGER,
-      created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      FOREIGN KEY (category_id) REFERENCES waste_categories (id)
-    );
-    CREATE INDEX IF NOT EXISTS idx_browsing_sessions_domain ON browsing_sessions(domain);
-    CREATE INDEX IF NOT EXISTS idx_browsing_sessions_start_time ON browsing_sessions(start_time);
-    CREATE TABLE IF NOT EXISTS domains (
-      id INTEGER PRIMARY KEY AUTOINCREMENT,
-      domain TEXT UNIQUE NOT NULL,
-      category_id INTEGER,
-      is_active INTEGER NOT NULL DEFAULT 1,
-      created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
-      FOREIGN KEY (category_id) REFERENCES waste_categories (id)
-    );
-    CREATE INDEX IF NOT EXISTS idx_domains_domain ON domains(domain);
-    "#;
-    conn.execute_batch(sql)?;
-    Ok(())
+// ====== sessions commands ======
+#[tauri::command]
+fn db_upsert_session(state: State<Db>, rule_cache: State<rules::RuleCache>, session: Session) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let rules = rule_cache.get(&conn)?;
+    SqliteRepository::with_rules(&conn, &rules).upsert_session(&session)
 }
 
-// ====== Data models (serde) ======
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Session {
-    id: String,
-    start_time: String,
-    duration_seconds: i64,
-    session_key: String,
+#[tauri::command]
+fn db_get_sessions(state: State<Db>, query: SessionsQuery) -> AppResult<Vec<Session>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).list_sessions(&query)
+}
+
+#[tauri::command]
+fn db_delete_session(state: State<Db>, id: String) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).delete_session(&id)
+}
+
+// ====== manual edits ======
+#[tauri::command]
+fn db_split_session(state: State<Db>, id: String, at: String) -> AppResult<Vec<editing::EditedSession>> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let (first, second) = editing::split_session(&mut conn, &id, &at)?;
+    Ok(vec![first, second])
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WasteCategory {
-    id: Option<i64>,
-    r#type: String,
-    identifier: String,
-    label: String,       // "waste" | "productive"
-    is_active: bool,
+#[tauri::command]
+fn db_merge_sessions(state: State<Db>, ids: Vec<String>) -> AppResult<editing::EditedSession> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    editing::merge_sessions(&mut conn, &ids)
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct BrowsingSession {
+#[tauri::command]
+fn db_update_session(
+    state: State<Db>,
+    rule_cache: State<rules::RuleCache>,
     id: String,
-    domain: String,
-    url: String,
-    title: Option<String>,
-    start_time: String,
-    duration_seconds: i64,
-    category_id: Option<i64>,
-    tab_id: Option<i32>,
+    patch: editing::SessionPatch,
+) -> AppResult<editing::EditedSession> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    editing::update_session(&conn, &rule_cache.get(&conn)?, &id, patch)
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Domain {
-    id: Option<i64>,
-    domain: String,
-    category_id: Option<i64>,
-    is_active: bool,
+#[tauri::command]
+fn db_add_manual_session(
+    state: State<Db>,
+    rule_cache: State<rules::RuleCache>,
+    entry: editing::ManualSessionEntry,
+) -> AppResult<editing::EditedSession> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    editing::add_manual_session(&conn, &rule_cache.get(&conn)?, entry)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct BrowserData {
-    url: String,
-    domain: String,
-    title: String,
-    timestamp: String,
-    duration: Option<u64>,
-    tab_id: Option<u32>,
+#[tauri::command]
+fn db_add_manual_browsing_session(
+    state: State<Db>,
+    rule_cache: State<rules::RuleCache>,
+    entry: editing::ManualBrowsingEntry,
+) -> AppResult<String> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    editing::add_manual_browsing_session(&conn, &rule_cache.get(&conn)?, entry)
 }
 
-// ====== sessions commands ======
+// ====== waste_categories commands ======
 #[tauri::command]
-fn db_upsert_session(state: State<Db>, session: Session) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute(
-        "INSERT INTO sessions(id, start_time, duration_seconds, session_key) VALUES(?1, ?2, ?3, ?4)
-         ON CONFLICT(id) DO UPDATE SET start_time=excluded.start_time, duration_seconds=excluded.duration_seconds, session_key=excluded.session_key, updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
-        params![session.id, session.start_time, session.duration_seconds, session.session_key],
-    ).map_err(|e| e.to_string())?;
-    Ok(())
+fn db_list_waste_categories(state: State<Db>) -> AppResult<Vec<WasteCategory>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).list_waste_categories()
 }
 
-#[derive(Deserialize)]
-struct SessionsQuery { since: Option<String>, until: Option<String> }
-
-#[tauri::command]
-fn db_get_sessions(state: State<Db>, query: SessionsQuery) -> Result<Vec<Session>, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut sql = String::from("SELECT id, start_time, duration_seconds, session_key FROM sessions");
-    let mut clauses: Vec<&str> = Vec::new();
-    let mut binds: Vec<String> = Vec::new();
-    if let Some(s) = query.since.as_ref() { clauses.push("start_time >= ?"); binds.push(s.clone()); }
-    if let Some(u) = query.until.as_ref() { clauses.push("start_time < ?"); binds.push(u.clone()); }
-    if !clauses.is_empty() { sql.push_str(" WHERE "); sql.push_str(&clauses.join(" AND ")); }
-    sql.push_str(" ORDER BY start_time ASC");
-    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-    let rows = stmt.query_map(rusqlite::params_from_iter(binds.iter()), |row| {
-        Ok(Session {
-            id: row.get(0)?,
-            start_time: row.get(1)?,
-            duration_seconds: row.get(2)?,
-            session_key: row.get(3)?,
-        })
-    }).map_err(|e| e.to_string())?;
-    let mut out = Vec::new();
-    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
-    Ok(out)
+// category_id 未指定なら既存の割り当てを維持
+#[tauri::command]
+fn db_upsert_waste_category(state: State<Db>, cat: WasteCategory) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).upsert_waste_category(&cat)
 }
 
 #[tauri::command]
-fn db_delete_session(state: State<Db>, id: String) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute("DELETE FROM sessions WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
-    Ok(())
+fn db_delete_waste_category(state: State<Db>, id: i64) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).delete_waste_category(id)
 }
 
-// ====== waste_categories commands ======
+// ====== categories (hierarchy) ======
 #[tauri::command]
-fn db_list_waste_categories(state: State<Db>) -> Result<Vec<WasteCategory>, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut stmt = conn.prepare("SELECT id, type, identifier, label, is_active FROM waste_categories WHERE is_active IN (0,1) ORDER BY type, identifier")
-        .map_err(|e| e.to_string())?;
-    let rows = stmt.query_map([], |row| {
-        Ok(WasteCategory {
-            id: row.get(0)?,
-            r#type: row.get(1)?,
-            identifier: row.get(2)?,
-            label: row.get(3)?,
-            is_active: {
-                let v: i64 = row.get(4)?; v != 0
-            },
-        })
-    }).map_err(|e| e.to_string())?;
-    let mut out = Vec::new();
-    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
-    Ok(out)
+fn categories_list(state: State<Db>) -> AppResult<Vec<categories::Category>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::list_categories(&conn)
 }
 
 #[tauri::command]
-fn db_upsert_waste_category(state: State<Db>, cat: WasteCategory) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute(
-        "INSERT INTO waste_categories(type, identifier, label, is_active) VALUES(?1, ?2, ?3, ?4)
-         ON CONFLICT(type, identifier) DO UPDATE SET label=excluded.label, is_active=excluded.is_active, updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
-        params![cat.r#type, cat.identifier, cat.label, if cat.is_active {1} else {0}],
-    ).map_err(|e| e.to_string())?;
-    Ok(())
+fn categories_create(state: State<Db>, category: categories::NewCategory) -> AppResult<categories::Category> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::create_category(&conn, category)
 }
 
 #[tauri::command]
-fn db_delete_waste_category(state: State<Db>, id: i64) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute("DELETE FROM waste_categories WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
-    Ok(())
+fn categories_update(state: State<Db>, id: i64, patch: categories::CategoryPatch) -> AppResult<categories::Category> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::update_category(&mut conn, id, patch)
 }
 
 #[tauri::command]
-fn db_get_user_setting(state: State<Db>, key: String) -> Result<Option<String>, String> {
-    let conn_guard = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut stmt = conn_guard.prepare("SELECT value FROM user_settings WHERE key = ?1").map_err(|e| e.to_string())?;
-    let mut rows = stmt.query(params![key]).map_err(|e| e.to_string())?;
-    if let Some(row) = rows.next().map_err(|e| e.to_string())? {
-        let value: String = row.get(0).map_err(|e| e.to_string())?;
-        Ok(Some(value))
-    } else {
-        Ok(None)
-    }
+fn categories_delete(state: State<Db>, id: i64) -> AppResult<()> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::delete_category(&mut conn, id)
 }
 
 #[tauri::command]
-fn db_set_user_setting(state: State<Db>, key: String, value: String) -> Result<(), String> {
-    let conn_guard = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn_guard
-        .execute(
-            "INSERT INTO user_settings(key, value, updated_at) VALUES(?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ','now'))
-             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
-            params![key, value],
-        )
-        .map_err(|e| e.to_string())?;
-    Ok(())
+fn categories_assign(state: State<Db>, waste_category_id: i64, category_id: Option<i64>) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::assign_category(&conn, waste_category_id, category_id)
+}
+
+#[tauri::command]
+fn db_get_user_setting(state: State<Db>, key: String) -> AppResult<Option<String>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).get_setting(&key)
+}
+
+#[tauri::command]
+fn db_set_user_setting(state: State<Db>, key: String, value: String) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).set_setting(&key, &value)
 }
 
 // ====== browsing_sessions commands ======
 #[tauri::command]
-fn db_upsert_browsing_session(state: State<Db>, session: BrowsingSession) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute(
-        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id) 
-         VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-         ON CONFLICT(id) DO UPDATE SET 
-         domain=excluded.domain, url=excluded.url, title=excluded.title, 
-         duration_seconds=excluded.duration_seconds, category_id=excluded.category_id, 
-         updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
-        params![
-            session.id, 
-            session.domain, 
-            session.url, 
-            session.title, 
-            session.start_time, 
-            session.duration_seconds, 
-            session.category_id,
-            session.tab_id
-        ],
-    ).map_err(|e| e.to_string())?;
-    Ok(())
+fn db_upsert_browsing_session(state: State<Db>, session: BrowsingSession) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).upsert_browsing_session(&session)
 }
 
-#[derive(Deserialize)]
-struct BrowsingSessionsQuery { 
-    since: Option<String>, 
-    until: Option<String>,
-    domain: Option<String>,
+#[tauri::command]
+fn db_get_browsing_sessions(state: State<Db>, query: BrowsingSessionsQuery) -> AppResult<Vec<BrowsingSession>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).list_browsing_sessions(&query)
 }
 
 #[tauri::command]
-fn db_get_browsing_sessions(state: State<Db>, query: BrowsingSessionsQuery) -> Result<Vec<BrowsingSession>, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut sql = String::from("SELECT id, domain, url, title, start_time, duration_seconds, category_id, tab_id FROM browsing_sessions");
-    let mut clauses: Vec<&str> = Vec::new();
-    let mut binds: Vec<String> = Vec::new();
-    
-    if let Some(s) = query.since.as_ref() { 
-        clauses.push("start_time >= ?"); 
-        binds.push(s.clone()); 
-    }
-    if let Some(u) = query.until.as_ref() { 
-        clauses.push("start_time < ?"); 
-        binds.push(u.clone()); 
+fn db_delete_browsing_session(state: State<Db>, id: String) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).delete_browsing_session(&id)
+}
+
+// ====== bulk delete / trash ======
+#[tauri::command]
+fn db_bulk_delete(state: State<Db>, request: trash::BulkDeleteRequest) -> AppResult<trash::BulkDeleteResult> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    trash::bulk_delete(&mut conn, &request)
+}
+
+#[tauri::command]
+fn trash_list(state: State<Db>) -> AppResult<Vec<trash::TrashBatch>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(trash::list_batches(&conn, &trash::TrashConfig::load(&conn))?)
+}
+
+#[tauri::command]
+fn trash_restore(state: State<Db>, batch_id: i64) -> AppResult<trash::TrashRestoreResult> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    trash::restore_batch(&mut conn, batch_id)
+}
+
+// batch_id 未指定なら期限切れのバッチだけを削除
+#[tauri::command]
+fn trash_purge(state: State<Db>, batch_id: Option<i64>) -> AppResult<usize> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(trash::purge(&conn, &trash::TrashConfig::load(&conn), chrono::Utc::now(), batch_id)?)
+}
+
+#[tauri::command]
+fn trash_get_config(state: State<Db>) -> AppResult<trash::TrashConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(trash::TrashConfig::load(&conn))
+}
+
+#[tauri::command]
+fn trash_update_config(state: State<Db>, config: trash::TrashConfigPatch) -> AppResult<trash::TrashConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = trash::TrashConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// ====== reclassify sessions by latest rules / waste_categories ======
+#[tauri::command]
+fn db_reclassify_sessions(state: State<Db>, since: Option<String>, until: Option<String>) -> AppResult<u64> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(rules::reclassify_sessions(&mut conn, since.as_deref(), until.as_deref())?)
+}
+
+// ====== reclassify browsing_sessions by latest rules / domains mapping ======
+#[tauri::command]
+fn db_reclassify_browsing_sessions(state: State<Db>, since: Option<String>, until: Option<String>) -> AppResult<u64> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(rules::reclassify_browsing_sessions(&mut conn, since.as_deref(), until.as_deref())?)
+}
+
+// ====== classification rules ======
+#[tauri::command]
+fn rules_list(state: State<Db>) -> AppResult<Vec<rules::ClassificationRule>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    rules::list_rules(&conn)
+}
+
+#[tauri::command]
+fn rules_create(state: State<Db>, rule: rules::NewRule) -> AppResult<rules::ClassificationRule> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    rules::create_rule(&conn, rule)
+}
+
+#[tauri::command]
+fn rules_update(state: State<Db>, id: i64, patch: rules::RulePatch) -> AppResult<rules::ClassificationRule> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    rules::update_rule(&conn, id, patch)
+}
+
+#[tauri::command]
+fn rules_delete(state: State<Db>, id: i64) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    rules::delete_rule(&conn, id)
+}
+
+// 保存済みの有効なルールで試しに分類する（target=app なら subject は識別子、domain ならドメイン）
+#[tauri::command]
+fn rules_test(
+    state: State<Db>,
+    target: rules::RuleTarget,
+    subject: String,
+    url: Option<String>,
+    title: Option<String>,
+) -> AppResult<Option<rules::RuleMatch>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let set = rules::RuleSet::load(&conn)?;
+    Ok(match target {
+        rules::RuleTarget::App => set.match_app(&subject, title.as_deref()),
+        rules::RuleTarget::Domain => {
+            let url = url.unwrap_or_else(|| format!("https://{}/", subject));
+            set.match_browsing(&subject, &url, title.as_deref())
+        }
+    })
+}
+
+// ====== browser title commands ======
+#[tauri::command]
+fn browser_title_get_config(state: State<Db>) -> AppResult<browser_title::BrowserTitleConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(browser_title::BrowserTitleConfig::load(&conn))
+}
+
+#[tauri::command]
+fn browser_title_update_config(state: State<Db>, config: browser_title::BrowserTitleConfigPatch) -> AppResult<browser_title::BrowserTitleConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = browser_title::BrowserTitleConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// 設定画面でタイトルのパターンを試す用（ブラウザでないか形式が合わなければ null）
+#[tauri::command]
+fn browser_title_parse(state: State<Db>, identifier: String, window_title: String) -> AppResult<Option<browser_title::ParsedTitle>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let titles = browser_title::BrowserTitles::load(&conn)?;
+    Ok(titles.parse(&identifier, &window_title))
+}
+
+// ====== domains commands ======
+#[tauri::command]
+fn db_upsert_domain(state: State<Db>, domain: Domain) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).upsert_domain(&domain)
+}
+
+#[tauri::command]
+fn db_get_domains(state: State<Db>) -> AppResult<Vec<Domain>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    SqliteRepository::new(&conn).list_domains()
+}
+
+#[tauri::command]
+fn db_classify_domain(state: State<Db>, rule_cache: State<rules::RuleCache>, domain_name: String) -> AppResult<Option<i64>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    // ドメイン単体の問い合わせなのでパス/タイトル条件付きのルールは一致しない
+    let url = format!("https://{}/", domain_name);
+    Ok(rules::classify_browsing_with(&conn, &rule_cache.get(&conn)?, &domain_name, &url, None)?)
+}
+
+// ====== aggregates ======
+#[tauri::command]
+fn db_aggregate_waste(state: State<Db>, query: aggregate::AggregateQuery) -> AppResult<Vec<aggregate::AggregateBucket>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    aggregate::aggregate(&conn, &query)
+}
+
+#[tauri::command]
+fn db_aggregate_categories(state: State<Db>, query: aggregate::CategoryTotalsQuery) -> AppResult<Vec<categories::CategoryRollup>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    categories::rollup(&conn, &query)
+}
+
+// ブラウザの時間を閲覧記録のドメイン別の内訳に置き換えた重複のないタイムラインと合計
+#[tauri::command]
+fn db_unified_timeline(state: State<Db>, query: timeline::TimelineQuery) -> AppResult<timeline::UnifiedTimeline> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    timeline::unified_timeline(&conn, &query)
+}
+
+#[tauri::command]
+fn timeline_get_config(state: State<Db>) -> AppResult<timeline::TimelineConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(timeline::TimelineConfig::load(&conn))
+}
+
+#[tauri::command]
+fn timeline_update_config(state: State<Db>, config: timeline::TimelineConfigPatch) -> AppResult<timeline::TimelineConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = timeline::TimelineConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// ====== export ======
+#[tauri::command]
+fn export_data(state: State<Db>, request: export::ExportRequest) -> AppResult<export::ExportResult> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    export::export(&conn, &request)
+}
+
+// ====== import ======
+#[tauri::command]
+fn import_data(state: State<Db>, request: import::ImportRequest) -> AppResult<import::ImportResult> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    import::import(&mut conn, &request)
+}
+
+// ====== backup ======
+pub const DATABASE_RESTORED_EVENT: &str = "database:restored";
+// 自動バックアップの判定間隔（実際の取得間隔は interval_hours）
+const BACKUP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+fn spawn_backup_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || loop {
+        {
+            let state = app.state::<backup::BackupState>();
+            let config = state.config.lock().map(|c| c.clone()).unwrap_or_default();
+            let db = app.state::<Db>();
+            let result = match db.0.lock() {
+                Ok(conn) => backup::run_scheduled(&conn, &config.directory_or(&state.default_dir), &config, chrono::Local::now()),
+                Err(_) => Err(AppError::db_poisoned()),
+            };
+            match result {
+                Ok(Some(info)) => info!("Automatic backup written to {}", info.path),
+                Ok(None) => {}
+                Err(e) => error!("Automatic backup failed: {}", e),
+            }
+        }
+        std::thread::sleep(BACKUP_CHECK_INTERVAL);
+    });
+}
+
+// path 未指定ならバックアップディレクトリに作成
+#[tauri::command]
+fn backup_database(state: State<Db>, backup_state: State<backup::BackupState>, path: Option<String>) -> AppResult<backup::BackupInfo> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let now = chrono::Local::now();
+    match path {
+        Some(p) => backup::backup_to(&conn, std::path::Path::new(&p), backup::BackupKind::Manual, now),
+        None => backup::create_backup(&conn, &backup_state.directory(), backup::BackupKind::Manual, now),
     }
-    if let Some(d) = query.domain.as_ref() { 
-        clauses.push("domain = ?"); 
-        binds.push(d.clone()); 
+}
+
+// 復元後はメモリ上の設定も復元した DB の値に読み直す
+#[tauri::command]
+fn restore_database(app: AppHandle, path: String) -> AppResult<backup::RestoreResult> {
+    let db = app.state::<Db>();
+    let backup_state = app.state::<backup::BackupState>();
+    let mut conn = db.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let result = backup::restore(
+        &mut conn,
+        &backup_state.db_path,
+        std::path::Path::new(&path),
+        &backup_state.directory(),
+        chrono::Local::now(),
+    )?;
+    info!("Database restored from {} (schema version {})", result.restored_from, result.schema_version);
+    if let Ok(mut config) = backup_state.config.lock() {
+        *config = backup::BackupConfig::load(&conn);
     }
-    
-    if !clauses.is_empty() { 
-        sql.push_str(" WHERE "); 
-        sql.push_str(&clauses.join(" AND ")); 
+    if let Ok(mut config) = app.state::<sampler::SamplerState>().config.lock() {
+        *config = sampler::SamplerConfig::load(&conn);
     }
-    sql.push_str(" ORDER BY start_time DESC");
-    
-    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
-    let rows = stmt.query_map(rusqlite::params_from_iter(binds.iter()), |row| {
-        Ok(BrowsingSession {
-            id: row.get(0)?,
-            domain: row.get(1)?,
-            url: row.get(2)?,
-            title: row.get(3)?,
-            start_time: row.get(4)?,
-            duration_seconds: row.get(5)?,
-            category_id: row.get(6)?,
-            tab_id: row.get(7)?,
-        })
-    }).map_err(|e| e.to_string())?;
-    
-    let mut out = Vec::new();
-    for r in rows { 
-        out.push(r.map_err(|e| e.to_string())?); 
+    drop(conn);
+    if let Err(e) = app.emit(DATABASE_RESTORED_EVENT, &result) {
+        error!("Failed to emit database restored event: {}", e);
     }
-    Ok(out)
+    Ok(result)
 }
 
 #[tauri::command]
-fn db_delete_browsing_session(state: State<Db>, id: String) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute("DELETE FROM browsing_sessions WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
-    Ok(())
+fn backup_list(backup_state: State<backup::BackupState>) -> Vec<backup::BackupInfo> {
+    backup::list_backups(&backup_state.directory())
+}
+
+#[tauri::command]
+fn backup_get_config(backup_state: State<backup::BackupState>) -> AppResult<backup::BackupConfig> {
+    backup_state.config.lock().map(|c| c.clone()).map_err(|_| AppError::internal("backup lock poisoned"))
 }
 
-// ====== reclassify browsing_sessions by latest domains mapping ======
 #[tauri::command]
-fn db_reclassify_browsing_sessions(state: State<Db>, since: Option<String>, until: Option<String>) -> Result<u64, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
+fn backup_update_config(
+    state: State<Db>,
+    backup_state: State<backup::BackupState>,
+    config: backup::BackupConfigPatch,
+) -> AppResult<backup::BackupConfig> {
+    let mut current = backup_state.config.lock().map_err(|_| AppError::internal("backup lock poisoned"))?;
+    let next = current.apply(config)?;
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    next.save(&conn)?;
+    *current = next.clone();
+    Ok(next)
+}
 
-    // Build optional WHERE clause
-    let mut clauses: Vec<&str> = Vec::new();
-    let mut binds: Vec<String> = Vec::new();
-    if let Some(s) = since.as_ref() { clauses.push("start_time >= ?"); binds.push(s.clone()); }
-    if let Some(u) = until.as_ref() { clauses.push("start_time < ?"); binds.push(u.clone()); }
+// ====== budgets ======
+pub const BUDGET_THRESHOLD_EVENT: &str = "budget:threshold";
+const BUDGET_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+fn spawn_budget_checker(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || loop {
+        std::thread::sleep(BUDGET_CHECK_INTERVAL);
+        let db = app.state::<Db>();
+        let result = match db.0.lock() {
+            Ok(conn) => {
+                let config = budgets::BudgetConfig::load(&conn);
+                budgets::check(&conn, &config, chrono::Local::now()).map(|alerts| (config, alerts))
+            }
+            Err(_) => Err(AppError::db_poisoned()),
+        };
+        match result {
+            Ok((config, alerts)) => {
+                for alert in alerts {
+                    info!("Budget {} reached {}% ({}s used)", alert.status.label, alert.threshold, alert.status.used_seconds);
+                    if let Err(e) = app.emit(BUDGET_THRESHOLD_EVENT, &alert) {
+                        error!("Failed to emit budget event: {}", e);
+                    }
+                    if config.notify {
+                        notify_budget(&app, &alert);
+                    }
+                }
+            }
+            Err(e) => error!("Budget check failed: {}", e),
+        }
+    });
+}
 
-    // Update browsing_sessions.category_id by current domains.category_id
-    // Only update when domains.is_active = 1
-    let mut sql = String::from("UPDATE browsing_sessions AS bs SET category_id = (SELECT d.category_id FROM domains d WHERE d.domain = bs.domain AND d.is_active = 1) ");
-    if !clauses.is_empty() {
-        sql.push_str("WHERE ");
-        sql.push_str(&clauses.join(" AND "));
+fn notify_budget(app: &AppHandle, alert: &budgets::BudgetAlert) {
+    use tauri_plugin_notification::NotificationExt;
+    let s = &alert.status;
+    let body = if s.exceeded {
+        format!("{}: 今日の上限（{}分）に達しました", s.label, s.daily_limit_seconds / 60)
+    } else {
+        format!("{}: 今日の上限の{}%を使いました（残り{}分）", s.label, alert.threshold, s.remaining_seconds / 60)
+    };
+    if let Err(e) = app.notification().builder().title("WasteDay").body(body).show() {
+        error!("Failed to show budget notification: {}", e);
     }
+}
 
-    let affected = conn.execute(&sql, rusqlite::params_from_iter(binds.iter())).map_err(|e| e.to_string())? as u64;
-    Ok(affected)
+#[tauri::command]
+fn budgets_list(state: State<Db>) -> AppResult<Vec<budgets::Budget>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    budgets::list_budgets(&conn)
 }
 
-// ====== domains commands ======
 #[tauri::command]
-fn db_upsert_domain(state: State<Db>, domain: Domain) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    conn.execute(
-        "INSERT INTO domains(domain, category_id, is_active) VALUES(?1, ?2, ?3)
-         ON CONFLICT(domain) DO UPDATE SET category_id=excluded.category_id, is_active=excluded.is_active, updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
-        params![domain.domain, domain.category_id, if domain.is_active {1} else {0}],
-    ).map_err(|e| e.to_string())?;
-    Ok(())
+fn budgets_create(state: State<Db>, budget: budgets::NewBudget) -> AppResult<budgets::Budget> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    budgets::create_budget(&conn, budget)
 }
 
 #[tauri::command]
-fn db_get_domains(state: State<Db>) -> Result<Vec<Domain>, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut stmt = conn.prepare("SELECT id, domain, category_id, is_active FROM domains WHERE is_active IN (0,1) ORDER BY domain")
-        .map_err(|e| e.to_string())?;
-    let rows = stmt.query_map([], |row| {
-        Ok(Domain {
-            id: row.get(0)?,
-            domain: row.get(1)?,
-            category_id: row.get(2)?,
-            is_active: {
-                let v: i64 = row.get(3)?; v != 0
-            },
-        })
-    }).map_err(|e| e.to_string())?;
-    let mut out = Vec::new();
-    for r in rows { out.push(r.map_err(|e| e.to_string())?); }
-    Ok(out)
+fn budgets_update(state: State<Db>, id: i64, patch: budgets::BudgetPatch) -> AppResult<budgets::Budget> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    budgets::update_budget(&conn, id, patch)
 }
 
 #[tauri::command]
-fn db_classify_domain(state: State<Db>, domain_name: String) -> Result<Option<i64>, String> {
-    let conn = state.0.lock().map_err(|_| "db lock poisoned".to_string())?;
-    let mut stmt = conn.prepare("SELECT category_id FROM domains WHERE domain = ?1 AND is_active = 1")
-        .map_err(|e| e.to_string())?;
-    
-    let mut rows = stmt.query(params![domain_name]).map_err(|e| e.to_string())?;
-    if let Some(row) = rows.next().map_err(|e| e.to_string())? {
-        let category_id: Option<i64> = row.get(0).map_err(|e| e.to_string())?;
-        Ok(category_id)
-    } else {
-        Ok(None)
+fn budgets_delete(state: State<Db>, id: i64) -> AppResult<()> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    budgets::delete_budget(&conn, id)
+}
+
+// 有効な上限ごとの今日の利用時間と残り時間
+#[tauri::command]
+fn budgets_status(state: State<Db>) -> AppResult<Vec<budgets::BudgetStatus>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    budgets::status(&conn, chrono::Local::now())
+}
+
+#[tauri::command]
+fn budgets_get_config(state: State<Db>) -> AppResult<budgets::BudgetConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(budgets::BudgetConfig::load(&conn))
+}
+
+#[tauri::command]
+fn budgets_update_config(state: State<Db>, config: budgets::BudgetConfigPatch) -> AppResult<budgets::BudgetConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = budgets::BudgetConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// ====== policy ======
+#[tauri::command]
+fn policy_get_config(state: State<Db>) -> AppResult<policy::PolicyConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(policy::PolicyConfig::load(&conn))
+}
+
+#[tauri::command]
+fn policy_update_config(state: State<Db>, config: policy::PolicyConfigPatch) -> AppResult<policy::PolicyConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = policy::PolicyConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// 拡張機能に返すのと同じ判定（設定画面での確認用）
+#[tauri::command]
+fn policy_check(
+    state: State<Db>,
+    rule_cache: State<rules::RuleCache>,
+    domain: String,
+    url: Option<String>,
+    title: Option<String>,
+) -> AppResult<policy::PolicyDecision> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let config = policy::PolicyConfig::load(&conn);
+    let rules = rule_cache.get(&conn)?;
+    policy::decide(&conn, &rules, &config, &policy::PolicyQuery { domain, url, title }, chrono::Local::now())
+}
+
+// ====== retention ======
+// 起動直後の自動バックアップより後に走るよう最初は少し待つ
+const RETENTION_INITIAL_DELAY: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+const RETENTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+
+fn spawn_retention_job(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        std::thread::sleep(RETENTION_INITIAL_DELAY);
+        loop {
+            let db = app.state::<Db>();
+            let result = match db.0.lock() {
+                Ok(mut conn) => {
+                    // ゴミ箱の期限切れバッチは保持ポリシーの有効/無効に関係なく削除する
+                    match trash::purge(&conn, &trash::TrashConfig::load(&conn), chrono::Utc::now(), None) {
+                        Ok(n) if n > 0 => info!("Purged {} expired trash batches", n),
+                        Ok(_) => {}
+                        Err(e) => error!("Failed to purge trash: {}", e),
+                    }
+                    let config = retention::RetentionConfig::load(&conn);
+                    if config.enabled {
+                        retention::run(&mut conn, &config, chrono::Local::now(), false).map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                }
+                Err(_) => Err(AppError::db_poisoned()),
+            };
+            match result {
+                Ok(Some(report)) if report.sessions_pruned + report.browsing_sessions_pruned > 0 => info!(
+                    "Retention pruned {} sessions and {} browsing sessions before {}",
+                    report.sessions_pruned, report.browsing_sessions_pruned, report.cutoff
+                ),
+                Ok(_) => {}
+                Err(e) => error!("Retention job failed: {}", e),
+            }
+            std::thread::sleep(RETENTION_INTERVAL);
+        }
+    });
+}
+
+#[tauri::command]
+fn retention_get_config(state: State<Db>) -> AppResult<retention::RetentionConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(retention::RetentionConfig::load(&conn))
+}
+
+#[tauri::command]
+fn retention_update_config(state: State<Db>, config: retention::RetentionConfigPatch) -> AppResult<retention::RetentionConfig> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let next = retention::RetentionConfig::load(&conn).apply(config)?;
+    next.save(&conn)?;
+    Ok(next)
+}
+
+// dry_run=true なら削除せずに対象件数だけ返す（保存済みの設定を使う。raw_days を渡せばそれで試算）
+#[tauri::command]
+fn retention_run(state: State<Db>, dry_run: bool, raw_days: Option<u32>) -> AppResult<retention::RetentionReport> {
+    let mut conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    let config = retention::RetentionConfig::load(&conn).apply(retention::RetentionConfigPatch { raw_days, ..Default::default() })?;
+    retention::run(&mut conn, &config, chrono::Local::now(), dry_run)
+}
+
+// ====== ingest client commands ======
+#[tauri::command]
+fn ingest_start_pairing(state: State<Db>) -> AppResult<ingest_auth::PairingCode> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    ingest_auth::start_pairing(&conn)
+}
+
+#[tauri::command]
+fn ingest_list_clients(state: State<Db>) -> AppResult<Vec<ingest_auth::IngestClient>> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(ingest_auth::list_clients(&conn)?)
+}
+
+#[tauri::command]
+fn ingest_revoke_client(state: State<Db>, id: i64) -> AppResult<bool> {
+    let conn = state.0.lock().map_err(|_| AppError::db_poisoned())?;
+    Ok(ingest_auth::revoke_client(&conn, id)?)
+}
+
+#[tauri::command]
+fn ingest_get_server_status(state: State<ingest_server::IngestServerState>) -> ingest_server::IngestServerStatus {
+    state.status()
+}
+
+// 設定を保存してサーバを再起動（待ち受けアドレス/ポートが変わらなければ何もしない）
+// 旧サーバの停止待ちでブロックするため別スレッドで実行
+#[tauri::command]
+async fn ingest_update_server_config(
+    app: AppHandle,
+    config: ingest_server::IngestServerConfigPatch,
+) -> AppResult<ingest_server::IngestServerStatus> {
+    {
+        let state = app.state::<ingest_server::IngestServerState>();
+        let mut current = state.config.lock().map_err(|_| AppError::internal("ingest server lock poisoned"))?;
+        let next = current.apply(config)?;
+        if next == *current {
+            return Ok(state.status());
+        }
+        let db = app.state::<Db>();
+        let conn = db.0.lock().map_err(|_| AppError::db_poisoned())?;
+        next.save(&conn)?;
+        *current = next;
     }
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || start_ingest_server(&handle))
+        .await
+        .map_err(|e| AppError::internal(e.to_string()))?;
+    Ok(app.state::<ingest_server::IngestServerState>().status())
 }
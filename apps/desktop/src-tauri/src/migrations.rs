@@ -0,0 +1,378 @@
+// スキーマのバージョン管理: PRAGMA user_version に適用済みバージョンを記録し、
+// 未適用のマイグレーションを順番に1つずつトランザクションで適用する
+use rusqlite::Connection;
+use std::fmt;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+// 追加するときは末尾に version を1つ増やして足すこと（既存のステップは変更しない）
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        // v1 は旧 apply_schema と同一。user_version=0 の既存DBにもそのまま適用できるよう IF NOT EXISTS を維持
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+          id TEXT PRIMARY KEY,
+          start_time TEXT NOT NULL,
+          duration_seconds INTEGER NOT NULL,
+          session_key TEXT NOT NULL,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_sessions_start_time ON sessions(start_time);
+        CREATE TABLE IF NOT EXISTS waste_categories (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          type TEXT NOT NULL,
+          identifier TEXT NOT NULL,
+          label TEXT NOT NULL,
+          is_active INTEGER NOT NULL DEFAULT 1,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          UNIQUE(type, identifier)
+        );
+        CREATE TABLE IF NOT EXISTS user_settings (
+          key TEXT PRIMARY KEY,
+          value TEXT NOT NULL,
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+        );
+        CREATE TABLE IF NOT EXISTS browsing_sessions (
+          id TEXT PRIMARY KEY,
+          domain TEXT NOT NULL,
+          url TEXT NOT NULL,
+          title TEXT,
+          start_time TEXT NOT NULL,
+          duration_seconds INTEGER DEFAULT 0,
+          category_id INTEGER,
+          tab_id INTEGER,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          FOREIGN KEY (category_id) REFERENCES waste_categories (id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_browsing_sessions_domain ON browsing_sessions(domain);
+        CREATE INDEX IF NOT EXISTS idx_browsing_sessions_start_time ON browsing_sessions(start_time);
+        CREATE TABLE IF NOT EXISTS domains (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          domain TEXT UNIQUE NOT NULL,
+          category_id INTEGER,
+          is_active INTEGER NOT NULL DEFAULT 1,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          FOREIGN KEY (category_id) REFERENCES waste_categories (id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_domains_domain ON domains(domain);
+        "#,
+    },
+    Migration {
+        version: 2,
+        name: "sessions_structured_key",
+        // session_key（category=app;identifier=...;user_state=...）を列に展開して既存行をバックフィル
+        sql: r#"
+        ALTER TABLE sessions ADD COLUMN category TEXT;
+        ALTER TABLE sessions ADD COLUMN identifier TEXT;
+        ALTER TABLE sessions ADD COLUMN user_state TEXT;
+        ALTER TABLE sessions ADD COLUMN window_title TEXT;
+        UPDATE sessions SET
+          category = CASE WHEN instr(';' || session_key || ';', ';category=') > 0 THEN
+            substr(
+              substr(';' || session_key || ';', instr(';' || session_key || ';', ';category=') + 10),
+              1,
+              instr(substr(';' || session_key || ';', instr(';' || session_key || ';', ';category=') + 10), ';') - 1
+            ) END,
+          identifier = CASE WHEN instr(';' || session_key || ';', ';identifier=') > 0 THEN
+            lower(substr(
+              substr(';' || session_key || ';', instr(';' || session_key || ';', ';identifier=') + 12),
+              1,
+              instr(substr(';' || session_key || ';', instr(';' || session_key || ';', ';identifier=') + 12), ';') - 1
+            )) END,
+          user_state = CASE WHEN instr(';' || session_key || ';', ';user_state=') > 0 THEN
+            substr(
+              substr(';' || session_key || ';', instr(';' || session_key || ';', ';user_state=') + 12),
+              1,
+              instr(substr(';' || session_key || ';', instr(';' || session_key || ';', ';user_state=') + 12), ';') - 1
+            ) END;
+        CREATE INDEX IF NOT EXISTS idx_sessions_identifier_start_time ON sessions(identifier, start_time);
+        CREATE INDEX IF NOT EXISTS idx_sessions_user_state_start_time ON sessions(user_state, start_time);
+        CREATE INDEX IF NOT EXISTS idx_sessions_category_start_time ON sessions(category, start_time);
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "ingest_clients",
+        // 取り込みAPIのクライアント（トークンは SHA-256 のみ保存）と、未使用のペアリングコード
+        sql: r#"
+        CREATE TABLE ingest_clients (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          name TEXT NOT NULL,
+          origin TEXT,
+          token_hash TEXT NOT NULL UNIQUE,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          last_used_at TEXT,
+          revoked_at TEXT
+        );
+        CREATE TABLE ingest_pairing_codes (
+          code TEXT PRIMARY KEY,
+          expires_at TEXT NOT NULL,
+          attempts INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "daily_summaries",
+        // 保持期間を過ぎた生データの日別集計（day はローカル日付、集計後に元の行は削除される）
+        sql: r#"
+        CREATE TABLE daily_app_summaries (
+          day TEXT NOT NULL,
+          identifier TEXT NOT NULL,
+          user_state TEXT NOT NULL,
+          duration_seconds INTEGER NOT NULL,
+          session_count INTEGER NOT NULL,
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          PRIMARY KEY (day, identifier, user_state)
+        );
+        CREATE TABLE daily_domain_summaries (
+          day TEXT NOT NULL,
+          domain TEXT NOT NULL,
+          category_id INTEGER,
+          duration_seconds INTEGER NOT NULL,
+          session_count INTEGER NOT NULL,
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          PRIMARY KEY (day, domain)
+        );
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "trash",
+        // 一括削除した行の退避先（1回の削除 = 1バッチ）。行は列追加に耐えるよう JSON で保存する
+        sql: r#"
+        CREATE TABLE trash_batches (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          description TEXT,
+          deleted_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+        );
+        CREATE INDEX idx_trash_batches_deleted_at ON trash_batches(deleted_at);
+        CREATE TABLE trash_rows (
+          batch_id INTEGER NOT NULL,
+          source_table TEXT NOT NULL,
+          row_id TEXT NOT NULL,
+          data TEXT NOT NULL,
+          PRIMARY KEY (batch_id, source_table, row_id),
+          FOREIGN KEY (batch_id) REFERENCES trash_batches (id) ON DELETE CASCADE
+        );
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "manual_flag",
+        // 手入力・手修正した行（計測値と区別する）。サンプラー/取り込みはこの行を上書きしない
+        sql: r#"
+        ALTER TABLE sessions ADD COLUMN is_manual INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE browsing_sessions ADD COLUMN is_manual INTEGER NOT NULL DEFAULT 0;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "classification_rules",
+        // パターンによる分類ルール（priority の大きい順に評価し、最初に一致したルールを採用）
+        sql: r#"
+        CREATE TABLE classification_rules (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          target TEXT NOT NULL,
+          pattern TEXT NOT NULL,
+          title_regex TEXT,
+          category_id INTEGER NOT NULL,
+          priority INTEGER NOT NULL DEFAULT 0,
+          is_active INTEGER NOT NULL DEFAULT 1,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          FOREIGN KEY (category_id) REFERENCES waste_categories (id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_classification_rules_target ON classification_rules(target, is_active, priority);
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "categories",
+        // 階層カテゴリ（waste_weight が NULL なら親から継承）。既存の label は Productive / Waste に振り分ける
+        sql: r#"
+        CREATE TABLE categories (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          name TEXT NOT NULL,
+          parent_id INTEGER,
+          color TEXT,
+          waste_weight REAL,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          FOREIGN KEY (parent_id) REFERENCES categories (id) ON DELETE SET NULL
+        );
+        CREATE INDEX idx_categories_parent_id ON categories(parent_id);
+        INSERT INTO categories(id, name, parent_id, color, waste_weight) VALUES
+          (1, 'Productive', NULL, '#22c55e', 0.0),
+          (2, 'Waste', NULL, '#ef4444', 1.0),
+          (3, 'Work', 1, '#3b82f6', NULL),
+          (4, 'Learning', 1, '#8b5cf6', NULL),
+          (5, 'Social', 2, '#f97316', NULL),
+          (6, 'Video', 2, '#ec4899', NULL),
+          (7, 'Gaming', 2, '#eab308', NULL);
+        ALTER TABLE waste_categories ADD COLUMN category_id INTEGER REFERENCES categories (id) ON DELETE SET NULL;
+        UPDATE waste_categories SET category_id = CASE WHEN label = 'waste' THEN 2 ELSE 1 END;
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "session_classification",
+        // sessions に分類結果（waste_categories.id）を保存する。既存行は識別子の完全一致でバックフィル
+        // （分類ルールはここでは評価しない。db_reclassify_sessions で反映する）
+        sql: r#"
+        ALTER TABLE sessions ADD COLUMN category_id INTEGER REFERENCES waste_categories (id) ON DELETE SET NULL;
+        UPDATE sessions SET category_id = (
+          SELECT wc.id FROM waste_categories wc WHERE wc.type = 'app' AND wc.identifier = sessions.identifier AND wc.is_active = 1
+        );
+        CREATE INDEX idx_sessions_category_id_start_time ON sessions(category_id, start_time);
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "budgets",
+        // 1日あたりの上限（target は category なら categories.id、app なら識別子、domain ならドメイン）と
+        // その日に通知済みのしきい値（同じしきい値を1日に何度も通知しない）
+        sql: r#"
+        CREATE TABLE budgets (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          target_type TEXT NOT NULL,
+          target TEXT NOT NULL,
+          daily_limit_seconds INTEGER NOT NULL,
+          is_active INTEGER NOT NULL DEFAULT 1,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          UNIQUE(target_type, target)
+        );
+        CREATE TABLE budget_notifications (
+          budget_id INTEGER NOT NULL,
+          day TEXT NOT NULL,
+          threshold INTEGER NOT NULL,
+          notified_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+          PRIMARY KEY (budget_id, day, threshold),
+          FOREIGN KEY (budget_id) REFERENCES budgets (id) ON DELETE CASCADE
+        );
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "browsing_heartbeat_index",
+        // 取り込み時に同じタブ・URL の直前のセッションを探してハートビートを結合する
+        sql: r#"
+        CREATE INDEX idx_browsing_sessions_tab_url_start_time ON browsing_sessions(tab_id, url, start_time);
+        "#,
+    },
+    Migration {
+        version: 12,
+        name: "rule_set_token",
+        // 分類ルールのキャッシュ（rules::RuleCache）用: ルール・domains・ブラウザタイトル設定が変わるたびに token を作り直す
+        sql: r#"
+        CREATE TABLE rule_set_token (
+          id INTEGER PRIMARY KEY CHECK (id = 1),
+          token BLOB NOT NULL
+        );
+        INSERT INTO rule_set_token(id, token) VALUES(1, randomblob(16));
+        CREATE TRIGGER trg_classification_rules_insert_token AFTER INSERT ON classification_rules
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_classification_rules_update_token AFTER UPDATE ON classification_rules
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_classification_rules_delete_token AFTER DELETE ON classification_rules
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_domains_insert_token AFTER INSERT ON domains
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_domains_update_token AFTER UPDATE ON domains
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_domains_delete_token AFTER DELETE ON domains
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_browser_title_config_insert_token AFTER INSERT ON user_settings WHEN NEW.key = 'browser_title_config'
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_browser_title_config_update_token AFTER UPDATE ON user_settings
+          WHEN NEW.key = 'browser_title_config' OR OLD.key = 'browser_title_config'
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        CREATE TRIGGER trg_browser_title_config_delete_token AFTER DELETE ON user_settings WHEN OLD.key = 'browser_title_config'
+          BEGIN UPDATE rule_set_token SET token = randomblob(16); END;
+        "#,
+    },
+];
+
+/// このバージョンのアプリが扱える最新のスキーマバージョン
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Sqlite(rusqlite::Error),
+    /// より新しいアプリで作成/更新されたDB（ダウングレード不可）
+    NewerSchema { found: i64, supported: i64 },
+    /// マイグレーション一覧の version が連番になっていない
+    InvalidOrder { version: i64 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Sqlite(e) => write!(f, "migration failed: {}", e),
+            MigrationError::NewerSchema { found, supported } => write!(
+                f,
+                "database schema version {} is newer than supported version {}; please update the app",
+                found, supported
+            ),
+            MigrationError::InvalidOrder { version } => write!(f, "migration version {} is out of order", version),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+pub fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// 組み込みのマイグレーションをすべて適用し、適用後のバージョンを返す
+pub fn migrate(conn: &mut Connection) -> Result<i64, MigrationError> {
+    apply_migrations(conn, MIGRATIONS)
+}
+
+/// 指定したマイグレーション列を適用する（各ステップは個別のトランザクション）
+pub fn apply_migrations(conn: &mut Connection, migrations: &[Migration]) -> Result<i64, MigrationError> {
+    // foreign_keys はトランザクション内では切り替えられないため先に設定
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+
+    let supported = migrations.last().map(|m| m.version).unwrap_or(0);
+    let mut version = current_version(conn)?;
+    if version > supported {
+        return Err(MigrationError::NewerSchema { found: version, supported });
+    }
+
+    for (i, m) in migrations.iter().enumerate() {
+        if m.version != i as i64 + 1 {
+            return Err(MigrationError::InvalidOrder { version: m.version });
+        }
+        if m.version <= version {
+            continue;
+        }
+        log::info!("Applying migration {} ({})", m.version, m.name);
+        let tx = conn.transaction()?;
+        tx.execute_batch(m.sql)?;
+        tx.pragma_update(None, "user_version", m.version)?;
+        tx.commit()?;
+        version = m.version;
+    }
+    Ok(version)
+}
@@ -0,0 +1,58 @@
+// 前面ウィンドウ情報・アイドル秒の取得をOSごとに切り替えるための抽象化
+use serde::Serialize;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ForegroundInfo {
+    pub process_id: u32,
+    pub exe: String,
+    pub window_title: String,
+}
+
+pub trait ActivityProvider: Send + Sync {
+    /// 現在前面にあるウィンドウのプロセス/実行ファイル/タイトル
+    fn foreground_info(&self) -> ForegroundInfo;
+    /// 最後のユーザー入力からの経過秒
+    fn idle_seconds(&self) -> u64;
+}
+
+// 対応していない環境（macOS など）では常に空の情報を返す
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+struct UnsupportedProvider;
+
+impl ActivityProvider for UnsupportedProvider {
+    fn foreground_info(&self) -> ForegroundInfo {
+        ForegroundInfo { process_id: 0, exe: String::from("unknown"), window_title: String::new() }
+    }
+
+    fn idle_seconds(&self) -> u64 {
+        0
+    }
+}
+
+/// 実行中のOSに応じたプロバイダを返す
+#[cfg(target_os = "windows")]
+pub fn default_provider() -> Box<dyn ActivityProvider> {
+    Box::new(windows::WindowsProvider)
+}
+
+/// 実行中のOSに応じたプロバイダを返す（X11 に接続できなければ空の情報を返す）
+#[cfg(target_os = "linux")]
+pub fn default_provider() -> Box<dyn ActivityProvider> {
+    match x11::X11Provider::new() {
+        Ok(p) => Box::new(p),
+        Err(e) => {
+            log::error!("X11 activity provider unavailable: {}", e);
+            Box::new(UnsupportedProvider)
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn default_provider() -> Box<dyn ActivityProvider> {
+    Box::new(UnsupportedProvider)
+}
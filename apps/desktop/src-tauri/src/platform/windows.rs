@@ -0,0 +1,71 @@
+// Win32 API による前面ウィンドウ/アイドル秒の取得
+use std::path::Path;
+use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::Input::KeyboardAndMouse::GetLastInputInfo;
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::Foundation::BOOL;
+use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+use windows::Win32::System::ProcessStatus::K32GetModuleFileNameExW;
+use windows::Win32::Foundation::CloseHandle;
+
+use super::{ActivityProvider, ForegroundInfo};
+
+pub struct WindowsProvider;
+
+impl ActivityProvider for WindowsProvider {
+    fn foreground_info(&self) -> ForegroundInfo {
+        unsafe {
+            let hwnd: HWND = GetForegroundWindow();
+            let mut pid: u32 = 0;
+            let _tid = GetWindowThreadProcessId(hwnd, Some(&mut pid));
+
+            let mut title_buf: [u16; 512] = [0; 512];
+            let len = GetWindowTextW(hwnd, &mut title_buf);
+            let title = String::from_utf16_lossy(&title_buf[..len as usize]);
+
+            // exe 解決
+            let mut exe_name = String::from("unknown.exe");
+            if pid != 0 {
+                if let Ok(hproc) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+                    if !hproc.is_invalid() {
+                        let mut buf: [u16; 260] = [0; 260];
+                        let len = K32GetModuleFileNameExW(hproc, None, &mut buf);
+                        if len > 0 {
+                            let full = String::from_utf16_lossy(&buf[..len as usize]);
+                            if let Some(name) = Path::new(&full).file_name().and_then(|s| s.to_str()) {
+                                exe_name = name.to_string();
+                            } else {
+                                exe_name = full;
+                            }
+                        }
+                        let _ = CloseHandle(hproc);
+                    }
+                }
+            }
+
+            ForegroundInfo { process_id: pid, exe: exe_name, window_title: title }
+        }
+    }
+
+    fn idle_seconds(&self) -> u64 {
+        unsafe {
+            #[repr(C)]
+            struct LASTINPUTINFO {
+                cb_size: u32,
+                dw_time: u32,
+            }
+
+            let mut li = LASTINPUTINFO { cb_size: std::mem::size_of::<LASTINPUTINFO>() as u32, dw_time: 0 };
+            let ok: BOOL = std::mem::transmute(GetLastInputInfo(std::mem::transmute(&mut li)));
+            if ok.as_bool() {
+                let now = GetTickCount() as u64;
+                let last = li.dw_time as u64;
+                let diff = if now >= last { now - last } else { 0 };
+                diff / 1000
+            } else {
+                0
+            }
+        }
+    }
+}
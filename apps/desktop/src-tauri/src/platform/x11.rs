@@ -0,0 +1,235 @@
+// X11 (EWMH + XScreenSaver) による前面ウィンドウ/アイドル秒の取得
+// libX11 / libXss は実行時に動的ロードするため、ビルド時にはヘッダ不要
+use std::ffi::CString;
+use std::os::raw::{c_int, c_long, c_uchar, c_ulong};
+use std::path::Path;
+use std::ptr;
+use x11_dl::xlib::{self, Xlib};
+use x11_dl::xss::Xss;
+
+use super::{ActivityProvider, ForegroundInfo};
+
+pub struct X11Provider {
+    xlib: Xlib,
+    xss: Option<Xss>,
+}
+
+// BadWindow などで既定ハンドラがプロセスを終了させないよう、エラーは無視する
+unsafe extern "C" fn ignore_x_error(_display: *mut xlib::Display, _event: *mut xlib::XErrorEvent) -> c_int {
+    0
+}
+
+type XErrorHandler = Option<unsafe extern "C" fn(*mut xlib::Display, *mut xlib::XErrorEvent) -> c_int>;
+
+// エラーハンドラはプロセス全体で1つ（GTK/GDK も設定している）なので、問い合わせの間だけ差し替えて戻す
+// Drop で XSync して、問い合わせ中のエラーを戻す前に受け取る
+struct IgnoreErrors<'a> {
+    display: &'a Display<'a>,
+    previous: XErrorHandler,
+}
+
+impl<'a> IgnoreErrors<'a> {
+    fn install(display: &'a Display<'a>) -> Self {
+        let previous = unsafe { (display.xlib.XSetErrorHandler)(Some(ignore_x_error)) };
+        IgnoreErrors { display, previous }
+    }
+}
+
+impl Drop for IgnoreErrors<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.display.xlib.XSync)(self.display.ptr, xlib::False);
+            (self.display.xlib.XSetErrorHandler)(self.previous);
+        }
+    }
+}
+
+// XOpenDisplay したディスプレイを Drop で確実に閉じる
+struct Display<'a> {
+    xlib: &'a Xlib,
+    ptr: *mut xlib::Display,
+}
+
+impl Drop for Display<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.XCloseDisplay)(self.ptr);
+        }
+    }
+}
+
+impl X11Provider {
+    pub fn new() -> Result<Self, String> {
+        let xlib = Xlib::open().map_err(|e| e.to_string())?;
+        let xss = match Xss::open() {
+            Ok(x) => Some(x),
+            Err(e) => {
+                log::warn!("libXss not available, idle time will be reported as 0: {}", e);
+                None
+            }
+        };
+        Ok(Self { xlib, xss })
+    }
+
+    // サンプリングは数秒おきなので、X サーバ再起動に追従できるよう毎回接続する
+    fn open_display(&self) -> Option<Display<'_>> {
+        let ptr = unsafe { (self.xlib.XOpenDisplay)(ptr::null()) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Display { xlib: &self.xlib, ptr })
+        }
+    }
+
+    fn atom(&self, display: &Display, name: &str) -> c_ulong {
+        let cname = CString::new(name).unwrap_or_default();
+        unsafe { (self.xlib.XInternAtom)(display.ptr, cname.as_ptr(), xlib::False) }
+    }
+
+    // ウィンドウプロパティを生バイト列として取得（format と要素数を併せて返す）
+    fn get_property(&self, display: &Display, window: c_ulong, property: c_ulong, req_type: c_ulong) -> Option<(c_int, Vec<u8>, usize)> {
+        unsafe {
+            let mut actual_type: c_ulong = 0;
+            let mut actual_format: c_int = 0;
+            let mut nitems: c_ulong = 0;
+            let mut bytes_after: c_ulong = 0;
+            let mut data: *mut c_uchar = ptr::null_mut();
+            let status = (self.xlib.XGetWindowProperty)(
+                display.ptr,
+                window,
+                property,
+                0,
+                4096,
+                xlib::False,
+                req_type,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut data,
+            );
+            if status != xlib::Success as c_int || data.is_null() {
+                return None;
+            }
+            // format=32 の要素は Xlib 上 long として格納される
+            let item_size = match actual_format {
+                8 => 1,
+                16 => std::mem::size_of::<i16>(),
+                32 => std::mem::size_of::<c_long>(),
+                _ => 0,
+            };
+            let bytes = std::slice::from_raw_parts(data, nitems as usize * item_size).to_vec();
+            (self.xlib.XFree)(data as *mut _);
+            if actual_type == 0 {
+                return None;
+            }
+            Some((actual_format, bytes, nitems as usize))
+        }
+    }
+
+    fn get_cardinal(&self, display: &Display, window: c_ulong, property: c_ulong, req_type: c_ulong) -> Option<c_ulong> {
+        let (format, bytes, nitems) = self.get_property(display, window, property, req_type)?;
+        cardinal_from_property(format, &bytes, nitems)
+    }
+
+    fn get_title(&self, display: &Display, window: c_ulong) -> String {
+        // _NET_WM_NAME (UTF8_STRING) を優先し、無ければ従来の WM_NAME
+        let net_wm_name = self.atom(display, "_NET_WM_NAME");
+        let utf8 = self.atom(display, "UTF8_STRING");
+        if let Some((8, bytes, _)) = self.get_property(display, window, net_wm_name, utf8) {
+            return title_from_property(&bytes);
+        }
+        if let Some((8, bytes, _)) = self.get_property(display, window, xlib::XA_WM_NAME, xlib::AnyPropertyType as c_ulong) {
+            return title_from_property(&bytes);
+        }
+        String::new()
+    }
+}
+
+/// format=32 のプロパティの先頭要素（ウィンドウ id / pid）
+pub fn cardinal_from_property(format: c_int, bytes: &[u8], nitems: usize) -> Option<c_ulong> {
+    const SIZE: usize = std::mem::size_of::<c_long>();
+    if format != 32 || nitems == 0 || bytes.len() < SIZE {
+        return None;
+    }
+    let mut buf = [0u8; SIZE];
+    buf.copy_from_slice(&bytes[..SIZE]);
+    Some(c_long::from_ne_bytes(buf) as c_ulong)
+}
+
+/// format=8 のタイトル（末尾の NUL は除く。不正な UTF-8 は置換する）
+pub fn title_from_property(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()
+}
+
+/// /proc/<pid>/exe のリンク先から実行ファイル名を取り出す
+pub fn exe_name_from_link(target: &Path) -> Option<String> {
+    let name = target.file_name()?.to_str()?;
+    // 実行中に更新されたバイナリは " (deleted)" が付与される
+    Some(name.trim_end_matches(" (deleted)").to_string())
+}
+
+fn exe_name_for_pid(pid: u32) -> Option<String> {
+    exe_name_from_link(&std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?)
+}
+
+impl ActivityProvider for X11Provider {
+    fn foreground_info(&self) -> ForegroundInfo {
+        let mut info = ForegroundInfo { process_id: 0, exe: String::from("unknown"), window_title: String::new() };
+        let display = match self.open_display() {
+            Some(d) => d,
+            None => return info,
+        };
+        let _ignore = IgnoreErrors::install(&display);
+        let root = unsafe { (self.xlib.XDefaultRootWindow)(display.ptr) };
+        let active_atom = self.atom(&display, "_NET_ACTIVE_WINDOW");
+        let window = match self.get_cardinal(&display, root, active_atom, xlib::XA_WINDOW) {
+            Some(w) if w != 0 => w,
+            _ => return info,
+        };
+
+        info.window_title = self.get_title(&display, window);
+
+        let pid_atom = self.atom(&display, "_NET_WM_PID");
+        if let Some(pid) = self.get_cardinal(&display, window, pid_atom, xlib::XA_CARDINAL) {
+            info.process_id = pid as u32;
+            if let Some(name) = exe_name_for_pid(info.process_id) {
+                info.exe = name;
+            }
+        }
+        info
+    }
+
+    fn idle_seconds(&self) -> u64 {
+        let xss = match self.xss.as_ref() {
+            Some(x) => x,
+            None => return 0,
+        };
+        let display = match self.open_display() {
+            Some(d) => d,
+            None => return 0,
+        };
+        let _ignore = IgnoreErrors::install(&display);
+        unsafe {
+            let mut event_base: c_int = 0;
+            let mut error_base: c_int = 0;
+            if (xss.XScreenSaverQueryExtension)(display.ptr, &mut event_base, &mut error_base) == xlib::False {
+                return 0;
+            }
+            let info = (xss.XScreenSaverAllocInfo)();
+            if info.is_null() {
+                return 0;
+            }
+            let root = (self.xlib.XDefaultRootWindow)(display.ptr);
+            // idle は c_ulong（32bit 環境では u32）
+            #[allow(clippy::unnecessary_cast)]
+            let idle_ms = if (xss.XScreenSaverQueryInfo)(display.ptr, root, info) != 0 {
+                (*info).idle as u64
+            } else {
+                0
+            };
+            (self.xlib.XFree)(info as *mut _);
+            idle_ms / 1000
+        }
+    }
+}
@@ -0,0 +1,273 @@
+// ブラウザ拡張向けのブロック判定: 利用上限（budgets）と集中時間（focus）からドメインごとに allow / warn / block を決める
+// 分類は取り込みと同じ（ルール → domains）で、判定に使う状態はすべてデスクトップアプリ側の DB にある
+// 設定は user_settings（policy_config）に JSON で保存する
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, SecondsFormat, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::budgets::{self, BudgetStatus, BudgetTarget};
+use crate::categories;
+use crate::error::{AppError, AppResult};
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+use crate::rules::{self, RuleSet};
+
+const CONFIG_SETTING_KEY: &str = "policy_config";
+// 親をたどる上限（壊れたデータで循環していても止まるように）
+const MAX_DEPTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Allow,
+    Warn,
+    Block,
+}
+
+/// 集中時間の枠（ローカル時刻）
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FocusSchedule {
+    /// 曜日（1 = 月曜 … 7 = 日曜）。空なら毎日
+    #[serde(default)]
+    pub days: Vec<u32>,
+    /// "HH:MM"。end が start より前なら日をまたぐ（days は開始した日で判定）
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyConfig {
+    /// 無効なら常に allow
+    pub enabled: bool,
+    /// 上限を使い切ったドメインへの対応
+    pub budget_action: PolicyAction,
+    /// 上限のこの割合（%）以上で warn（0 なら警告しない）
+    pub warn_percent: u32,
+    /// 集中時間中に waste のドメインへの対応
+    pub focus_action: PolicyAction,
+    pub focus_schedules: Vec<FocusSchedule>,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            budget_action: PolicyAction::Block,
+            warn_percent: 80,
+            focus_action: PolicyAction::Block,
+            focus_schedules: Vec::new(),
+        }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct PolicyConfigPatch {
+    pub enabled: Option<bool>,
+    pub budget_action: Option<PolicyAction>,
+    pub warn_percent: Option<u32>,
+    pub focus_action: Option<PolicyAction>,
+    pub focus_schedules: Option<Vec<FocusSchedule>>,
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| format!("invalid time (expected HH:MM): {}", value))
+}
+
+impl FocusSchedule {
+    fn validate(&self) -> Result<(), String> {
+        if self.days.iter().any(|d| !(1..=7).contains(d)) {
+            return Err("days must be between 1 (Mon) and 7 (Sun)".to_string());
+        }
+        if parse_time(&self.start)? == parse_time(&self.end)? {
+            return Err("focus schedule start and end must differ".to_string());
+        }
+        Ok(())
+    }
+
+    fn runs_on(&self, day: u32) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    // now が枠内なら終了時刻を返す
+    fn active_until(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let (start, end) = (parse_time(&self.start).ok()?, parse_time(&self.end).ok()?);
+        let today = now.date_naive();
+        let weekday = now.weekday().number_from_monday();
+        let time = now.time();
+        let at = |date: chrono::NaiveDate, t: NaiveTime| Local.from_local_datetime(&date.and_time(t)).earliest();
+        if start < end {
+            (self.runs_on(weekday) && start <= time && time < end).then(|| at(today, end)).flatten()
+        } else if time >= start {
+            self.runs_on(weekday).then(|| at(today + Duration::days(1), end)).flatten()
+        } else if time < end {
+            let yesterday = if weekday == 1 { 7 } else { weekday - 1 };
+            self.runs_on(yesterday).then(|| at(today, end)).flatten()
+        } else {
+            None
+        }
+    }
+}
+
+impl PolicyConfig {
+    pub fn apply(&self, patch: PolicyConfigPatch) -> Result<PolicyConfig, String> {
+        let next = PolicyConfig {
+            enabled: patch.enabled.unwrap_or(self.enabled),
+            budget_action: patch.budget_action.unwrap_or(self.budget_action),
+            warn_percent: patch.warn_percent.unwrap_or(self.warn_percent),
+            focus_action: patch.focus_action.unwrap_or(self.focus_action),
+            focus_schedules: patch.focus_schedules.unwrap_or_else(|| self.focus_schedules.clone()),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.warn_percent > 100 {
+            return Err("warn_percent must be between 0 and 100".to_string());
+        }
+        self.focus_schedules.iter().try_for_each(FocusSchedule::validate)
+    }
+
+    pub fn load(conn: &Connection) -> PolicyConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, PolicyConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+
+    /// 集中時間中なら、その枠の終了時刻（重なっていれば最も遅いもの）
+    pub fn focus_until(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.focus_schedules.iter().filter_map(|s| s.active_until(now)).max()
+    }
+}
+
+/// 拡張機能から送られる判定対象
+#[derive(Deserialize, Debug, Clone)]
+pub struct PolicyQuery {
+    pub domain: String,
+    /// 未指定なら https://{domain}/ として分類する
+    pub url: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyReason {
+    Budget,
+    Focus,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PolicyDecision {
+    pub domain: String,
+    pub action: PolicyAction,
+    /// allow なら None
+    pub reason: Option<PolicyReason>,
+    /// 分類結果（waste_categories.id / label）。未分類なら None
+    pub category_id: Option<i64>,
+    pub label: Option<String>,
+    /// このドメインに掛かる上限のうち最も使っているもの
+    pub budget: Option<BudgetStatus>,
+    /// 集中時間の終了時刻（集中時間中のみ）
+    pub focus_until: Option<String>,
+    /// 拡張機能がそのまま表示する文言
+    pub message: Option<String>,
+}
+
+// waste_categories.category_id から根までのカテゴリ id
+fn category_chain(conn: &Connection, waste_category_id: i64) -> AppResult<Vec<i64>> {
+    let leaf: Option<i64> = conn
+        .query_row("SELECT category_id FROM waste_categories WHERE id = ?1", params![waste_category_id], |row| row.get(0))
+        .optional()?
+        .flatten();
+    let Some(leaf) = leaf else { return Ok(Vec::new()) };
+    let all = categories::list_categories(conn)?;
+    let mut chain = vec![leaf];
+    while chain.len() < MAX_DEPTH {
+        let current = *chain.last().unwrap_or(&leaf);
+        match all.iter().find(|c| c.id == current).and_then(|c| c.parent_id) {
+            Some(parent) if !chain.contains(&parent) => chain.push(parent),
+            _ => break,
+        }
+    }
+    Ok(chain)
+}
+
+fn applies_to(status: &BudgetStatus, domain: &str, chain: &[i64]) -> bool {
+    match status.target_type {
+        BudgetTarget::Domain => budgets::matches_domain(domain, &status.target),
+        BudgetTarget::Category => status.target.parse::<i64>().map(|id| chain.contains(&id)).unwrap_or(false),
+        BudgetTarget::App => false,
+    }
+}
+
+/// ドメインへのアクセスを判定する（上限と集中時間のうち厳しい方を採用）
+pub fn decide(conn: &Connection, rules: &RuleSet, config: &PolicyConfig, query: &PolicyQuery, now: DateTime<Local>) -> AppResult<PolicyDecision> {
+    let domain = query.domain.trim().to_lowercase();
+    if domain.is_empty() {
+        return Err(AppError::validation("domain is required").with_detail("field", "domain"));
+    }
+    let url = query.url.clone().unwrap_or_else(|| format!("https://{}/", domain));
+    let category_id = rules::classify_browsing_with(conn, rules, &domain, &url, query.title.as_deref())?;
+    let label: Option<String> = match category_id {
+        Some(id) => conn
+            .query_row("SELECT label FROM waste_categories WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?,
+        None => None,
+    };
+
+    let mut decision = PolicyDecision {
+        domain: domain.clone(),
+        action: PolicyAction::Allow,
+        reason: None,
+        category_id,
+        label,
+        budget: None,
+        focus_until: None,
+        message: None,
+    };
+    if !config.enabled {
+        return Ok(decision);
+    }
+
+    let chain = match category_id {
+        Some(id) => category_chain(conn, id)?,
+        None => Vec::new(),
+    };
+    decision.budget = budgets::status(conn, now)?
+        .into_iter()
+        .filter(|s| applies_to(s, &domain, &chain))
+        .max_by(|a, b| a.percent.total_cmp(&b.percent));
+    if let Some(b) = &decision.budget {
+        let action = if b.exceeded {
+            config.budget_action
+        } else if config.warn_percent > 0 && b.percent >= config.warn_percent as f64 {
+            config.budget_action.min(PolicyAction::Warn)
+        } else {
+            PolicyAction::Allow
+        };
+        if action > PolicyAction::Allow {
+            decision.action = action;
+            decision.reason = Some(PolicyReason::Budget);
+            decision.message = Some(if b.exceeded {
+                format!("{}: 今日の上限（{}分）を使い切りました", b.label, b.daily_limit_seconds / 60)
+            } else {
+                format!("{}: 今日の上限の{}%を使っています（残り{}分）", b.label, b.percent.floor(), b.remaining_seconds / 60)
+            });
+        }
+    }
+
+    // 集中時間は waste に分類されたドメインだけが対象
+    if decision.label.as_deref() == Some("waste") {
+        if let Some(until) = config.focus_until(now) {
+            decision.focus_until = Some(until.to_rfc3339_opts(SecondsFormat::Secs, false));
+            if config.focus_action > decision.action {
+                decision.action = config.focus_action;
+                decision.reason = Some(PolicyReason::Focus);
+                decision.message = Some(format!("集中時間中です（{}まで）", until.format("%H:%M")));
+            }
+        }
+    }
+    Ok(decision)
+}
@@ -0,0 +1,405 @@
+// 記録・マスタ・設定の読み書き（sessions / browsing_sessions / waste_categories / domains / user_settings）
+// Tauri コマンド・取り込みサーバ・サンプラーはここを通して DB に触る（SQL をコマンドに書かない）
+// SqliteRepository は任意の Connection（Transaction も可）の上で動くので、インメモリDBでテストできる
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::categories;
+use crate::error::{AppError, AppResult};
+use crate::rules::{self, RuleSet};
+
+// ====== models ======
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub start_time: String,
+    pub duration_seconds: i64,
+    pub session_key: String,
+    // session_key を展開した列（未指定なら session_key から補完）
+    pub category: Option<String>,
+    pub identifier: Option<String>,
+    pub user_state: Option<String>,
+    pub window_title: Option<String>,
+    // 手入力・手修正した行（読み取り専用。upsert_session では変更しない）
+    #[serde(default)]
+    pub is_manual: bool,
+    // 分類結果（waste_categories.id、読み取り専用。保存時にルール/識別子から決める）
+    #[serde(default)]
+    pub category_id: Option<i64>,
+}
+
+// session_key（category=app;identifier=chrome.exe;user_state=active）の各要素
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SessionKeyParts {
+    category: Option<String>,
+    identifier: Option<String>,
+    user_state: Option<String>,
+}
+
+impl SessionKeyParts {
+    fn parse(key: &str) -> Self {
+        let mut parts = SessionKeyParts::default();
+        for part in key.split(';') {
+            if let Some((k, v)) = part.split_once('=') {
+                if v.is_empty() {
+                    continue;
+                }
+                match k {
+                    "category" => parts.category = Some(v.to_string()),
+                    "identifier" => parts.identifier = Some(v.to_lowercase()),
+                    "user_state" => parts.user_state = Some(v.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        parts
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct SessionsQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub category: Option<String>,
+    pub identifier: Option<String>,
+    pub user_state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WasteCategory {
+    pub id: Option<i64>,
+    pub r#type: String,
+    pub identifier: String,
+    pub label: String, // "waste" | "productive"（category_id があればカテゴリの重みから決まる）
+    pub is_active: bool,
+    #[serde(default)]
+    pub category_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BrowsingSession {
+    pub id: String,
+    pub domain: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub start_time: String,
+    pub duration_seconds: i64,
+    pub category_id: Option<i64>,
+    pub tab_id: Option<i32>,
+    #[serde(default)]
+    pub is_manual: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct BrowsingSessionsQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub domain: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Domain {
+    pub id: Option<i64>,
+    pub domain: String,
+    pub category_id: Option<i64>,
+    pub is_active: bool,
+}
+
+// ====== trait ======
+pub trait Repository {
+    /// 計測したセッションを保存する（分類はルール/識別子から決める。手修正済みの行は上書きしない）
+    fn upsert_session(&self, session: &Session) -> AppResult<()>;
+    /// 開始時刻の昇順
+    fn list_sessions(&self, query: &SessionsQuery) -> AppResult<Vec<Session>>;
+    fn delete_session(&self, id: &str) -> AppResult<()>;
+
+    /// 手修正済みの行は上書きしない
+    fn upsert_browsing_session(&self, session: &BrowsingSession) -> AppResult<()>;
+    /// 開始時刻の降順
+    fn list_browsing_sessions(&self, query: &BrowsingSessionsQuery) -> AppResult<Vec<BrowsingSession>>;
+    /// 同じタブ・URL で開始時刻が [since, until] の計測行（手修正済みを除く、開始順）
+    fn list_tab_browsing_sessions(&self, tab_id: i32, url: &str, since: &str, until: &str) -> AppResult<Vec<BrowsingSession>>;
+    /// 区間を広げる。latest（タイトル, 分類）を渡せばそれも更新する
+    fn extend_browsing_session(&self, id: &str, start_time: &str, duration_seconds: i64, latest: Option<(&str, Option<i64>)>) -> AppResult<()>;
+    fn delete_browsing_session(&self, id: &str) -> AppResult<()>;
+
+    fn list_waste_categories(&self) -> AppResult<Vec<WasteCategory>>;
+    /// (type, identifier) で上書きし、未分類のセッションにも反映する
+    fn upsert_waste_category(&self, category: &WasteCategory) -> AppResult<()>;
+    fn delete_waste_category(&self, id: i64) -> AppResult<()>;
+
+    fn list_domains(&self) -> AppResult<Vec<Domain>>;
+    fn upsert_domain(&self, domain: &Domain) -> AppResult<()>;
+
+    fn get_setting(&self, key: &str) -> AppResult<Option<String>>;
+    fn set_setting(&self, key: &str, value: &str) -> AppResult<()>;
+}
+
+// ====== JSON 設定 ======
+/// user_settings に JSON で保存した設定を読む。未保存・読めない値・validate に通らない値は既定値
+pub fn load_json_setting<T>(repo: &impl Repository, key: &str, validate: impl Fn(&T) -> Result<(), String>) -> T
+where
+    T: DeserializeOwned + Default,
+{
+    repo.get_setting(key)
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str::<T>(&v).ok())
+        .filter(|c| validate(c).is_ok())
+        .unwrap_or_default()
+}
+
+pub fn save_json_setting<T: Serialize>(repo: &impl Repository, key: &str, value: &T) -> AppResult<()> {
+    let json = serde_json::to_string(value).map_err(|e| AppError::internal(format!("failed to serialize {}: {}", key, e)))?;
+    repo.set_setting(key, &json)
+}
+
+// ====== SQLite ======
+pub struct SqliteRepository<'c> {
+    conn: &'c Connection,
+    // upsert_session の分類に使う（未指定なら呼び出しごとに読み込む）
+    rules: Option<&'c RuleSet>,
+}
+
+impl<'c> SqliteRepository<'c> {
+    pub fn new(conn: &'c Connection) -> Self {
+        SqliteRepository { conn, rules: None }
+    }
+
+    /// 読み込み済みのルールで分類する（サンプラーなど、続けて書き込む場合）
+    pub fn with_rules(conn: &'c Connection, rules: &'c RuleSet) -> Self {
+        SqliteRepository { conn, rules: Some(rules) }
+    }
+}
+
+const SESSION_COLUMNS: &str =
+    "id, start_time, duration_seconds, session_key, category, identifier, user_state, window_title, is_manual, category_id";
+const BROWSING_COLUMNS: &str = "id, domain, url, title, start_time, COALESCE(duration_seconds, 0), category_id, tab_id, is_manual";
+
+fn read_session(row: &Row) -> rusqlite::Result<Session> {
+    Ok(Session {
+        id: row.get(0)?,
+        start_time: row.get(1)?,
+        duration_seconds: row.get(2)?,
+        session_key: row.get(3)?,
+        category: row.get(4)?,
+        identifier: row.get(5)?,
+        user_state: row.get(6)?,
+        window_title: row.get(7)?,
+        is_manual: row.get(8)?,
+        category_id: row.get(9)?,
+    })
+}
+
+fn read_browsing_session(row: &Row) -> rusqlite::Result<BrowsingSession> {
+    Ok(BrowsingSession {
+        id: row.get(0)?,
+        domain: row.get(1)?,
+        url: row.get(2)?,
+        title: row.get(3)?,
+        start_time: row.get(4)?,
+        duration_seconds: row.get(5)?,
+        category_id: row.get(6)?,
+        tab_id: row.get(7)?,
+        is_manual: row.get(8)?,
+    })
+}
+
+// WHERE 句を組み立てて実行する（binds は clauses の ? と同じ順）
+fn query_where<T>(
+    conn: &Connection,
+    base: &str,
+    clauses: &[&str],
+    binds: &[String],
+    order: &str,
+    read: fn(&Row) -> rusqlite::Result<T>,
+) -> AppResult<Vec<T>> {
+    let mut sql = base.to_string();
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(order);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(binds.iter()), read)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+impl Repository for SqliteRepository<'_> {
+    fn upsert_session(&self, session: &Session) -> AppResult<()> {
+        let parts = SessionKeyParts::parse(&session.session_key);
+        let category = session.category.clone().or(parts.category);
+        let identifier = session.identifier.as_ref().map(|v| v.to_lowercase()).or(parts.identifier);
+        let user_state = session.user_state.clone().or(parts.user_state);
+        let category_id = match self.rules {
+            Some(set) => rules::classify_session_with(self.conn, set, identifier.as_deref(), session.window_title.as_deref())?,
+            None => rules::classify_session_with(self.conn, &RuleSet::load(self.conn)?, identifier.as_deref(), session.window_title.as_deref())?,
+        };
+        // タイトルなしの更新では既存のタイトルを残すので、分類も既存の値を残す
+        self.conn.execute(
+            "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state, window_title, category_id) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(id) DO UPDATE SET start_time=excluded.start_time, duration_seconds=excluded.duration_seconds, session_key=excluded.session_key,
+               category=excluded.category, identifier=excluded.identifier, user_state=excluded.user_state,
+               window_title=COALESCE(excluded.window_title, sessions.window_title),
+               category_id=CASE WHEN excluded.window_title IS NULL AND sessions.window_title IS NOT NULL THEN sessions.category_id ELSE excluded.category_id END,
+               updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')
+             WHERE sessions.is_manual = 0",
+            params![session.id, session.start_time, session.duration_seconds, session.session_key, category, identifier, user_state, session.window_title, category_id],
+        )?;
+        Ok(())
+    }
+
+    fn list_sessions(&self, query: &SessionsQuery) -> AppResult<Vec<Session>> {
+        let mut clauses: Vec<&str> = Vec::new();
+        let mut binds: Vec<String> = Vec::new();
+        if let Some(s) = query.since.as_ref() { clauses.push("start_time >= ?"); binds.push(s.clone()); }
+        if let Some(u) = query.until.as_ref() { clauses.push("start_time < ?"); binds.push(u.clone()); }
+        if let Some(c) = query.category.as_ref() { clauses.push("category = ?"); binds.push(c.clone()); }
+        if let Some(i) = query.identifier.as_ref() { clauses.push("identifier = ?"); binds.push(i.to_lowercase()); }
+        if let Some(st) = query.user_state.as_ref() { clauses.push("user_state = ?"); binds.push(st.clone()); }
+        let base = format!("SELECT {} FROM sessions", SESSION_COLUMNS);
+        query_where(self.conn, &base, &clauses, &binds, " ORDER BY start_time ASC", read_session)
+    }
+
+    fn delete_session(&self, id: &str) -> AppResult<()> {
+        self.conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn upsert_browsing_session(&self, session: &BrowsingSession) -> AppResult<()> {
+        self.conn.execute(
+            "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id)
+             VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(id) DO UPDATE SET
+               domain=excluded.domain, url=excluded.url, title=excluded.title,
+               duration_seconds=excluded.duration_seconds, category_id=excluded.category_id,
+               updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')
+             WHERE browsing_sessions.is_manual = 0",
+            params![
+                session.id,
+                session.domain,
+                session.url,
+                session.title,
+                session.start_time,
+                session.duration_seconds,
+                session.category_id,
+                session.tab_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn list_browsing_sessions(&self, query: &BrowsingSessionsQuery) -> AppResult<Vec<BrowsingSession>> {
+        let mut clauses: Vec<&str> = Vec::new();
+        let mut binds: Vec<String> = Vec::new();
+        if let Some(s) = query.since.as_ref() { clauses.push("start_time >= ?"); binds.push(s.clone()); }
+        if let Some(u) = query.until.as_ref() { clauses.push("start_time < ?"); binds.push(u.clone()); }
+        if let Some(d) = query.domain.as_ref() { clauses.push("domain = ?"); binds.push(d.clone()); }
+        let base = format!("SELECT {} FROM browsing_sessions", BROWSING_COLUMNS);
+        query_where(self.conn, &base, &clauses, &binds, " ORDER BY start_time DESC", read_browsing_session)
+    }
+
+    fn list_tab_browsing_sessions(&self, tab_id: i32, url: &str, since: &str, until: &str) -> AppResult<Vec<BrowsingSession>> {
+        let sql = format!(
+            "SELECT {} FROM browsing_sessions \
+             WHERE tab_id = ?1 AND url = ?2 AND is_manual = 0 AND start_time >= ?3 AND start_time <= ?4 \
+             ORDER BY start_time, id",
+            BROWSING_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params![tab_id, url, since, until], read_browsing_session)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn extend_browsing_session(&self, id: &str, start_time: &str, duration_seconds: i64, latest: Option<(&str, Option<i64>)>) -> AppResult<()> {
+        self.conn.execute(
+            "UPDATE browsing_sessions SET start_time = ?2, duration_seconds = ?3, \
+               title = CASE WHEN ?4 THEN ?5 ELSE title END, category_id = CASE WHEN ?4 THEN ?6 ELSE category_id END, \
+               updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') \
+             WHERE id = ?1",
+            params![id, start_time, duration_seconds, latest.is_some(), latest.map(|(t, _)| t), latest.and_then(|(_, c)| c)],
+        )?;
+        Ok(())
+    }
+
+    fn delete_browsing_session(&self, id: &str) -> AppResult<()> {
+        self.conn.execute("DELETE FROM browsing_sessions WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn list_waste_categories(&self) -> AppResult<Vec<WasteCategory>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, type, identifier, label, is_active, category_id FROM waste_categories WHERE is_active IN (0,1) ORDER BY type, identifier",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(WasteCategory {
+                id: row.get(0)?,
+                r#type: row.get(1)?,
+                identifier: row.get(2)?,
+                label: row.get(3)?,
+                is_active: row.get::<_, i64>(4)? != 0,
+                category_id: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn upsert_waste_category(&self, category: &WasteCategory) -> AppResult<()> {
+        self.conn.execute(
+            "INSERT INTO waste_categories(type, identifier, label, is_active, category_id) VALUES(?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(type, identifier) DO UPDATE SET label=excluded.label, is_active=excluded.is_active,
+               category_id=COALESCE(excluded.category_id, waste_categories.category_id), updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
+            params![category.r#type, category.identifier, category.label, category.is_active as i64, category.category_id],
+        )?;
+        categories::sync_labels(self.conn)?;
+        // 登録前に記録された（未分類の）セッションにも反映する
+        if category.r#type == "app" {
+            rules::fill_unclassified_sessions(self.conn, &category.identifier)?;
+        }
+        Ok(())
+    }
+
+    fn delete_waste_category(&self, id: i64) -> AppResult<()> {
+        self.conn.execute("DELETE FROM waste_categories WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn list_domains(&self) -> AppResult<Vec<Domain>> {
+        let mut stmt = self.conn.prepare("SELECT id, domain, category_id, is_active FROM domains WHERE is_active IN (0,1) ORDER BY domain")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Domain {
+                id: row.get(0)?,
+                domain: row.get(1)?,
+                category_id: row.get(2)?,
+                is_active: row.get::<_, i64>(3)? != 0,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn upsert_domain(&self, domain: &Domain) -> AppResult<()> {
+        self.conn.execute(
+            "INSERT INTO domains(domain, category_id, is_active) VALUES(?1, ?2, ?3)
+             ON CONFLICT(domain) DO UPDATE SET category_id=excluded.category_id, is_active=excluded.is_active, updated_at=strftime('%Y-%m-%dT%H:%M:%fZ','now')",
+            params![domain.domain, domain.category_id, domain.is_active as i64],
+        )?;
+        Ok(())
+    }
+
+    fn get_setting(&self, key: &str) -> AppResult<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM user_settings WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
+        self.conn.execute(
+            "INSERT INTO user_settings(key, value, updated_at) VALUES(?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+// データ保持ポリシー: 保持期間を過ぎた sessions / browsing_sessions を日別集計テーブルにまとめてから削除し、
+// 空いたページを incremental vacuum で解放する。集計テーブルは削除しない（集計は無期限に残る）
+// 設定は user_settings（retention_config）に JSON で保存する
+// 集計済みの範囲は user_settings（retention_watermark）に記録し、import はそれより前の生データを取り込まない
+use chrono::{DateTime, Duration, Local, NaiveTime, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::repository::{load_json_setting, save_json_setting, Repository, SqliteRepository};
+
+const CONFIG_SETTING_KEY: &str = "retention_config";
+const WATERMARK_SETTING_KEY: &str = "retention_watermark";
+// これより短い保持期間は誤設定とみなす
+const MIN_RAW_DAYS: u32 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RetentionConfig {
+    /// 既定は無効（有効にするまで生データは削除しない）
+    pub enabled: bool,
+    /// 生データを残す日数（今日を含まない、ローカル日付の境界で判定）
+    pub raw_days: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self { enabled: false, raw_days: 90 }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct RetentionConfigPatch {
+    pub enabled: Option<bool>,
+    pub raw_days: Option<u32>,
+}
+
+impl RetentionConfig {
+    pub fn apply(&self, patch: RetentionConfigPatch) -> Result<RetentionConfig, String> {
+        let next = RetentionConfig {
+            enabled: patch.enabled.unwrap_or(self.enabled),
+            raw_days: patch.raw_days.unwrap_or(self.raw_days),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(MIN_RAW_DAYS..=36500).contains(&self.raw_days) {
+            return Err(format!("raw_days must be between {} and 36500", MIN_RAW_DAYS));
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> RetentionConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, RetentionConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+
+    /// この時刻より前に始まった生データが対象（ローカル日付の 0 時、UTC の ISO 文字列）
+    pub fn cutoff(&self, now: DateTime<Local>) -> String {
+        let day = now.date_naive() - Duration::days(self.raw_days as i64);
+        let midnight = Local
+            .from_local_datetime(&day.and_time(NaiveTime::MIN))
+            .earliest()
+            .unwrap_or(now);
+        midnight.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub cutoff: String,
+    /// 削除した（dry_run なら削除される）生データの行数
+    pub sessions_pruned: usize,
+    pub browsing_sessions_pruned: usize,
+    /// 追加/加算した日別集計の行数
+    pub daily_app_rows: usize,
+    pub daily_domain_rows: usize,
+    /// incremental vacuum で解放したページ数
+    pub vacuumed_pages: i64,
+}
+
+// 集計は加算で UPSERT する。同じ生データが二度集計されないよう、集計済みの範囲（watermark）より前の行は
+// import で取り込まない。手入力のセッションはその範囲にも追加できるが、新しいデータなので次回そのまま加算する
+const ROLLUP_SESSIONS_SQL: &str = "
+    INSERT INTO daily_app_summaries(day, identifier, user_state, duration_seconds, session_count)
+    SELECT date(start_time, 'localtime'), COALESCE(identifier, ''), COALESCE(user_state, ''),
+           SUM(duration_seconds), COUNT(*)
+    FROM sessions
+    WHERE start_time < ?1
+    GROUP BY 1, 2, 3
+    ON CONFLICT(day, identifier, user_state) DO UPDATE SET
+      duration_seconds = duration_seconds + excluded.duration_seconds,
+      session_count = session_count + excluded.session_count,
+      updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')";
+
+const ROLLUP_BROWSING_SQL: &str = "
+    INSERT INTO daily_domain_summaries(day, domain, category_id, duration_seconds, session_count)
+    SELECT date(start_time, 'localtime'), domain, MAX(category_id),
+           SUM(COALESCE(duration_seconds, 0)), COUNT(*)
+    FROM browsing_sessions
+    WHERE start_time < ?1
+    GROUP BY 1, 2
+    ON CONFLICT(day, domain) DO UPDATE SET
+      category_id = COALESCE(excluded.category_id, category_id),
+      duration_seconds = duration_seconds + excluded.duration_seconds,
+      session_count = session_count + excluded.session_count,
+      updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')";
+
+/// 保持期間を過ぎた生データを集計して削除する。dry_run ならトランザクションを巻き戻して件数だけ返す
+pub fn run(conn: &mut Connection, config: &RetentionConfig, now: DateTime<Local>, dry_run: bool) -> AppResult<RetentionReport> {
+    config.validate()?;
+    let cutoff = config.cutoff(now);
+    let mut report = RetentionReport { dry_run, cutoff: cutoff.clone(), ..Default::default() };
+
+    let tx = conn.transaction()?;
+    report.daily_app_rows = tx.execute(ROLLUP_SESSIONS_SQL, params![cutoff])?;
+    report.daily_domain_rows = tx.execute(ROLLUP_BROWSING_SQL, params![cutoff])?;
+    report.sessions_pruned = tx.execute("DELETE FROM sessions WHERE start_time < ?1", params![cutoff])?;
+    report.browsing_sessions_pruned = tx.execute("DELETE FROM browsing_sessions WHERE start_time < ?1", params![cutoff])?;
+    if dry_run {
+        tx.rollback()?;
+        return Ok(report);
+    }
+    // raw_days を延ばしても集計済みの範囲は縮めない
+    if watermark(&tx)?.is_none_or(|w| w < cutoff) {
+        SqliteRepository::new(&tx).set_setting(WATERMARK_SETTING_KEY, &cutoff)?;
+    }
+    tx.commit()?;
+
+    if report.sessions_pruned + report.browsing_sessions_pruned > 0 {
+        report.vacuumed_pages = incremental_vacuum(conn)?;
+    }
+    Ok(report)
+}
+
+/// この時刻より前の生データは日別集計にまとめ済み（retention を一度も実行していなければ None）
+pub fn watermark(conn: &Connection) -> AppResult<Option<String>> {
+    SqliteRepository::new(conn).get_setting(WATERMARK_SETTING_KEY)
+}
+
+// PRAGMA auto_vacuum の値
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+
+/// auto_vacuum を INCREMENTAL に切り替える。既存DBでは VACUUM でファイルを作り直すので、
+/// 起動時（DB をアプリの状態に登録する前）に呼ぶ。切り替えた場合は true
+pub fn enable_incremental_vacuum(conn: &Connection) -> AppResult<bool> {
+    let mode: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+    if mode == AUTO_VACUUM_INCREMENTAL {
+        return Ok(false);
+    }
+    conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")?;
+    Ok(true)
+}
+
+/// 空きページを解放する。auto_vacuum が INCREMENTAL でなければ何もしない（フル VACUUM はしない）
+pub fn incremental_vacuum(conn: &Connection) -> AppResult<i64> {
+    let mode: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+    if mode != AUTO_VACUUM_INCREMENTAL {
+        log::warn!("auto_vacuum is not INCREMENTAL; skipping vacuum");
+        return Ok(0);
+    }
+    let free_before: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA incremental_vacuum;")?;
+    let free_after: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    Ok(free_before - free_after)
+}
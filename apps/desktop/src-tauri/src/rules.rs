@@ -0,0 +1,467 @@
+// パターンによる分類ルール: アプリ（識別子 + ウィンドウタイトル）とドメイン（ワイルドカード/パス前方一致 + タイトル）を
+// priority の大きい順に評価して category_id（waste_categories.id）を決める。一致しなければ domains の完全一致にフォールバック
+// 記録時（サンプラー / upsert_browser_data / import / 手入力）と db_reclassify_sessions / db_reclassify_browsing_sessions で使う
+// ルールは呼び出しごとではなく、バッチ/トランザクションごとに読み込むか RuleCache から取って渡す
+// ブラウザのアプリのセッションは、ウィンドウタイトルからサイトが分かればドメインと同じ規則で分類する（browser_title）
+use regex::{Regex, RegexBuilder};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::browser_title::BrowserTitles;
+use crate::error::{AppError, AppResult};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTarget {
+    /// pattern は識別子（chrome.exe など、* でワイルドカード）
+    App,
+    /// pattern は `youtube.com` / `*.youtube.com` / `youtube.com/shorts` の形式
+    Domain,
+}
+
+impl RuleTarget {
+    fn as_str(self) -> &'static str {
+        match self {
+            RuleTarget::App => "app",
+            RuleTarget::Domain => "domain",
+        }
+    }
+
+    fn parse(value: &str) -> Option<RuleTarget> {
+        match value {
+            "app" => Some(RuleTarget::App),
+            "domain" => Some(RuleTarget::Domain),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ClassificationRule {
+    pub id: i64,
+    pub target: RuleTarget,
+    pub pattern: String,
+    /// タイトル（ウィンドウタイトル/タブのタイトル）に対する正規表現。大文字小文字は区別しない
+    pub title_regex: Option<String>,
+    pub category_id: i64,
+    /// 大きいほど先に評価する（同じ priority なら id の小さい順）
+    pub priority: i64,
+    pub is_active: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewRule {
+    pub target: RuleTarget,
+    pub pattern: String,
+    pub title_regex: Option<String>,
+    pub category_id: i64,
+    #[serde(default)]
+    pub priority: i64,
+    /// 既定は有効
+    pub is_active: Option<bool>,
+}
+
+// 部分更新用（未指定の項目は現在値を維持、title_regex を外すときは空文字）
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct RulePatch {
+    pub pattern: Option<String>,
+    pub title_regex: Option<String>,
+    pub category_id: Option<i64>,
+    pub priority: Option<i64>,
+    pub is_active: Option<bool>,
+}
+
+// ====== matching ======
+
+/// 一致したルール（UI でどのルールが効いたかを示す用）
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleMatch {
+    pub rule_id: i64,
+    pub category_id: i64,
+}
+
+// 評価用にパースしたルール
+#[derive(Debug)]
+struct CompiledRule {
+    id: i64,
+    target: RuleTarget,
+    host: String,
+    path_prefix: Option<String>,
+    title: Option<Regex>,
+    category_id: i64,
+}
+
+/// 有効なルールを評価順に並べたもの
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    browser: BrowserTitles,
+}
+
+fn normalize_pattern(pattern: &str) -> Result<String, String> {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.is_empty() {
+        return Err("pattern is required".to_string());
+    }
+    Ok(pattern)
+}
+
+fn compile_title(title_regex: Option<&str>) -> Result<Option<Regex>, String> {
+    match title_regex.map(str::trim).filter(|r| !r.is_empty()) {
+        None => Ok(None),
+        Some(r) => RegexBuilder::new(r)
+            .case_insensitive(true)
+            .build()
+            .map(Some)
+            .map_err(|e| format!("invalid title_regex: {}", e)),
+    }
+}
+
+fn compile(id: i64, target: RuleTarget, pattern: &str, title_regex: Option<&str>, category_id: i64) -> Result<CompiledRule, String> {
+    let pattern = normalize_pattern(pattern)?;
+    let (host, path_prefix) = match target {
+        RuleTarget::App => (pattern, None),
+        RuleTarget::Domain => match pattern.split_once('/') {
+            Some((host, path)) => (host.to_string(), Some(format!("/{}", path))),
+            None => (pattern, None),
+        },
+    };
+    if host.is_empty() {
+        return Err("pattern must include a host".to_string());
+    }
+    Ok(CompiledRule { id, target, host, path_prefix, title: compile_title(title_regex)?, category_id })
+}
+
+// `*` を任意の文字列として一致（大文字小文字は呼び出し側で揃える）
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// `*.example.com` はサブドメインに加えて example.com 自体にも一致
+fn host_matches(pattern: &str, host: &str) -> bool {
+    if let Some(apex) = pattern.strip_prefix("*.") {
+        if host == apex {
+            return true;
+        }
+    }
+    glob_match(pattern, host)
+}
+
+// URL のパス部分（クエリ/フラグメントを除く）。スキームがなければ先頭の / 以降
+fn url_path(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let path = rest.find('/').map(|i| &rest[i..]).unwrap_or("/");
+    path.split(['?', '#']).next().unwrap_or("/")
+}
+
+impl CompiledRule {
+    fn to_match(&self) -> RuleMatch {
+        RuleMatch { rule_id: self.id, category_id: self.category_id }
+    }
+
+    fn title_matches(&self, title: Option<&str>) -> bool {
+        match &self.title {
+            None => true,
+            Some(re) => title.is_some_and(|t| re.is_match(t)),
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn load(conn: &Connection) -> rusqlite::Result<RuleSet> {
+        let mut stmt = conn.prepare(
+            "SELECT id, target, pattern, title_regex, category_id FROM classification_rules
+             WHERE is_active = 1 ORDER BY priority DESC, id ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?, row.get::<_, i64>(4)?))
+        })?;
+        let mut rules = Vec::new();
+        for r in rows {
+            let (id, target, pattern, title_regex, category_id) = r?;
+            // 壊れたルール（手で書き換えた行など）は評価から外す
+            let Some(target) = RuleTarget::parse(&target) else { continue };
+            match compile(id, target, &pattern, title_regex.as_deref(), category_id) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => log::warn!("Skipping classification rule {}: {}", id, e),
+            }
+        }
+        Ok(RuleSet { rules, browser: BrowserTitles::load(conn)? })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// アプリのセッションに一致した最初のルール
+    pub fn match_app(&self, identifier: &str, window_title: Option<&str>) -> Option<RuleMatch> {
+        let identifier = identifier.to_lowercase();
+        self.rules
+            .iter()
+            .filter(|r| r.target == RuleTarget::App)
+            .find(|r| glob_match(&r.host, &identifier) && r.title_matches(window_title))
+            .map(CompiledRule::to_match)
+    }
+
+    /// 閲覧セッションに一致した最初のルール（パスは url から取る）
+    pub fn match_browsing(&self, domain: &str, url: &str, title: Option<&str>) -> Option<RuleMatch> {
+        let domain = domain.to_lowercase();
+        let path = url_path(url).to_lowercase();
+        self.rules
+            .iter()
+            .filter(|r| r.target == RuleTarget::Domain)
+            .find(|r| {
+                host_matches(&r.host, &domain)
+                    && r.path_prefix.as_deref().is_none_or(|p| path.starts_with(p))
+                    && r.title_matches(title)
+            })
+            .map(CompiledRule::to_match)
+    }
+
+    pub fn classify_app(&self, identifier: &str, window_title: Option<&str>) -> Option<i64> {
+        self.match_app(identifier, window_title).map(|m| m.category_id)
+    }
+
+    pub fn classify_browsing(&self, domain: &str, url: &str, title: Option<&str>) -> Option<i64> {
+        self.match_browsing(domain, url, title).map(|m| m.category_id)
+    }
+}
+
+/// ルール → domains（完全一致）の順で閲覧セッションの category_id を決める
+pub fn classify_browsing_with(conn: &Connection, rules: &RuleSet, domain: &str, url: &str, title: Option<&str>) -> rusqlite::Result<Option<i64>> {
+    if let Some(category_id) = rules.classify_browsing(domain, url, title) {
+        return Ok(Some(category_id));
+    }
+    Ok(conn
+        .query_row("SELECT category_id FROM domains WHERE domain = ?1 AND is_active = 1", params![domain], |row| row.get(0))
+        .optional()?
+        .flatten())
+}
+
+/// ルール → ウィンドウタイトルから分かったサイト（ブラウザのみ）→ waste_categories（type=app の識別子の完全一致）の順で
+/// アプリのセッションの category_id を決める
+pub fn classify_session_with(conn: &Connection, rules: &RuleSet, identifier: Option<&str>, window_title: Option<&str>) -> rusqlite::Result<Option<i64>> {
+    let Some(identifier) = identifier.map(str::to_lowercase) else { return Ok(None) };
+    if let Some(category_id) = rules.classify_app(&identifier, window_title) {
+        return Ok(Some(category_id));
+    }
+    let parsed = window_title.and_then(|t| rules.browser.parse(&identifier, t));
+    if let Some((site, page_title)) = parsed.and_then(|p| p.site.map(|s| (s, p.page_title))) {
+        let url = format!("https://{}/", site);
+        if let Some(category_id) = classify_browsing_with(conn, rules, &site, &url, Some(page_title.as_str()))? {
+            return Ok(Some(category_id));
+        }
+    }
+    conn.query_row(
+        "SELECT id FROM waste_categories WHERE type = 'app' AND identifier = ?1 AND is_active = 1",
+        params![identifier],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// 読み込んだ RuleSet を使い回すためのキャッシュ（アプリの状態と取り込みサーバが持つ）
+/// ルール・domains・ブラウザタイトル設定が変わるとトリガーが rule_set_token を作り直すので、次の get で読み直す
+#[derive(Debug, Default)]
+pub struct RuleCache {
+    cached: Mutex<Option<(Vec<u8>, Arc<RuleSet>)>>,
+}
+
+impl RuleCache {
+    pub fn get(&self, conn: &Connection) -> rusqlite::Result<Arc<RuleSet>> {
+        let token: Vec<u8> = conn.query_row("SELECT token FROM rule_set_token WHERE id = 1", [], |row| row.get(0))?;
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_token, rules)) = cached.as_ref() {
+            if *cached_token == token {
+                return Ok(rules.clone());
+            }
+        }
+        let rules = Arc::new(RuleSet::load(conn)?);
+        *cached = Some((token, rules.clone()));
+        Ok(rules)
+    }
+}
+
+// (id, domain, url, title, category_id)
+type ReclassifyRow = (String, String, String, Option<String>, Option<i64>);
+
+/// 期間内の browsing_sessions を現在のルール/domains で分類し直し、category_id が変わった行数を返す
+/// 手入力・手修正した行（is_manual=1）は対象外（編集で選んだ分類を残す）
+pub fn reclassify_browsing_sessions(conn: &mut Connection, since: Option<&str>, until: Option<&str>) -> rusqlite::Result<u64> {
+    let tx = conn.transaction()?;
+    let rules = RuleSet::load(&tx)?;
+    let rows: Vec<ReclassifyRow> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, domain, url, title, category_id FROM browsing_sessions
+             WHERE is_manual = 0 AND (?1 IS NULL OR start_time >= ?1) AND (?2 IS NULL OR start_time < ?2)",
+        )?;
+        let mapped = stmt.query_map(params![since, until], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))?;
+        mapped.collect::<rusqlite::Result<_>>()?
+    };
+    let mut affected = 0u64;
+    for (id, domain, url, title, current) in rows {
+        let next = classify_browsing_with(&tx, &rules, &domain, &url, title.as_deref())?;
+        if next != current {
+            tx.execute(
+                "UPDATE browsing_sessions SET category_id = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+                params![id, next],
+            )?;
+            affected += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(affected)
+}
+
+// (id, identifier, window_title, category_id)
+type ReclassifySessionRow = (String, Option<String>, Option<String>, Option<i64>);
+
+/// 期間内の sessions を現在のルール/waste_categories で分類し直し、category_id が変わった行数を返す
+/// 手入力・手修正した行（is_manual=1）は対象外
+pub fn reclassify_sessions(conn: &mut Connection, since: Option<&str>, until: Option<&str>) -> rusqlite::Result<u64> {
+    let tx = conn.transaction()?;
+    let rules = RuleSet::load(&tx)?;
+    let rows: Vec<ReclassifySessionRow> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, identifier, window_title, category_id FROM sessions
+             WHERE is_manual = 0 AND (?1 IS NULL OR start_time >= ?1) AND (?2 IS NULL OR start_time < ?2)",
+        )?;
+        let mapped = stmt.query_map(params![since, until], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        mapped.collect::<rusqlite::Result<_>>()?
+    };
+    let mut affected = 0u64;
+    for (id, identifier, window_title, current) in rows {
+        let next = classify_session_with(&tx, &rules, identifier.as_deref(), window_title.as_deref())?;
+        if next != current {
+            tx.execute(
+                "UPDATE sessions SET category_id = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now') WHERE id = ?1",
+                params![id, next],
+            )?;
+            affected += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(affected)
+}
+
+/// アプリを waste_categories に登録したときに、まだ分類されていないその識別子の sessions を割り当てる
+pub fn fill_unclassified_sessions(conn: &Connection, identifier: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE sessions SET category_id = (
+           SELECT wc.id FROM waste_categories wc WHERE wc.type = 'app' AND wc.identifier = sessions.identifier AND wc.is_active = 1
+         ), updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE identifier = ?1 AND category_id IS NULL
+           AND EXISTS (SELECT 1 FROM waste_categories wc WHERE wc.type = 'app' AND wc.identifier = ?1 AND wc.is_active = 1)",
+        params![identifier.to_lowercase()],
+    )
+}
+
+// ====== CRUD ======
+
+fn read_rule(row: &rusqlite::Row) -> rusqlite::Result<ClassificationRule> {
+    let target: String = row.get(1)?;
+    Ok(ClassificationRule {
+        id: row.get(0)?,
+        target: RuleTarget::parse(&target).unwrap_or(RuleTarget::Domain),
+        pattern: row.get(2)?,
+        title_regex: row.get(3)?,
+        category_id: row.get(4)?,
+        priority: row.get(5)?,
+        is_active: row.get::<_, i64>(6)? != 0,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+const RULE_COLUMNS: &str = "id, target, pattern, title_regex, category_id, priority, is_active, created_at, updated_at";
+
+/// 評価順（priority の大きい順）で全ルールを返す
+pub fn list_rules(conn: &Connection) -> AppResult<Vec<ClassificationRule>> {
+    let sql = format!("SELECT {} FROM classification_rules ORDER BY priority DESC, id ASC", RULE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], read_rule)?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+pub fn get_rule(conn: &Connection, id: i64) -> AppResult<ClassificationRule> {
+    let sql = format!("SELECT {} FROM classification_rules WHERE id = ?1", RULE_COLUMNS);
+    conn.query_row(&sql, params![id], read_rule)
+        .optional()?
+        .ok_or_else(|| AppError::not_found("rule", id))
+}
+
+pub(crate) fn ensure_category(conn: &Connection, category_id: i64) -> AppResult<()> {
+    conn.query_row("SELECT 1 FROM waste_categories WHERE id = ?1", params![category_id], |_| Ok(()))
+        .optional()?
+        .ok_or_else(|| AppError::not_found("category", category_id))
+}
+
+pub fn create_rule(conn: &Connection, rule: NewRule) -> AppResult<ClassificationRule> {
+    compile(0, rule.target, &rule.pattern, rule.title_regex.as_deref(), rule.category_id)?;
+    ensure_category(conn, rule.category_id)?;
+    let title_regex = rule.title_regex.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+    conn.execute(
+        "INSERT INTO classification_rules(target, pattern, title_regex, category_id, priority, is_active) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            rule.target.as_str(),
+            normalize_pattern(&rule.pattern)?,
+            title_regex,
+            rule.category_id,
+            rule.priority,
+            if rule.is_active.unwrap_or(true) { 1 } else { 0 }
+        ],
+    )?;
+    get_rule(conn, conn.last_insert_rowid())
+}
+
+pub fn update_rule(conn: &Connection, id: i64, patch: RulePatch) -> AppResult<ClassificationRule> {
+    let current = get_rule(conn, id)?;
+    let pattern = normalize_pattern(patch.pattern.as_deref().unwrap_or(&current.pattern))?;
+    let title_regex = match patch.title_regex {
+        Some(r) => Some(r.trim().to_string()).filter(|r| !r.is_empty()),
+        None => current.title_regex,
+    };
+    let category_id = patch.category_id.unwrap_or(current.category_id);
+    compile(id, current.target, &pattern, title_regex.as_deref(), category_id)?;
+    ensure_category(conn, category_id)?;
+    conn.execute(
+        "UPDATE classification_rules SET pattern = ?2, title_regex = ?3, category_id = ?4, priority = ?5, is_active = ?6,
+           updated_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+         WHERE id = ?1",
+        params![
+            id,
+            pattern,
+            title_regex,
+            category_id,
+            patch.priority.unwrap_or(current.priority),
+            if patch.is_active.unwrap_or(current.is_active) { 1 } else { 0 }
+        ],
+    )?;
+    get_rule(conn, id)
+}
+
+pub fn delete_rule(conn: &Connection, id: i64) -> AppResult<()> {
+    let deleted = conn.execute("DELETE FROM classification_rules WHERE id = ?1", params![id])?;
+    if deleted == 0 {
+        return Err(AppError::not_found("rule", id));
+    }
+    Ok(())
+}
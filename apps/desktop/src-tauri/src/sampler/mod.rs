@@ -0,0 +1,277 @@
+// Rust 側で常駐するサンプラー: 前面アプリ/アイドル秒を定期取得してセッション化し、sessions に直接保存する
+// UI（webview）はイベントを受け取って表示するだけにする
+mod sessionizer;
+
+use chrono::Utc;
+use log::{error, info};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub use sessionizer::{Sample, SessionEvent, SessionEventType, Sessionizer, UserState};
+
+use crate::browser_title::BrowserTitles;
+use crate::error::AppResult;
+use crate::repository::{load_json_setting, save_json_setting, Repository, Session, SqliteRepository};
+use crate::rules::{RuleCache, RuleSet};
+use crate::{Activity, Db};
+
+pub const SAMPLE_EVENT: &str = "sampling:sample";
+pub const SESSION_EVENT: &str = "sampling:session";
+
+const CONFIG_SETTING_KEY: &str = "sampler_config";
+// ブラウザのタイトルの設定と domains を読み直す間隔
+const BROWSER_TITLES_RELOAD_SECONDS: u64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SamplerConfig {
+    pub sampling_interval_ms: u64,
+    pub idle_gap_threshold_seconds: i64,
+    pub session_switch_grace_period_seconds: i64,
+    pub idle_threshold_seconds: u64,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            sampling_interval_ms: 5000,
+            idle_gap_threshold_seconds: 20,
+            session_switch_grace_period_seconds: 5,
+            idle_threshold_seconds: 60,
+        }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct SamplerConfigPatch {
+    pub sampling_interval_ms: Option<u64>,
+    pub idle_gap_threshold_seconds: Option<i64>,
+    pub session_switch_grace_period_seconds: Option<i64>,
+    pub idle_threshold_seconds: Option<u64>,
+}
+
+impl SamplerConfig {
+    pub fn apply(&self, patch: SamplerConfigPatch) -> Result<SamplerConfig, String> {
+        let next = SamplerConfig {
+            sampling_interval_ms: patch.sampling_interval_ms.unwrap_or(self.sampling_interval_ms),
+            idle_gap_threshold_seconds: patch.idle_gap_threshold_seconds.unwrap_or(self.idle_gap_threshold_seconds),
+            session_switch_grace_period_seconds: patch.session_switch_grace_period_seconds.unwrap_or(self.session_switch_grace_period_seconds),
+            idle_threshold_seconds: patch.idle_threshold_seconds.unwrap_or(self.idle_threshold_seconds),
+        };
+        next.validate()?;
+        Ok(next)
+    }
+
+    // 範囲は @wasteday/ingest の CONFIG_VALIDATION に合わせる
+    fn validate(&self) -> Result<(), String> {
+        if !(1000..=60000).contains(&self.sampling_interval_ms) {
+            return Err("sampling_interval_ms must be between 1000 and 60000 milliseconds".to_string());
+        }
+        if !(5..=300).contains(&self.idle_gap_threshold_seconds) {
+            return Err("idle_gap_threshold_seconds must be between 5 and 300 seconds".to_string());
+        }
+        if !(1..=60).contains(&self.session_switch_grace_period_seconds) {
+            return Err("session_switch_grace_period_seconds must be between 1 and 60 seconds".to_string());
+        }
+        if self.idle_threshold_seconds == 0 {
+            return Err("idle_threshold_seconds must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> SamplerConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, SamplerConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SamplePayload {
+    pub timestamp: String,
+    pub process_id: u32,
+    pub exe: String,
+    pub identifier: String,
+    pub window_title: String,
+    pub user_state: UserState,
+    pub idle_sec: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SamplerStatus {
+    pub is_running: bool,
+    pub last_sample_time: Option<String>,
+    pub error_count: u64,
+    pub open_sessions: Vec<String>,
+    pub config: SamplerConfig,
+}
+
+pub struct SamplerState {
+    pub config: Mutex<SamplerConfig>,
+    running: AtomicBool,
+    status: Mutex<SamplerStatus>,
+}
+
+impl SamplerState {
+    pub fn new(config: SamplerConfig) -> Self {
+        Self {
+            running: AtomicBool::new(true),
+            status: Mutex::new(SamplerStatus {
+                is_running: true,
+                last_sample_time: None,
+                error_count: 0,
+                open_sessions: Vec::new(),
+                config: config.clone(),
+            }),
+            config: Mutex::new(config),
+        }
+    }
+
+    pub fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::SeqCst);
+    }
+
+    pub fn status(&self) -> SamplerStatus {
+        let mut status = self.status.lock().map(|s| s.clone()).unwrap_or_else(|e| e.into_inner().clone());
+        status.is_running = self.running.load(Ordering::SeqCst);
+        if let Ok(cfg) = self.config.lock() {
+            status.config = cfg.clone();
+        }
+        status
+    }
+}
+
+// 終了/更新イベントを sessions 行として保存（id は従来の `${start_time}-${session_key}` と同じ）
+fn persist_event(conn: &Connection, rules: &RuleSet, event: &SessionEvent) -> AppResult<()> {
+    if event.kind == SessionEventType::Started || event.duration_seconds <= 0 {
+        return Ok(());
+    }
+    let session = Session {
+        id: format!("{}-{}", event.start_time, event.session_key),
+        start_time: event.start_time.clone(),
+        duration_seconds: event.duration_seconds,
+        session_key: event.session_key.clone(),
+        category: None,
+        identifier: None,
+        user_state: None,
+        window_title: event.window_title.clone().filter(|t| !t.is_empty()),
+        is_manual: false,
+        category_id: None,
+    };
+    SqliteRepository::with_rules(conn, rules).upsert_session(&session)
+}
+
+fn persist_events(app: &AppHandle, events: &[SessionEvent]) -> Result<(), String> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let db = app.try_state::<Db>().ok_or_else(|| "db not initialized".to_string())?;
+    let conn = db.0.lock().map_err(|_| "db lock poisoned".to_string())?;
+    let cache = app.try_state::<RuleCache>().ok_or_else(|| "rule cache not initialized".to_string())?;
+    let rules = cache.get(&conn).map_err(|e| e.to_string())?;
+    for event in events {
+        persist_event(&conn, &rules, event).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn load_browser_titles(app: &AppHandle) -> BrowserTitles {
+    let Some(db) = app.try_state::<Db>() else { return BrowserTitles::default() };
+    let loaded = match db.0.lock() {
+        Ok(conn) => BrowserTitles::load(&conn).map_err(|e| e.to_string()),
+        Err(_) => Err("db lock poisoned".to_string()),
+    };
+    loaded.unwrap_or_else(|e| {
+        error!("Failed to load browser title patterns: {}", e);
+        BrowserTitles::default()
+    })
+}
+
+fn take_sample(app: &AppHandle, config: &SamplerConfig, titles: &BrowserTitles) -> (Sample, SamplePayload) {
+    let activity = app.state::<Activity>();
+    let info = activity.0.foreground_info();
+    let idle_sec = activity.0.idle_seconds();
+    let user_state = if idle_sec >= config.idle_threshold_seconds { UserState::Idle } else { UserState::Active };
+    let timestamp = Utc::now();
+    let identifier = if info.exe.is_empty() { "unknown.exe".to_string() } else { info.exe.to_lowercase() };
+    let site = titles.parse(&identifier, &info.window_title).and_then(|p| p.site);
+
+    let sample = Sample {
+        timestamp,
+        category: "app".to_string(),
+        identifier: identifier.clone(),
+        window_title: Some(info.window_title.clone()),
+        site,
+        user_state,
+    };
+    let payload = SamplePayload {
+        timestamp: sessionizer::format_timestamp(&timestamp),
+        process_id: info.process_id,
+        exe: info.exe,
+        identifier,
+        window_title: info.window_title,
+        user_state,
+        idle_sec,
+    };
+    (sample, payload)
+}
+
+fn emit_events(app: &AppHandle, events: &[SessionEvent]) {
+    for event in events {
+        if let Err(e) = app.emit(SESSION_EVENT, event) {
+            error!("Failed to emit session event: {}", e);
+        }
+    }
+}
+
+/// setup から呼ぶ: サンプリングループを別スレッドで開始
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        info!("Sampler thread started");
+        let mut sessionizer = Sessionizer::new();
+        let mut titles = load_browser_titles(&app);
+        let mut titles_loaded_at = std::time::Instant::now();
+        loop {
+            let state = app.state::<SamplerState>();
+            let config = state.config.lock().map(|c| c.clone()).unwrap_or_default();
+            if titles_loaded_at.elapsed().as_secs() >= BROWSER_TITLES_RELOAD_SECONDS {
+                titles = load_browser_titles(&app);
+                titles_loaded_at = std::time::Instant::now();
+            }
+
+            if state.running.load(Ordering::SeqCst) {
+                let (sample, payload) = take_sample(&app, &config, &titles);
+                let events = sessionizer.process_sample(&sample, &config);
+                let result = persist_events(&app, &events);
+
+                if let Ok(mut status) = state.status.lock() {
+                    status.last_sample_time = Some(payload.timestamp.clone());
+                    status.open_sessions = sessionizer.open_session_keys();
+                    if let Err(e) = &result {
+                        error!("Failed to persist sessions: {}", e);
+                        status.error_count += 1;
+                    }
+                }
+                let _ = app.emit(SAMPLE_EVENT, &payload);
+                emit_events(&app, &events);
+            } else if !sessionizer.open_session_keys().is_empty() {
+                // 停止時は開いているセッションを確定
+                let events = sessionizer.flush_all();
+                if let Err(e) = persist_events(&app, &events) {
+                    error!("Failed to persist flushed sessions: {}", e);
+                }
+                if let Ok(mut status) = state.status.lock() {
+                    status.open_sessions.clear();
+                }
+                emit_events(&app, &events);
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(config.sampling_interval_ms));
+        }
+    });
+}
@@ -0,0 +1,211 @@
+// サンプル列をセッションにまとめる（@wasteday/ingest の SessionManager と同じ規則）
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::SamplerConfig;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserState {
+    Active,
+    Idle,
+}
+
+impl UserState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserState::Active => "active",
+            UserState::Idle => "idle",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub timestamp: DateTime<Utc>,
+    pub category: String,
+    pub identifier: String,
+    pub window_title: Option<String>,
+    /// ブラウザのウィンドウタイトルから分かったサイト（browser_title）
+    pub site: Option<String>,
+    pub user_state: UserState,
+}
+
+impl Sample {
+    // category=app;identifier=chrome.exe;user_state=active
+    pub fn session_key(&self) -> String {
+        format!(
+            "category={};identifier={};user_state={}",
+            self.category,
+            self.identifier.to_lowercase(),
+            self.user_state.as_str()
+        )
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventType {
+    #[serde(rename = "session_started")]
+    Started,
+    #[serde(rename = "session_updated")]
+    Updated,
+    #[serde(rename = "session_ended")]
+    Ended,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionEvent {
+    #[serde(rename = "type")]
+    pub kind: SessionEventType,
+    pub session_key: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub duration_seconds: i64,
+    pub is_idle: bool,
+    pub is_media_playing: bool,
+    pub window_title: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct OpenSession {
+    session_key: String,
+    start_time: DateTime<Utc>,
+    last_time: DateTime<Utc>,
+    is_idle: bool,
+    window_title: Option<String>,
+    site: Option<String>,
+}
+
+// JS の toISOString() と同じ形式（ミリ秒 + Z）
+pub fn format_timestamp(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn duration_seconds(start: &DateTime<Utc>, end: &DateTime<Utc>) -> i64 {
+    (*end - *start).num_seconds().max(0)
+}
+
+#[derive(Default)]
+pub struct Sessionizer {
+    open_sessions: BTreeMap<String, OpenSession>,
+}
+
+impl Sessionizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open_session_keys(&self) -> Vec<String> {
+        self.open_sessions.keys().cloned().collect()
+    }
+
+    /// サンプル1件を処理し、発生したセッションイベントを返す
+    pub fn process_sample(&mut self, sample: &Sample, config: &SamplerConfig) -> Vec<SessionEvent> {
+        let key = sample.session_key();
+        let mut events = self.end_other_sessions(&key, &sample.timestamp, config);
+        events.extend(self.handle_current_session(key, sample, config));
+        events
+    }
+
+    /// 開いているセッションをすべて終了させる
+    pub fn flush_all(&mut self) -> Vec<SessionEvent> {
+        let sessions = std::mem::take(&mut self.open_sessions);
+        sessions
+            .into_values()
+            .map(|s| {
+                let end = s.last_time;
+                Self::event(SessionEventType::Ended, &s, &end)
+            })
+            .collect()
+    }
+
+    // 現在のキー以外のセッションを終了（切り替え直後の短いセッションは猶予期間内なら残す）
+    fn end_other_sessions(&mut self, current_key: &str, now: &DateTime<Utc>, config: &SamplerConfig) -> Vec<SessionEvent> {
+        let sampling_interval_seconds = config.sampling_interval_ms as f64 / 1000.0;
+        let mut ended = Vec::new();
+        for (key, session) in self.open_sessions.iter() {
+            if key == current_key {
+                continue;
+            }
+            let gap_since_last_sample = (*now - session.last_time).num_seconds();
+            let total_duration = duration_seconds(&session.start_time, now);
+            let should_merge = gap_since_last_sample <= config.session_switch_grace_period_seconds
+                && (total_duration as f64) < sampling_interval_seconds;
+            if !should_merge {
+                ended.push(key.clone());
+            }
+        }
+        ended
+            .into_iter()
+            .filter_map(|key| self.open_sessions.remove(&key))
+            .map(|s| Self::event(SessionEventType::Ended, &s, now))
+            .collect()
+    }
+
+    fn handle_current_session(&mut self, key: String, sample: &Sample, config: &SamplerConfig) -> Vec<SessionEvent> {
+        let is_idle = sample.user_state == UserState::Idle;
+        let existing = match self.open_sessions.get_mut(&key) {
+            Some(s) => s,
+            None => return vec![self.start_session(key, sample, is_idle)],
+        };
+
+        let gap = (sample.timestamp - existing.last_time).num_seconds().abs();
+        let within_gap = gap <= config.idle_gap_threshold_seconds;
+        // ブラウザでサイトが変わったら、このサンプルの時刻で前のサイトのセッションを閉じて分ける（サイトごとに分類するため）
+        if within_gap && existing.site != sample.site {
+            let mut events = Vec::new();
+            if let Some(prev) = self.open_sessions.remove(&key) {
+                events.push(Self::event(SessionEventType::Ended, &prev, &sample.timestamp));
+            }
+            events.push(self.start_session(key, sample, is_idle));
+            return events;
+        }
+        if within_gap {
+            existing.last_time = sample.timestamp;
+            if sample.window_title.as_deref().is_some_and(|t| !t.is_empty()) {
+                existing.window_title = sample.window_title.clone();
+            }
+            let end = existing.last_time;
+            return vec![Self::event(SessionEventType::Updated, existing, &end)];
+        }
+
+        // ギャップが閾値を超えたら前のセッションを最終サンプル時刻で閉じて新規開始
+        let mut events = Vec::new();
+        if let Some(prev) = self.open_sessions.remove(&key) {
+            let end = prev.last_time;
+            events.push(Self::event(SessionEventType::Ended, &prev, &end));
+        }
+        events.push(self.start_session(key, sample, is_idle));
+        events
+    }
+
+    fn start_session(&mut self, key: String, sample: &Sample, is_idle: bool) -> SessionEvent {
+        let session = OpenSession {
+            session_key: key.clone(),
+            start_time: sample.timestamp,
+            last_time: sample.timestamp,
+            is_idle,
+            window_title: sample.window_title.clone(),
+            site: sample.site.clone(),
+        };
+        let event = Self::event(SessionEventType::Started, &session, &sample.timestamp);
+        self.open_sessions.insert(key, session);
+        event
+    }
+
+    fn event(kind: SessionEventType, session: &OpenSession, end: &DateTime<Utc>) -> SessionEvent {
+        SessionEvent {
+            kind,
+            session_key: session.session_key.clone(),
+            start_time: format_timestamp(&session.start_time),
+            end_time: format_timestamp(end),
+            duration_seconds: if kind == SessionEventType::Started { 0 } else { duration_seconds(&session.start_time, end) },
+            is_idle: session.is_idle,
+            is_media_playing: false,
+            window_title: session.window_title.clone(),
+            url: session.site.as_ref().map(|s| format!("https://{}/", s)),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+// 統合タイムライン: ブラウザ（chrome.exe など）が前面だった sessions の時間を browsing_sessions のドメイン別の内訳で置き換え、
+// 重なる閲覧記録がない残りは「browser (unknown tab)」（分類はブラウザのアプリのもの）として扱う
+// 他のアプリが前面だった時間の閲覧記録は数えず、sessions がない時間帯の閲覧記録はそのまま使う
+// 浪費/生産的の合計はこの重複のない区間から計算する（保持期間を過ぎて日別集計にまとめた分は含まない）
+// ブラウザとみなす識別子は user_settings（timeline_config）に JSON で保存する
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+
+const CONFIG_SETTING_KEY: &str = "timeline_config";
+pub const UNKNOWN_TAB_LABEL: &str = "browser (unknown tab)";
+// 範囲開始前に始まって範囲内まで続くセッションも拾う
+const LOOKBACK_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TimelineConfig {
+    /// ブラウザのアプリの識別子（小文字）
+    pub browser_identifiers: Vec<String>,
+}
+
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        let ids = [
+            "chrome.exe", "msedge.exe", "brave.exe", "vivaldi.exe", "opera.exe", "firefox.exe",
+            "chrome", "google-chrome", "chromium", "brave", "msedge", "firefox",
+        ];
+        Self { browser_identifiers: ids.iter().map(|s| s.to_string()).collect() }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct TimelineConfigPatch {
+    pub browser_identifiers: Option<Vec<String>>,
+}
+
+impl TimelineConfig {
+    pub fn apply(&self, patch: TimelineConfigPatch) -> Result<TimelineConfig, String> {
+        let mut ids: Vec<String> = patch
+            .browser_identifiers
+            .map(|v| v.iter().map(|s| s.trim().to_lowercase()).collect())
+            .unwrap_or_else(|| self.browser_identifiers.clone());
+        ids.sort();
+        ids.dedup();
+        let next = TimelineConfig { browser_identifiers: ids };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.browser_identifiers.iter().any(|s| s.is_empty()) {
+            return Err("browser identifiers must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> TimelineConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, TimelineConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TimelineQuery {
+    pub since: String,
+    pub until: String,
+    /// idle 状態の sessions も含めるか（既定: 含める。db_aggregate_waste と同じ）
+    pub include_idle: Option<bool>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineSource {
+    App,
+    Browser,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeClass {
+    Waste,
+    Productive,
+    Unclassified,
+}
+
+impl TimeClass {
+    fn from_label(label: Option<&str>) -> TimeClass {
+        match label {
+            None => TimeClass::Unclassified,
+            Some("waste") => TimeClass::Waste,
+            Some(_) => TimeClass::Productive,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeClass::Waste => "waste",
+            TimeClass::Productive => "productive",
+            TimeClass::Unclassified => "unclassified",
+        }
+    }
+}
+
+/// 重なりのない1区間
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TimelineEntry {
+    pub start_time: String,
+    pub end_time: String,
+    pub duration_seconds: i64,
+    /// browser は閲覧記録（ドメイン別の内訳と不明タブ）
+    pub source: TimelineSource,
+    /// アプリの識別子（アプリの記録がない時間帯の閲覧記録は None）
+    pub identifier: Option<String>,
+    /// ドメイン（アプリと不明タブは None）
+    pub domain: Option<String>,
+    pub title: Option<String>,
+    /// 表示名（ドメイン / 識別子 / browser (unknown tab)）
+    pub label: String,
+    pub user_state: Option<String>,
+    /// 分類（waste_categories.id）と階層カテゴリ（categories.id）
+    pub category_id: Option<i64>,
+    pub category_node: Option<i64>,
+    pub class: TimeClass,
+    #[serde(skip)]
+    start_ms: i64,
+    #[serde(skip)]
+    end_ms: i64,
+}
+
+impl TimelineEntry {
+    /// 丸める前の長さ（ミリ秒）
+    pub fn duration_ms(&self) -> i64 {
+        self.end_ms - self.start_ms
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimelineTotals {
+    pub waste_seconds: i64,
+    pub productive_seconds: i64,
+    pub unclassified_seconds: i64,
+    /// 上の3つのうち browser (unknown tab) の分
+    pub browser_unknown_seconds: i64,
+    pub total_seconds: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UnifiedTimeline {
+    pub entries: Vec<TimelineEntry>,
+    pub totals: TimelineTotals,
+}
+
+// DB から読んだ区間（範囲内に切り詰め済み）
+#[derive(Debug, Clone)]
+struct Span {
+    start: i64,
+    end: i64,
+    identifier: Option<String>,
+    domain: Option<String>,
+    title: Option<String>,
+    user_state: Option<String>,
+    category_id: Option<i64>,
+    label: Option<String>,
+    node: Option<i64>,
+}
+
+const APP_SQL: &str = "
+    SELECT s.start_time, s.duration_seconds, s.identifier, s.window_title, s.user_state, wc.id, wc.label, wc.category_id
+    FROM sessions s
+    LEFT JOIN waste_categories wc ON wc.id = s.category_id AND wc.is_active = 1
+    WHERE s.start_time >= ?1 AND s.start_time < ?2 AND (?3 = 1 OR s.user_state IS NOT 'idle')";
+
+// ドメイン設定 → 記録時のカテゴリ → domain 型カテゴリ の順に解決（aggregate と同じ）
+const BROWSER_SQL: &str = "
+    SELECT b.start_time, COALESCE(b.duration_seconds, 0), b.domain, b.title,
+           COALESCE(dc.id, bc.id, wd.id), COALESCE(dc.label, bc.label, wd.label),
+           CASE WHEN dc.id IS NOT NULL THEN dc.category_id WHEN bc.id IS NOT NULL THEN bc.category_id ELSE wd.category_id END
+    FROM browsing_sessions b
+    LEFT JOIN domains d ON d.domain = b.domain AND d.is_active = 1
+    LEFT JOIN waste_categories dc ON dc.id = d.category_id AND dc.is_active = 1
+    LEFT JOIN waste_categories bc ON bc.id = b.category_id AND bc.is_active = 1
+    LEFT JOIN waste_categories wd ON wd.type = 'domain' AND wd.identifier = b.domain AND wd.is_active = 1
+    WHERE b.start_time >= ?1 AND b.start_time < ?2";
+
+fn parse_ms(value: &str, field: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.timestamp_millis())
+        .map_err(|e| format!("invalid {}: {}", field, e))
+}
+
+fn format_ms(ms: i64) -> String {
+    Utc.timestamp_millis_opt(ms).single().map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true)).unwrap_or_default()
+}
+
+// (start_time, duration_seconds, identifier/domain, title, user_state, category_id, label, node)
+type SpanRow = (String, i64, Option<String>, Option<String>, Option<String>, Option<i64>, Option<String>, Option<i64>);
+
+fn load_spans(conn: &Connection, sql: &str, params: &[&dyn rusqlite::ToSql], range: (i64, i64), app: bool) -> rusqlite::Result<Vec<Span>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt
+        .query_map(params, |row| {
+            if app {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?))
+            } else {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, None, row.get(4)?, row.get(5)?, row.get(6)?))
+            }
+        })?;
+    let mut out = Vec::new();
+    for row in rows {
+        let (start_time, duration, key, title, user_state, category_id, label, node): SpanRow = row?;
+        let Ok(start) = parse_ms(&start_time, "start_time") else { continue };
+        let (start, end) = (start.max(range.0), (start + duration.max(0) * 1000).min(range.1));
+        if end <= start {
+            continue;
+        }
+        let (identifier, domain) = if app { (key, None) } else { (None, key) };
+        out.push(Span { start, end, identifier, domain, title, user_state, category_id, label, node });
+    }
+    out.sort_by_key(|s| (s.start, s.end));
+    Ok(out)
+}
+
+// 重なる区間は先に始まった方を優先して切り詰める（複数ウィンドウなどで同時に記録された分）
+fn flatten(spans: Vec<Span>) -> Vec<Span> {
+    let mut out: Vec<Span> = Vec::with_capacity(spans.len());
+    let mut cursor = i64::MIN;
+    for mut s in spans {
+        s.start = s.start.max(cursor);
+        if s.end > s.start {
+            cursor = s.end;
+            out.push(s);
+        }
+    }
+    out
+}
+
+// 開始順の区間の和集合
+fn union(spans: &[Span]) -> Vec<(i64, i64)> {
+    let mut out: Vec<(i64, i64)> = Vec::new();
+    for s in spans {
+        match out.last_mut() {
+            Some(last) if s.start <= last.1 => last.1 = last.1.max(s.end),
+            _ => out.push((s.start, s.end)),
+        }
+    }
+    out
+}
+
+fn entry(span: &Span, start: i64, end: i64, source: TimelineSource, label: String) -> TimelineEntry {
+    TimelineEntry {
+        start_time: format_ms(start),
+        end_time: format_ms(end),
+        duration_seconds: ((end - start) as f64 / 1000.0).round() as i64,
+        source,
+        identifier: span.identifier.clone(),
+        domain: span.domain.clone(),
+        title: span.title.clone(),
+        label,
+        user_state: span.user_state.clone(),
+        category_id: span.category_id,
+        category_node: span.node,
+        class: TimeClass::from_label(span.label.as_deref()),
+        start_ms: start,
+        end_ms: end,
+    }
+}
+
+/// 期間内の統合タイムライン（開始順）と区分ごとの合計
+pub fn unified_timeline(conn: &Connection, query: &TimelineQuery) -> AppResult<UnifiedTimeline> {
+    let since = parse_ms(&query.since, "since")?;
+    let until = parse_ms(&query.until, "until")?;
+    if until <= since {
+        return Err(AppError::validation("until must be after since").with_detail("field", "until"));
+    }
+    let (lookback_s, until_s) = (format_ms(since - LOOKBACK_MS), format_ms(until));
+    let include_idle = query.include_idle.unwrap_or(true);
+    let apps = flatten(load_spans(conn, APP_SQL, &[&lookback_s, &until_s, &include_idle], (since, until), true)?);
+    let pages = flatten(load_spans(conn, BROWSER_SQL, &[&lookback_s, &until_s], (since, until), false)?);
+    let browsers = TimelineConfig::load(conn).browser_identifiers;
+
+    // apps と pages はどちらも重なりのない開始順なので、前から順に突き合わせる
+    let mut entries = Vec::with_capacity(apps.len() + pages.len());
+    let mut first = 0;
+    for app in &apps {
+        let identifier = app.identifier.clone().unwrap_or_default();
+        if !browsers.contains(&identifier.to_lowercase()) {
+            entries.push(entry(app, app.start, app.end, TimelineSource::App, identifier));
+            continue;
+        }
+        // ブラウザが前面だった時間をドメイン別の内訳と不明タブに分ける
+        while first < pages.len() && pages[first].end <= app.start {
+            first += 1;
+        }
+        let mut cursor = app.start;
+        for page in pages[first..].iter().take_while(|p| p.start < app.end) {
+            let (start, end) = (page.start.max(app.start), page.end.min(app.end));
+            if start > cursor {
+                entries.push(entry(app, cursor, start, TimelineSource::Browser, UNKNOWN_TAB_LABEL.to_string()));
+            }
+            let mut e = entry(page, start, end, TimelineSource::Browser, page.domain.clone().unwrap_or_default());
+            e.identifier = app.identifier.clone();
+            e.user_state = app.user_state.clone();
+            entries.push(e);
+            cursor = end;
+        }
+        if app.end > cursor {
+            entries.push(entry(app, cursor, app.end, TimelineSource::Browser, UNKNOWN_TAB_LABEL.to_string()));
+        }
+    }
+
+    // アプリの記録がない時間帯の閲覧記録だけを足す
+    let covered = union(&apps);
+    let mut first = 0;
+    for page in &pages {
+        while first < covered.len() && covered[first].1 <= page.start {
+            first += 1;
+        }
+        let mut cursor = page.start;
+        for &(start, end) in covered[first..].iter().take_while(|(s, _)| *s < page.end) {
+            if start > cursor {
+                entries.push(entry(page, cursor, start, TimelineSource::Browser, page.domain.clone().unwrap_or_default()));
+            }
+            cursor = cursor.max(end);
+        }
+        if page.end > cursor {
+            entries.push(entry(page, cursor, page.end, TimelineSource::Browser, page.domain.clone().unwrap_or_default()));
+        }
+    }
+    entries.sort_by_key(|e| (e.start_ms, e.end_ms));
+
+    // ミリ秒で合計してから秒に丸める
+    let (mut waste, mut productive, mut unclassified, mut unknown) = (0i64, 0i64, 0i64, 0i64);
+    for e in &entries {
+        let d = e.end_ms - e.start_ms;
+        match e.class {
+            TimeClass::Waste => waste += d,
+            TimeClass::Productive => productive += d,
+            TimeClass::Unclassified => unclassified += d,
+        }
+        if e.source == TimelineSource::Browser && e.domain.is_none() {
+            unknown += d;
+        }
+    }
+    let secs = |v: i64| (v as f64 / 1000.0).round() as i64;
+    let totals = TimelineTotals {
+        waste_seconds: secs(waste),
+        productive_seconds: secs(productive),
+        unclassified_seconds: secs(unclassified),
+        browser_unknown_seconds: secs(unknown),
+        total_seconds: secs(waste + productive + unclassified),
+    };
+    Ok(UnifiedTimeline { entries, totals })
+}
+
+/// 集計用に統合タイムラインの区間を一時テーブル（temp.unified_spans: julianday の t0/t1, class, node）に書き出す
+pub fn fill_unified_spans(conn: &Connection, query: &TimelineQuery) -> AppResult<()> {
+    let timeline = unified_timeline(conn, query)?;
+    conn.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS unified_spans(t0 REAL NOT NULL, t1 REAL NOT NULL, class TEXT NOT NULL, node INTEGER);
+         DELETE FROM temp.unified_spans;",
+    )?;
+    let mut stmt = conn.prepare("INSERT INTO temp.unified_spans(t0, t1, class, node) VALUES(?1 / 86400000.0 + 2440587.5, ?2 / 86400000.0 + 2440587.5, ?3, ?4)")?;
+    for e in &timeline.entries {
+        stmt.execute(params![e.start_ms, e.end_ms, e.class.as_str(), e.category_node])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,376 @@
+// 一括削除とゴミ箱: 期間・識別子・ドメイン・id で sessions / browsing_sessions をまとめて削除し、
+// 削除した行は trash_rows に JSON で退避する（バッチ単位で復元でき、保持期間を過ぎたら完全に削除）
+// 設定は user_settings（trash_config）に JSON で保存する
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::repository::{load_json_setting, save_json_setting, SqliteRepository};
+use crate::rules::{self, RuleSet};
+
+const CONFIG_SETTING_KEY: &str = "trash_config";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TrashConfig {
+    /// ゴミ箱に残す日数
+    pub retention_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+// 部分更新用（未指定の項目は現在値を維持）
+#[derive(Deserialize, Debug, Default)]
+pub struct TrashConfigPatch {
+    pub retention_days: Option<u32>,
+}
+
+impl TrashConfig {
+    pub fn apply(&self, patch: TrashConfigPatch) -> Result<TrashConfig, String> {
+        let next = TrashConfig { retention_days: patch.retention_days.unwrap_or(self.retention_days) };
+        next.validate()?;
+        Ok(next)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=365).contains(&self.retention_days) {
+            return Err("retention_days must be between 1 and 365".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn load(conn: &Connection) -> TrashConfig {
+        load_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, TrashConfig::validate)
+    }
+
+    pub fn save(&self, conn: &Connection) -> AppResult<()> {
+        save_json_setting(&SqliteRepository::new(conn), CONFIG_SETTING_KEY, self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashTable {
+    Sessions,
+    BrowsingSessions,
+}
+
+impl TrashTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrashTable::Sessions => "sessions",
+            TrashTable::BrowsingSessions => "browsing_sessions",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<TrashTable> {
+        match name {
+            "sessions" => Some(TrashTable::Sessions),
+            "browsing_sessions" => Some(TrashTable::BrowsingSessions),
+            _ => None,
+        }
+    }
+}
+
+/// 削除条件（指定した条件の AND）。identifier は sessions のみ、domain は browsing_sessions のみに適用し、
+/// どちらも未指定なら両方のテーブルが対象になる
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct BulkDeleteRequest {
+    /// 開始時刻がこの範囲 [since, until) に入る行
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub identifier: Option<String>,
+    /// サブドメインも含む（youtube.com → www.youtube.com も対象）
+    pub domain: Option<String>,
+    pub ids: Option<Vec<String>>,
+    /// ゴミ箱一覧に表示する説明
+    pub description: Option<String>,
+    /// true なら削除せずに件数だけ返す
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BulkDeleteResult {
+    pub dry_run: bool,
+    /// 作成したゴミ箱のバッチ（dry_run や対象なしの場合は None）
+    pub batch_id: Option<i64>,
+    pub sessions: usize,
+    pub browsing_sessions: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TrashBatch {
+    pub id: i64,
+    pub description: Option<String>,
+    pub deleted_at: String,
+    /// この時刻を過ぎると完全に削除される
+    pub expires_at: String,
+    pub sessions: usize,
+    pub browsing_sessions: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TrashRestoreResult {
+    pub restored: usize,
+    /// 同じ id の行が既に記録し直されていたため戻さなかった行
+    pub conflicts: usize,
+}
+
+fn normalize_time(value: &Option<String>, field: &str) -> Result<Option<String>, String> {
+    value
+        .as_ref()
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v)
+                .map(|t| t.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true))
+                .map_err(|e| format!("invalid {}: {}", field, e))
+        })
+        .transpose()
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_ref().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// 対象テーブルごとの WHERE 句とパラメータ
+fn filters(request: &BulkDeleteRequest) -> Result<Vec<(TrashTable, String, Vec<SqlValue>)>, String> {
+    let since = normalize_time(&request.since, "since")?;
+    let until = normalize_time(&request.until, "until")?;
+    if let (Some(s), Some(u)) = (&since, &until) {
+        if u <= s {
+            return Err("until must be after since".to_string());
+        }
+    }
+    let identifier = non_empty(&request.identifier).map(|v| v.to_lowercase());
+    let domain = non_empty(&request.domain).map(|v| v.to_lowercase());
+    let ids: Vec<String> = request.ids.clone().unwrap_or_default();
+    if identifier.is_some() && domain.is_some() {
+        return Err("identifier and domain cannot be combined".to_string());
+    }
+    // 条件なしの全削除は受け付けない
+    if since.is_none() && until.is_none() && identifier.is_none() && domain.is_none() && ids.is_empty() {
+        return Err("at least one filter is required".to_string());
+    }
+
+    let mut out = Vec::new();
+    for table in [TrashTable::Sessions, TrashTable::BrowsingSessions] {
+        let mut clauses: Vec<String> = Vec::new();
+        let mut values: Vec<SqlValue> = Vec::new();
+        if let Some(s) = &since {
+            clauses.push("start_time >= ?".to_string());
+            values.push(SqlValue::Text(s.clone()));
+        }
+        if let Some(u) = &until {
+            clauses.push("start_time < ?".to_string());
+            values.push(SqlValue::Text(u.clone()));
+        }
+        match (table, &identifier, &domain) {
+            (TrashTable::Sessions, _, Some(_)) | (TrashTable::BrowsingSessions, Some(_), _) => continue,
+            (TrashTable::Sessions, Some(ident), None) => {
+                clauses.push("identifier = ?".to_string());
+                values.push(SqlValue::Text(ident.clone()));
+            }
+            (TrashTable::BrowsingSessions, None, Some(d)) => {
+                // サブドメインは末尾の完全一致で見る（LIKE だと _ や % がワイルドカードになる）
+                clauses.push("(domain = ? OR substr(domain, -length(?) - 1) = '.' || ?)".to_string());
+                values.extend([d, d, d].map(|v| SqlValue::Text(v.clone())));
+            }
+            _ => {}
+        }
+        if !ids.is_empty() {
+            clauses.push(format!("id IN ({})", vec!["?"; ids.len()].join(", ")));
+            values.extend(ids.iter().cloned().map(SqlValue::Text));
+        }
+        out.push((table, clauses.join(" AND "), values));
+    }
+    Ok(out)
+}
+
+fn table_columns(conn: &Connection, table: TrashTable) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table.name()))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+/// 条件に合う行をゴミ箱へ移す（1トランザクション）
+pub fn bulk_delete(conn: &mut Connection, request: &BulkDeleteRequest) -> AppResult<BulkDeleteResult> {
+    let filters = filters(request)?;
+    let tx = conn.transaction()?;
+    let mut counts = [0usize; 2];
+    for (table, clause, values) in &filters {
+        let n: i64 = tx
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {} WHERE {}", table.name(), clause),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )?;
+        counts[*table as usize] = n as usize;
+    }
+    let total = counts[0] + counts[1];
+    if request.dry_run || total == 0 {
+        return Ok(BulkDeleteResult {
+            dry_run: request.dry_run,
+            batch_id: None,
+            sessions: counts[0],
+            browsing_sessions: counts[1],
+        });
+    }
+
+    tx.execute("INSERT INTO trash_batches(description) VALUES(?1)", params![non_empty(&request.description)])?;
+    let batch_id = tx.last_insert_rowid();
+    for (table, clause, values) in &filters {
+        // 現在の列をすべて JSON にする（後から列が増えても復元時は共通の列だけ戻す）
+        let object = table_columns(&tx, *table)?
+            .iter()
+            .map(|c| format!("'{0}', \"{0}\"", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tx.execute(
+            &format!(
+                "INSERT INTO trash_rows(batch_id, source_table, row_id, data)
+                 SELECT {}, '{}', id, json_object({}) FROM {} WHERE {}",
+                batch_id,
+                table.name(),
+                object,
+                table.name(),
+                clause
+            ),
+            params_from_iter(values.iter()),
+        )?;
+        tx.execute(&format!("DELETE FROM {} WHERE {}", table.name(), clause), params_from_iter(values.iter()))?;
+    }
+    tx.commit()?;
+    Ok(BulkDeleteResult { dry_run: false, batch_id: Some(batch_id), sessions: counts[0], browsing_sessions: counts[1] })
+}
+
+pub fn list_batches(conn: &Connection, config: &TrashConfig) -> rusqlite::Result<Vec<TrashBatch>> {
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.description, b.deleted_at,
+                SUM(r.source_table = 'sessions'), SUM(r.source_table = 'browsing_sessions')
+         FROM trash_batches b
+         LEFT JOIN trash_rows r ON r.batch_id = b.id
+         GROUP BY b.id
+         ORDER BY b.deleted_at DESC, b.id DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let deleted_at: String = row.get(2)?;
+        Ok(TrashBatch {
+            id: row.get(0)?,
+            description: row.get(1)?,
+            expires_at: expires_at(&deleted_at, config),
+            deleted_at,
+            sessions: row.get::<_, Option<i64>>(3)?.unwrap_or(0) as usize,
+            browsing_sessions: row.get::<_, Option<i64>>(4)?.unwrap_or(0) as usize,
+        })
+    })?;
+    rows.collect()
+}
+
+fn expires_at(deleted_at: &str, config: &TrashConfig) -> String {
+    DateTime::parse_from_rfc3339(deleted_at)
+        .map(|t| {
+            (t.with_timezone(&Utc) + Duration::days(config.retention_days as i64)).to_rfc3339_opts(SecondsFormat::Millis, true)
+        })
+        .unwrap_or_default()
+}
+
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => n.as_i64().map(SqlValue::Integer).unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or(0.0))),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn category_exists(conn: &Connection, category_id: i64) -> rusqlite::Result<bool> {
+    Ok(conn.query_row("SELECT 1 FROM waste_categories WHERE id = ?1", params![category_id], |_| Ok(())).optional()?.is_some())
+}
+
+// 退避した行の値で、今のルールから分類し直す
+fn reclassify(conn: &Connection, rules: &RuleSet, table: TrashTable, object: &serde_json::Map<String, Value>) -> rusqlite::Result<Option<i64>> {
+    let text = |key: &str| object.get(key).and_then(Value::as_str);
+    match table {
+        TrashTable::Sessions => rules::classify_session_with(conn, rules, text("identifier"), text("window_title")),
+        TrashTable::BrowsingSessions => {
+            rules::classify_browsing_with(conn, rules, text("domain").unwrap_or_default(), text("url").unwrap_or_default(), text("title"))
+        }
+    }
+}
+
+/// バッチの行を元のテーブルに戻し、バッチを削除する
+/// 退避後に分類（waste_categories）が削除されていた行は、今のルールで分類し直してから戻す
+pub fn restore_batch(conn: &mut Connection, batch_id: i64) -> AppResult<TrashRestoreResult> {
+    let tx = conn.transaction()?;
+    let exists = tx
+        .query_row("SELECT 1 FROM trash_batches WHERE id = ?1", params![batch_id], |_| Ok(()))
+        .optional()?
+        .is_some();
+    if !exists {
+        return Err(AppError::not_found("trash batch", batch_id));
+    }
+    let rows: Vec<(String, String)> = {
+        let mut stmt = tx.prepare("SELECT source_table, data FROM trash_rows WHERE batch_id = ?1")?;
+        let mapped = stmt.query_map(params![batch_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        mapped.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+
+    let rules = RuleSet::load(&tx)?;
+    let mut result = TrashRestoreResult { restored: 0, conflicts: 0 };
+    for (source_table, data) in rows {
+        // 退避した行が読めないのは DB の中身が壊れている場合
+        let table = TrashTable::from_name(&source_table)
+            .ok_or_else(|| AppError::new(ErrorCode::DbCorrupt, format!("unknown table in trash: {}", source_table)))?;
+        let mut object: serde_json::Map<String, Value> =
+            serde_json::from_str(&data).map_err(|e| AppError::new(ErrorCode::DbCorrupt, e.to_string()))?;
+        // 消えた分類を指したまま戻すと外部キー違反でバッチ全体が戻せなくなる
+        if let Some(category_id) = object.get("category_id").and_then(Value::as_i64) {
+            if !category_exists(&tx, category_id)? {
+                let next = reclassify(&tx, &rules, table, &object)?;
+                object.insert("category_id".to_string(), Value::from(next));
+            }
+        }
+        let columns: Vec<String> = table_columns(&tx, table)?
+            .into_iter()
+            .filter(|c| object.contains_key(c))
+            .collect();
+        let values: Vec<SqlValue> = columns.iter().map(|c| json_to_sql(&object[c])).collect();
+        let changed = tx
+            .execute(
+                &format!(
+                    "INSERT INTO {}({}) VALUES({}) ON CONFLICT(id) DO NOTHING",
+                    table.name(),
+                    columns.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<_>>().join(", "),
+                    vec!["?"; columns.len()].join(", ")
+                ),
+                params_from_iter(values.iter()),
+            )?;
+        if changed > 0 {
+            result.restored += 1;
+        } else {
+            result.conflicts += 1;
+        }
+    }
+    tx.execute("DELETE FROM trash_rows WHERE batch_id = ?1", params![batch_id])?;
+    tx.execute("DELETE FROM trash_batches WHERE id = ?1", params![batch_id])?;
+    tx.commit()?;
+    Ok(result)
+}
+
+/// 保持期間を過ぎたバッチ（batch_id 指定時はそのバッチ）を完全に削除し、削除したバッチ数を返す
+pub fn purge(conn: &Connection, config: &TrashConfig, now: DateTime<Utc>, batch_id: Option<i64>) -> rusqlite::Result<usize> {
+    let cutoff = (now - Duration::days(config.retention_days as i64)).to_rfc3339_opts(SecondsFormat::Millis, true);
+    let condition = "(?1 IS NOT NULL AND id = ?1) OR (?1 IS NULL AND deleted_at < ?2)";
+    conn.execute(
+        &format!("DELETE FROM trash_rows WHERE batch_id IN (SELECT id FROM trash_batches WHERE {})", condition),
+        params![batch_id, cutoff],
+    )?;
+    conn.execute(&format!("DELETE FROM trash_batches WHERE {}", condition), params![batch_id, cutoff])
+}
@@ -0,0 +1,149 @@
+use rusqlite::Connection;
+use wasteday_lib::aggregate::{self, AggregateQuery, DataSource, Granularity};
+use wasteday_lib::error::ErrorCode;
+use wasteday_lib::repository::{Repository, Session, SqliteRepository, WasteCategory};
+
+mod common;
+use common::migrated_db;
+
+// game.exe=浪費, code.exe=生産的, それ以外は未分類
+fn classified_db() -> Connection {
+    let conn = migrated_db();
+    let repo = SqliteRepository::new(&conn);
+    for (identifier, label) in [("game.exe", "waste"), ("code.exe", "productive")] {
+        let category = WasteCategory { id: None, r#type: "app".to_string(), identifier: identifier.to_string(), label: label.to_string(), is_active: true, category_id: None };
+        repo.upsert_waste_category(&category).unwrap();
+    }
+    conn
+}
+
+fn insert(conn: &Connection, identifier: &str, user_state: &str, start_time: &str, duration_seconds: i64) {
+    let session_key = format!("category=app;identifier={};user_state={}", identifier, user_state);
+    let session = Session {
+        id: format!("{}-{}", start_time, session_key),
+        start_time: start_time.to_string(),
+        duration_seconds,
+        session_key,
+        category: None,
+        identifier: None,
+        user_state: None,
+        window_title: None,
+        is_manual: false,
+        category_id: None,
+    };
+    SqliteRepository::new(conn).upsert_session(&session).unwrap();
+}
+
+fn query(since: &str, until: &str, granularity: Granularity) -> AggregateQuery {
+    AggregateQuery {
+        since: since.to_string(),
+        until: until.to_string(),
+        granularity,
+        source: DataSource::App,
+        include_idle: None,
+        week_start: None,
+        utc_offset_minutes: Some(0),
+    }
+}
+
+// (バケット, 浪費, 生産的, 未分類)
+fn buckets(conn: &Connection, query: &AggregateQuery) -> Vec<(String, i64, i64, i64)> {
+    aggregate::aggregate(conn, query)
+        .unwrap()
+        .into_iter()
+        .map(|b| (b.bucket, b.waste_seconds, b.productive_seconds, b.unclassified_seconds))
+        .collect()
+}
+
+fn row(bucket: &str, waste: i64, productive: i64, unclassified: i64) -> (String, i64, i64, i64) {
+    (bucket.to_string(), waste, productive, unclassified)
+}
+
+#[test]
+fn sessions_are_split_at_hour_boundaries_and_clipped_to_the_range() {
+    let conn = classified_db();
+    // 範囲の開始前から続く分は範囲内だけ数える
+    insert(&conn, "misc.exe", "active", "2025-01-01T09:45:00.000Z", 1800);
+    insert(&conn, "code.exe", "active", "2025-01-01T10:30:00.000Z", 5400);
+    insert(&conn, "game.exe", "active", "2025-01-01T11:50:00.000Z", 1200);
+    // 範囲の終わりを越える分は切り捨て
+    insert(&conn, "game.exe", "active", "2025-01-01T12:50:00.000Z", 1200);
+
+    let hourly = buckets(&conn, &query("2025-01-01T10:00:00Z", "2025-01-01T13:00:00Z", Granularity::Hour));
+    assert_eq!(
+        hourly,
+        vec![
+            row("2025-01-01T10:00:00", 0, 1800, 900),
+            row("2025-01-01T11:00:00", 600, 3600, 0),
+            row("2025-01-01T12:00:00", 1200, 0, 0),
+        ]
+    );
+    let daily = buckets(&conn, &query("2025-01-01T10:00:00Z", "2025-01-01T13:00:00Z", Granularity::Day));
+    assert_eq!(daily, vec![row("2025-01-01", 1800, 5400, 900)]);
+}
+
+#[test]
+fn fixed_utc_offset_moves_bucket_boundaries() {
+    let conn = classified_db();
+    // JST 23:00〜翌1:00
+    insert(&conn, "game.exe", "active", "2025-01-01T14:00:00.000Z", 7200);
+    // UTC-5 では前日の 22:00
+    insert(&conn, "code.exe", "active", "2025-01-01T03:00:00.000Z", 3600);
+    let daily = |offset| {
+        let q = AggregateQuery { utc_offset_minutes: Some(offset), ..query("2024-12-31T00:00:00Z", "2025-01-03T00:00:00Z", Granularity::Day) };
+        buckets(&conn, &q)
+    };
+
+    assert_eq!(daily(0), vec![row("2025-01-01", 7200, 3600, 0)]);
+    assert_eq!(daily(540), vec![row("2025-01-01", 3600, 3600, 0), row("2025-01-02", 3600, 0, 0)]);
+    assert_eq!(daily(-300), vec![row("2024-12-31", 0, 3600, 0), row("2025-01-01", 7200, 0, 0)]);
+
+    // 時間単位のバケット名もローカル時刻
+    let hourly = AggregateQuery { utc_offset_minutes: Some(540), ..query("2025-01-01T14:00:00Z", "2025-01-01T16:00:00Z", Granularity::Hour) };
+    assert_eq!(buckets(&conn, &hourly), vec![row("2025-01-01T23:00:00", 3600, 0, 0), row("2025-01-02T00:00:00", 3600, 0, 0)]);
+}
+
+#[test]
+fn week_and_month_buckets_follow_week_start() {
+    let conn = classified_db();
+    // 2025-01-04 は土曜、01-05 は日曜、01-06 は月曜
+    for start in ["2025-01-04T10:00:00.000Z", "2025-01-05T10:00:00.000Z", "2025-01-06T10:00:00.000Z", "2025-02-03T10:00:00.000Z"] {
+        insert(&conn, "game.exe", "active", start, 600);
+    }
+    // 1月31日(金) 23:30 から月をまたぐ
+    insert(&conn, "game.exe", "active", "2025-01-31T23:30:00.000Z", 3600);
+    let weekly = |week_start| {
+        let q = AggregateQuery { week_start, ..query("2025-01-01T00:00:00Z", "2025-03-01T00:00:00Z", Granularity::Week) };
+        buckets(&conn, &q)
+    };
+
+    // 既定は日曜始まり
+    let sunday = vec![row("2024-12-29", 600, 0, 0), row("2025-01-05", 1200, 0, 0), row("2025-01-26", 3600, 0, 0), row("2025-02-02", 600, 0, 0)];
+    assert_eq!(weekly(None), sunday);
+    assert_eq!(weekly(Some(0)), sunday);
+    assert_eq!(
+        weekly(Some(1)),
+        vec![row("2024-12-30", 1200, 0, 0), row("2025-01-06", 600, 0, 0), row("2025-01-27", 3600, 0, 0), row("2025-02-03", 600, 0, 0)]
+    );
+
+    let monthly = buckets(&conn, &query("2025-01-01T00:00:00Z", "2025-03-01T00:00:00Z", Granularity::Month));
+    assert_eq!(monthly, vec![row("2025-01-01", 3600, 0, 0), row("2025-02-01", 2400, 0, 0)]);
+
+    let invalid = AggregateQuery { week_start: Some(7), ..query("2025-01-01T00:00:00Z", "2025-03-01T00:00:00Z", Granularity::Week) };
+    assert_eq!(aggregate::aggregate(&conn, &invalid).unwrap_err().code, ErrorCode::Validation);
+}
+
+#[test]
+fn idle_sessions_are_included_unless_excluded() {
+    let conn = classified_db();
+    insert(&conn, "code.exe", "active", "2025-01-01T10:00:00.000Z", 600);
+    insert(&conn, "code.exe", "idle", "2025-01-01T10:10:00.000Z", 300);
+    let day = query("2025-01-01T00:00:00Z", "2025-01-02T00:00:00Z", Granularity::Day);
+
+    assert_eq!(buckets(&conn, &day), vec![row("2025-01-01", 0, 900, 0)]);
+    assert_eq!(buckets(&conn, &AggregateQuery { include_idle: Some(true), ..day.clone() }), vec![row("2025-01-01", 0, 900, 0)]);
+    assert_eq!(buckets(&conn, &AggregateQuery { include_idle: Some(false), ..day.clone() }), vec![row("2025-01-01", 0, 600, 0)]);
+
+    let reversed = query("2025-01-02T00:00:00Z", "2025-01-01T00:00:00Z", Granularity::Day);
+    assert_eq!(aggregate::aggregate(&conn, &reversed).unwrap_err().code, ErrorCode::Validation);
+}
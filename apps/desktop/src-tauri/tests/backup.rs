@@ -0,0 +1,102 @@
+use chrono::{Local, TimeZone};
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasteday_lib::backup::{self, BackupConfig, BackupKind};
+use wasteday_lib::error::ErrorCode;
+use wasteday_lib::migrations;
+
+mod common;
+use common::{count, BASELINE_FIXTURE};
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wasteday-backup-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn open_db(path: &Path) -> Connection {
+    let mut conn = Connection::open(path).unwrap();
+    conn.execute_batch(BASELINE_FIXTURE).unwrap();
+    migrations::migrate(&mut conn).unwrap();
+    conn
+}
+
+fn at(day: u32, hour: u32) -> chrono::DateTime<Local> {
+    Local.with_ymd_and_hms(2025, 1, day, hour, 0, 0).unwrap()
+}
+
+#[test]
+fn backup_and_restore_round_trip() {
+    let dir = work_dir("roundtrip");
+    let db_path = dir.join("wasteday.db");
+    let mut conn = open_db(&db_path);
+    let info = backup::create_backup(&conn, &dir.join("backups"), BackupKind::Manual, at(1, 9)).unwrap();
+    assert!(info.path.ends_with("wasteday-backup-20250101-090000.db"));
+    assert!(info.size_bytes > 0);
+    assert_eq!(backup::validate_backup(Path::new(&info.path)).unwrap(), migrations::latest_version());
+
+    conn.execute("DELETE FROM sessions", []).unwrap();
+    assert_eq!(count(&conn, "sessions"), 0);
+
+    let result = backup::restore(&mut conn, &db_path, Path::new(&info.path), &dir.join("backups"), at(1, 10)).unwrap();
+    assert_eq!(result.schema_version, migrations::latest_version());
+    assert_eq!(count(&conn, "sessions"), 3);
+    // 開き直した接続でも外部キー制約が有効
+    let foreign_keys: i64 = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap();
+    assert_eq!(foreign_keys, 1);
+    // 差し替え前の DB も退避されている
+    let pre_restore = Connection::open(&result.pre_restore_backup).unwrap();
+    assert_eq!(count(&pre_restore, "sessions"), 0);
+    assert!(!dir.join("wasteday.db.restore").exists());
+}
+
+#[test]
+fn restore_migrates_older_backups_and_rejects_invalid_files() {
+    let dir = work_dir("validate");
+    let db_path = dir.join("wasteday.db");
+    let mut conn = open_db(&db_path);
+
+    let old = dir.join("old.db");
+    Connection::open(&old).unwrap().execute_batch(BASELINE_FIXTURE).unwrap();
+    let result = backup::restore(&mut conn, &db_path, &old, &dir, at(2, 9)).unwrap();
+    assert_eq!(result.backup_schema_version, 0);
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version());
+
+    let newer = dir.join("newer.db");
+    let newer_conn = open_db(&newer);
+    newer_conn.pragma_update(None, "user_version", migrations::latest_version() + 1).unwrap();
+    drop(newer_conn);
+    let err = backup::restore(&mut conn, &db_path, &newer, &dir, at(2, 10)).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Conflict);
+    assert!(err.message.contains("newer"), "{}", err);
+
+    let garbage = dir.join("garbage.db");
+    fs::write(&garbage, b"definitely not sqlite").unwrap();
+    assert_eq!(backup::restore(&mut conn, &db_path, &garbage, &dir, at(2, 11)).unwrap_err().code, ErrorCode::DbCorrupt);
+    // 失敗したリストアは現在の DB に触れない
+    assert_eq!(count(&conn, "sessions"), 3);
+}
+
+#[test]
+fn scheduled_backups_respect_interval_and_retention() {
+    let dir = work_dir("schedule");
+    let conn = open_db(&dir.join("wasteday.db"));
+    let backups = dir.join("backups");
+    let config = BackupConfig { enabled: true, interval_hours: 24, keep: 2, directory: None };
+
+    backup::create_backup(&conn, &backups, BackupKind::Manual, at(1, 8)).unwrap();
+    assert!(backup::run_scheduled(&conn, &backups, &config, at(1, 9)).unwrap().is_some());
+    assert!(backup::run_scheduled(&conn, &backups, &config, at(1, 20)).unwrap().is_none());
+    assert!(backup::run_scheduled(&conn, &backups, &config, at(2, 9)).unwrap().is_some());
+    assert!(backup::run_scheduled(&conn, &backups, &config, at(3, 9)).unwrap().is_some());
+
+    let list = backup::list_backups(&backups);
+    let automatic: Vec<&str> = list.iter().filter(|b| b.kind == BackupKind::Automatic).map(|b| b.created_at.as_str()).collect();
+    assert_eq!(automatic, ["2025-01-03T09:00:00", "2025-01-02T09:00:00"]);
+    assert_eq!(list.iter().filter(|b| b.kind == BackupKind::Manual).count(), 1);
+
+    let disabled = BackupConfig { enabled: false, ..config };
+    assert!(backup::run_scheduled(&conn, &backups, &disabled, at(9, 9)).unwrap().is_none());
+}
@@ -0,0 +1,91 @@
+use rusqlite::params;
+use wasteday_lib::browser_title::{BrowserTitleConfig, BrowserTitleConfigPatch, BrowserTitlePattern, BrowserTitles, ParsedTitle};
+use wasteday_lib::rules;
+
+mod common;
+use common::fixture_db;
+
+// フィクスチャで youtube.com に割り当てられている分類
+const YOUTUBE: i64 = 3;
+
+fn parsed(page_title: &str, site: Option<&str>) -> Option<ParsedTitle> {
+    Some(ParsedTitle { page_title: page_title.to_string(), site: site.map(str::to_string) })
+}
+
+#[test]
+fn parses_page_title_and_site_for_known_browsers() {
+    let conn = fixture_db();
+    let titles = BrowserTitles::load(&conn).unwrap();
+
+    assert_eq!(titles.parse("chrome.exe", "Cat video - YouTube - Google Chrome"), parsed("Cat video - YouTube", Some("youtube.com")));
+    assert_eq!(titles.parse("msedge.exe", "Cat video - YouTube and 2 more pages - Microsoft\u{200B} Edge"), parsed("Cat video - YouTube", Some("youtube.com")));
+    assert_eq!(titles.parse("firefox", "(3) YouTube — Mozilla Firefox"), parsed("(3) YouTube", None));
+    assert_eq!(titles.parse("FIREFOX.EXE", "YouTube — Mozilla Firefox"), parsed("YouTube", Some("youtube.com")));
+    // 分類のないドメイン（github.com）やブラウザ以外、形式が合わないタイトルはサイトにしない
+    assert_eq!(titles.parse("chrome.exe", "wasteday - GitHub - Google Chrome"), parsed("wasteday - GitHub", None));
+    assert_eq!(titles.parse("code.exe", "main.rs - YouTube - Visual Studio Code"), None);
+    assert_eq!(titles.parse("chrome.exe", "Downloads"), None);
+    assert!(titles.is_browser("Chrome.exe") && !titles.is_browser("code.exe"));
+}
+
+#[test]
+fn domain_tokens_in_titles_match_known_domains_and_subdomains() {
+    let conn = fixture_db();
+    conn.execute("INSERT INTO domains(domain, category_id, is_active) VALUES('example.co.jp', 1, 1)", []).unwrap();
+    let titles = BrowserTitles::load(&conn).unwrap();
+
+    assert_eq!(titles.parse("chrome.exe", "Login | m.youtube.com - Google Chrome").unwrap().site.as_deref(), Some("youtube.com"));
+    // サイト名は登録ドメインの先頭のラベル（example.co.jp → example）
+    assert_eq!(titles.parse("chrome.exe", "ニュース | Example - Google Chrome").unwrap().site.as_deref(), Some("example.co.jp"));
+    assert_eq!(titles.parse("chrome.exe", "notyoutube.com - Google Chrome").unwrap().site, None);
+}
+
+#[test]
+fn browser_sessions_are_classified_by_the_site_in_the_title() {
+    let conn = fixture_db();
+    // ルール・設定の変更は RuleCache が拾う
+    let cache = rules::RuleCache::default();
+    let classify = |identifier: &str, title: &str| rules::classify_session_with(&conn, &cache.get(&conn).unwrap(), Some(identifier), Some(title)).unwrap();
+
+    assert_eq!(classify("msedge.exe", "Cat video - YouTube - Microsoft\u{200B} Edge"), Some(YOUTUBE));
+    // サイトが分からなければアプリの分類にフォールバックする
+    assert_eq!(classify("chrome.exe", "New Tab - Google Chrome"), Some(1));
+    assert_eq!(classify("msedge.exe", "New Tab - Microsoft\u{200B} Edge"), None);
+
+    // ドメインのルールも同じように効く
+    rules::create_rule(
+        &conn,
+        rules::NewRule { target: rules::RuleTarget::Domain, pattern: "youtube.com".to_string(), title_regex: Some("tutorial".to_string()), category_id: 2, priority: 0, is_active: None },
+    )
+    .unwrap();
+    assert_eq!(classify("chrome.exe", "Rust tutorial - YouTube - Google Chrome"), Some(2));
+
+    // 無効にすると縮退モードを使わない
+    BrowserTitleConfig::default().apply(BrowserTitleConfigPatch { enabled: Some(false), patterns: None }).unwrap().save(&conn).unwrap();
+    assert_eq!(classify("msedge.exe", "Cat video - YouTube - Microsoft\u{200B} Edge"), None);
+    conn.execute("DELETE FROM user_settings WHERE key = 'browser_title_config'", params![]).unwrap();
+    assert_eq!(classify("msedge.exe", "Cat video - YouTube - Microsoft\u{200B} Edge"), Some(YOUTUBE));
+}
+
+#[test]
+fn title_patterns_are_configurable_and_validated() {
+    let conn = fixture_db();
+    let vivaldi = BrowserTitlePattern { identifiers: vec![" Vivaldi.exe ".to_string()], pattern: r"^(?P<title>.+?) \[(?P<site>[^\]]+)\] - Vivaldi$".to_string() };
+    let config = BrowserTitleConfig::default().apply(BrowserTitleConfigPatch { enabled: None, patterns: Some(vec![vivaldi]) }).unwrap();
+    assert_eq!(config.patterns[0].identifiers, vec!["vivaldi.exe".to_string()]);
+    config.save(&conn).unwrap();
+    assert_eq!(BrowserTitleConfig::load(&conn), config);
+
+    let titles = BrowserTitles::load(&conn).unwrap();
+    // site グループがあればそのまま使う
+    assert_eq!(titles.parse("vivaldi.exe", "Home [www.YouTube.com] - Vivaldi"), parsed("Home", Some("youtube.com")));
+    assert_eq!(titles.parse("chrome.exe", "Cat video - YouTube - Google Chrome"), None);
+
+    let patch = |pattern: &str, identifiers: Vec<String>| BrowserTitleConfigPatch {
+        enabled: None,
+        patterns: Some(vec![BrowserTitlePattern { identifiers, pattern: pattern.to_string() }]),
+    };
+    assert!(config.apply(patch(r"^(.+) - Vivaldi$", vec!["vivaldi.exe".to_string()])).is_err());
+    assert!(config.apply(patch(r"^(?P<title>.+", vec!["vivaldi.exe".to_string()])).is_err());
+    assert!(config.apply(patch(r"^(?P<title>.+)$", vec![" ".to_string()])).is_err());
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Duration, Local, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use wasteday_lib::budgets::{self, BudgetConfig, BudgetConfigPatch, BudgetPatch, BudgetTarget, NewBudget};
+use wasteday_lib::error::ErrorCode;
+
+mod common;
+use common::fixture_db;
+
+// マイグレーションで作られる既定カテゴリ
+const WASTE: i64 = 2;
+
+// タイムゾーンに依存しないよう、ローカル時刻の 15:00 を「現在」とする
+fn now() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2025, 3, 10, 15, 0, 0).unwrap()
+}
+
+fn ago(minutes: i64) -> String {
+    (now() - Duration::minutes(minutes)).with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn insert_app(conn: &Connection, id: &str, identifier: &str, category_id: Option<i64>, start: &str, seconds: i64, user_state: &str) {
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state, category_id)
+         VALUES(?1, ?2, ?3, 'category=app;identifier=' || ?4 || ';user_state=' || ?5, 'app', ?4, ?5, ?6)",
+        params![id, start, seconds, identifier, user_state, category_id],
+    )
+    .unwrap();
+}
+
+fn insert_browsing(conn: &Connection, id: &str, domain: &str, start: &str, seconds: i64) {
+    conn.execute(
+        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds) VALUES(?1, ?2, 'https://' || ?2 || '/', NULL, ?3, ?4)",
+        params![id, domain, start, seconds],
+    )
+    .unwrap();
+}
+
+fn budget(conn: &Connection, target_type: BudgetTarget, target: &str, minutes: i64) -> i64 {
+    budgets::create_budget(
+        conn,
+        NewBudget { target_type, target: target.to_string(), daily_limit_seconds: minutes * 60, is_active: None },
+    )
+    .unwrap()
+    .id
+}
+
+#[test]
+fn status_reports_today_usage_per_target() {
+    let conn = fixture_db();
+    let app = budget(&conn, BudgetTarget::App, "Slack.exe", 60);
+    let domain = budget(&conn, BudgetTarget::Domain, "twitter.com", 30);
+    let category = budget(&conn, BudgetTarget::Category, &WASTE.to_string(), 120);
+
+    // 今日の 20 分 + idle（数えない）+ 前日分（数えない）
+    insert_app(&conn, "s1", "slack.exe", None, &ago(60), 1200, "active");
+    insert_app(&conn, "s2", "slack.exe", None, &ago(30), 600, "idle");
+    insert_app(&conn, "s3", "slack.exe", None, &ago(60 * 24), 600, "active");
+    // サブドメインも含めて 40 分のうち、slack.exe が前面だった 5 分を除く 35 分（上限超過）
+    insert_browsing(&conn, "b1", "twitter.com", &ago(90), 1200);
+    insert_browsing(&conn, "b2", "mobile.twitter.com", &ago(45), 1200);
+    insert_browsing(&conn, "b3", "nottwitter.com", &ago(45), 1200);
+    // waste_categories 1 (chrome.exe) は Waste に紐づく
+    insert_app(&conn, "s4", "chrome.exe", Some(1), &ago(120), 900, "active");
+
+    let rows = budgets::status(&conn, now()).unwrap();
+    let find = |id: i64| rows.iter().find(|s| s.budget_id == id).unwrap();
+
+    let s = find(app);
+    assert_eq!(s.target, "slack.exe");
+    assert_eq!((s.used_seconds, s.remaining_seconds, s.exceeded), (1200, 2400, false));
+    assert!((s.percent - 33.3).abs() < 1e-9);
+
+    let s = find(domain);
+    assert_eq!((s.used_seconds, s.remaining_seconds, s.exceeded), (2100, 0, true));
+
+    let s = find(category);
+    assert_eq!(s.label, "Waste");
+    assert_eq!(s.used_seconds, 900);
+}
+
+#[test]
+fn every_target_type_counts_the_same_unified_time() {
+    let conn = fixture_db();
+    let app = budget(&conn, BudgetTarget::App, "chrome.exe", 60);
+    let domain = budget(&conn, BudgetTarget::Domain, "my_site.com", 60);
+    let category = budget(&conn, BudgetTarget::Category, &WASTE.to_string(), 60);
+
+    // chrome.exe（Waste）の 10 分のうち 5 分は my_site.com の閲覧
+    insert_app(&conn, "s1", "chrome.exe", Some(1), &ago(60), 600, "active");
+    insert_browsing(&conn, "b1", "www.my_site.com", &ago(60), 300);
+    // _ は1文字のワイルドカードとして扱わない
+    insert_browsing(&conn, "b2", "www.myXsite.com", &ago(20), 300);
+    // code.exe が前面だった時間の閲覧記録はどの上限にも数えない
+    insert_app(&conn, "s2", "code.exe", Some(2), &ago(40), 600, "active");
+    insert_browsing(&conn, "b3", "my_site.com", &ago(40), 300);
+
+    let rows = budgets::status(&conn, now()).unwrap();
+    let used = |id: i64| rows.iter().find(|s| s.budget_id == id).unwrap().used_seconds;
+    assert_eq!(used(app), 600);
+    assert_eq!(used(domain), 300);
+    // 残りの不明タブの時間だけがブラウザの分類になる
+    assert_eq!(used(category), 300);
+}
+
+#[test]
+fn thresholds_fire_once_per_day_and_report_the_highest() {
+    let conn = fixture_db();
+    let id = budget(&conn, BudgetTarget::App, "slack.exe", 10);
+    let config = BudgetConfig::default();
+
+    insert_app(&conn, "s1", "slack.exe", None, &ago(30), 360, "active");
+    let alerts = budgets::check(&conn, &config, now()).unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!((alerts[0].threshold, alerts[0].status.budget_id), (50, id));
+    assert!(budgets::check(&conn, &config, now()).unwrap().is_empty());
+
+    // 80% と 100% を一度に超えたら 100% だけ通知する
+    insert_app(&conn, "s2", "slack.exe", None, &ago(10), 300, "active");
+    let alerts = budgets::check(&conn, &config, now()).unwrap();
+    assert_eq!(alerts.iter().map(|a| a.threshold).collect::<Vec<_>>(), vec![100]);
+    assert!(alerts[0].status.exceeded);
+    assert!(budgets::check(&conn, &config, now()).unwrap().is_empty());
+
+    // 上限を変えると今日の通知記録はやり直しになる
+    budgets::update_budget(&conn, id, BudgetPatch { daily_limit_seconds: Some(1200), ..Default::default() }).unwrap();
+    let alerts = budgets::check(&conn, &config, now()).unwrap();
+    assert_eq!(alerts.iter().map(|a| a.threshold).collect::<Vec<_>>(), vec![50]);
+
+    let disabled = config.apply(BudgetConfigPatch { enabled: Some(false), ..Default::default() }).unwrap();
+    insert_app(&conn, "s3", "slack.exe", None, &ago(5), 300, "active");
+    assert!(budgets::check(&conn, &disabled, now()).unwrap().is_empty());
+}
+
+#[test]
+fn invalid_budgets_are_rejected() {
+    let conn = fixture_db();
+    budget(&conn, BudgetTarget::Domain, "youtube.com", 30);
+
+    let new = |target_type, target: &str, minutes: i64| NewBudget {
+        target_type,
+        target: target.to_string(),
+        daily_limit_seconds: minutes * 60,
+        is_active: None,
+    };
+    let err = budgets::create_budget(&conn, new(BudgetTarget::Domain, "YouTube.com", 10)).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Conflict);
+    assert!(err.message.contains("already exists"), "{}", err);
+    assert!(budgets::create_budget(&conn, new(BudgetTarget::Category, "999", 10)).is_err());
+    assert!(budgets::create_budget(&conn, new(BudgetTarget::App, "code.exe", 0)).is_err());
+    assert!(budgets::create_budget(&conn, new(BudgetTarget::App, " ", 10)).is_err());
+    assert!(BudgetConfig::default().apply(BudgetConfigPatch { thresholds: Some(vec![0, 50]), ..Default::default() }).is_err());
+
+    let config = BudgetConfig::default().apply(BudgetConfigPatch { thresholds: Some(vec![90, 50, 90]), ..Default::default() }).unwrap();
+    assert_eq!(config.thresholds, vec![50, 90]);
+    config.save(&conn).unwrap();
+    assert_eq!(BudgetConfig::load(&conn), config);
+}
@@ -0,0 +1,106 @@
+use rusqlite::{params, Connection};
+use wasteday_lib::aggregate::{CategoryTotalsQuery, DataSource};
+use wasteday_lib::categories::{self, CategoryPatch, CategoryRollup, NewCategory};
+
+mod common;
+use common::fixture_db;
+
+// マイグレーションで作られる既定カテゴリ
+const PRODUCTIVE: i64 = 1;
+const WASTE: i64 = 2;
+const VIDEO: i64 = 6;
+
+fn label_of(conn: &Connection, waste_category_id: i64) -> String {
+    conn.query_row("SELECT label FROM waste_categories WHERE id = ?1", params![waste_category_id], |row| row.get(0)).unwrap()
+}
+
+fn day_query() -> CategoryTotalsQuery {
+    CategoryTotalsQuery {
+        since: "2025-01-01T00:00:00Z".to_string(),
+        until: "2025-01-02T00:00:00Z".to_string(),
+        source: DataSource::All,
+        include_idle: None,
+        utc_offset_minutes: Some(0),
+    }
+}
+
+fn find(rows: &[CategoryRollup], category_id: Option<i64>) -> &CategoryRollup {
+    rows.iter().find(|r| r.category_id == category_id).unwrap()
+}
+
+#[test]
+fn existing_labels_are_mapped_to_the_default_tree() {
+    let conn = fixture_db();
+    let list = categories::list_categories(&conn).unwrap();
+    let names: Vec<&str> = list.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["Productive", "Learning", "Work", "Waste", "Gaming", "Social", "Video"]);
+    let video = list.iter().find(|c| c.id == VIDEO).unwrap();
+    assert_eq!((video.depth, video.waste_weight, video.effective_waste_weight), (1, None, 1.0));
+
+    let mapped: Vec<Option<i64>> = conn
+        .prepare("SELECT category_id FROM waste_categories ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(mapped, [Some(WASTE), Some(PRODUCTIVE), Some(WASTE)]);
+}
+
+#[test]
+fn rollup_sums_children_into_parents_with_weights() {
+    let mut conn = fixture_db();
+    // chrome.exe と youtube.com を Video に
+    categories::assign_category(&conn, 1, Some(VIDEO)).unwrap();
+    categories::assign_category(&conn, 3, Some(VIDEO)).unwrap();
+
+    let rows = categories::rollup(&conn, &day_query()).unwrap();
+    assert_eq!(find(&rows, Some(PRODUCTIVE)).total_seconds, 1800);
+    assert_eq!(find(&rows, Some(VIDEO)).own_seconds, 720 + 300);
+    let waste = find(&rows, Some(WASTE));
+    assert_eq!((waste.own_seconds, waste.total_seconds, waste.weighted_waste_seconds), (0, 1020, 1020));
+    // github.com はカテゴリ未割り当て
+    assert_eq!(find(&rows, None).total_seconds, 300);
+
+    // 重みを下げると label も productive に変わる
+    categories::update_category(&mut conn, VIDEO, CategoryPatch { waste_weight: Some(0.2), ..Default::default() }).unwrap();
+    assert_eq!(label_of(&conn, 1), "productive");
+    let rows = categories::rollup(&conn, &day_query()).unwrap();
+    assert_eq!(find(&rows, Some(WASTE)).weighted_waste_seconds, 204);
+
+    // 継承に戻す
+    categories::update_category(&mut conn, VIDEO, CategoryPatch { waste_weight: Some(-1.0), ..Default::default() }).unwrap();
+    assert_eq!(label_of(&conn, 1), "waste");
+}
+
+#[test]
+fn tree_edits_are_validated_and_deletes_reparent() {
+    let mut conn = fixture_db();
+    let shorts = categories::create_category(
+        &conn,
+        NewCategory { name: "Shorts".to_string(), parent_id: Some(VIDEO), color: Some("#FF0000".to_string()), waste_weight: None },
+    )
+    .unwrap();
+    assert_eq!((shorts.depth, shorts.color.as_deref()), (2, Some("#ff0000")));
+    categories::assign_category(&conn, 1, Some(shorts.id)).unwrap();
+
+    let dup = NewCategory { name: "shorts".to_string(), parent_id: Some(VIDEO), color: None, waste_weight: None };
+    assert!(categories::create_category(&conn, dup).is_err());
+    let bad_color = NewCategory { name: "X".to_string(), parent_id: None, color: Some("red".to_string()), waste_weight: None };
+    assert!(categories::create_category(&conn, bad_color).is_err());
+    let bad_weight = NewCategory { name: "X".to_string(), parent_id: None, color: None, waste_weight: Some(1.5) };
+    assert!(categories::create_category(&conn, bad_weight).is_err());
+    // 子孫の下には移動できない
+    assert!(categories::update_category(&mut conn, WASTE, CategoryPatch { parent_id: Some(shorts.id), ..Default::default() }).is_err());
+
+    // Video を削除すると Shorts は Waste の直下へ
+    categories::delete_category(&mut conn, VIDEO).unwrap();
+    let moved = categories::get_category(&conn, shorts.id).unwrap();
+    assert_eq!((moved.parent_id, moved.depth), (Some(WASTE), 1));
+
+    // Shorts を削除すると割り当ては Waste へ
+    categories::delete_category(&mut conn, shorts.id).unwrap();
+    let category_id: Option<i64> = conn.query_row("SELECT category_id FROM waste_categories WHERE id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(category_id, Some(WASTE));
+    assert_eq!(label_of(&conn, 1), "waste");
+}
@@ -0,0 +1,27 @@
+// 統合テストで共有するフィクスチャ（各テストファイルから `mod common;` で使う）
+#![allow(dead_code)]
+
+use rusqlite::Connection;
+use wasteday_lib::migrations;
+
+/// v0 相当のスキーマとサンプルデータ（マイグレーション前）
+pub const BASELINE_FIXTURE: &str = include_str!("../fixtures/baseline_v0.sql");
+
+/// サンプルデータ入りで最新スキーマまで移行した DB
+pub fn fixture_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(BASELINE_FIXTURE).unwrap();
+    migrations::migrate(&mut conn).unwrap();
+    conn
+}
+
+/// 空の状態から最新スキーマまで移行した DB
+pub fn migrated_db() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrations::migrate(&mut conn).unwrap();
+    conn
+}
+
+pub fn count(conn: &Connection, table: &str) -> i64 {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+}
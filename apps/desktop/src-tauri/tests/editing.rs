@@ -0,0 +1,142 @@
+use rusqlite::Connection;
+use wasteday_lib::editing::{self, ManualBrowsingEntry, ManualSessionEntry, SessionPatch};
+use wasteday_lib::error::ErrorCode;
+use wasteday_lib::rules::RuleSet;
+
+mod common;
+use common::fixture_db;
+
+const CODE_ID: &str = "2025-01-01T09:00:00.000Z-category=app;identifier=code.exe;user_state=active";
+const CHROME_ID: &str = "2025-01-01T09:30:00.000Z-category=app;identifier=chrome.exe;user_state=active";
+
+fn manual_count(conn: &Connection) -> i64 {
+    conn.query_row("SELECT COUNT(*) FROM sessions WHERE is_manual = 1", [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn split_then_merge_restores_the_session() {
+    let mut conn = fixture_db();
+    let (first, second) = editing::split_session(&mut conn, CODE_ID, "2025-01-01T09:10:00Z").unwrap();
+    assert_eq!(first.duration_seconds, 600);
+    assert_eq!(second.start_time, "2025-01-01T09:10:00.000Z");
+    assert_eq!(second.duration_seconds, 1200);
+    assert!(first.is_manual && second.is_manual);
+
+    assert!(editing::split_session(&mut conn, CODE_ID, "2025-01-01T09:30:00Z").is_err());
+
+    let merged = editing::merge_sessions(&mut conn, &[second.id.clone(), first.id.clone()]).unwrap();
+    assert_eq!(merged.id, CODE_ID);
+    assert_eq!(merged.duration_seconds, 1800);
+    let total: i64 = conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0)).unwrap();
+    assert_eq!(total, 3);
+}
+
+#[test]
+fn merge_rejects_different_or_separated_sessions() {
+    let mut conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    let err = editing::merge_sessions(&mut conn, &[CODE_ID.to_string(), CHROME_ID.to_string()]).unwrap_err();
+    assert!(err.message.contains("same identifier"), "{}", err);
+
+    let later = editing::add_manual_session(
+        &conn,
+        &rules,
+        ManualSessionEntry {
+            start_time: "2025-01-01T11:00:00Z".to_string(),
+            end_time: None,
+            duration_seconds: Some(60),
+            identifier: "code.exe".to_string(),
+            category: None,
+            user_state: None,
+            window_title: None,
+            category_id: None,
+        },
+    )
+    .unwrap();
+    let err = editing::merge_sessions(&mut conn, &[CODE_ID.to_string(), later.id]).unwrap_err();
+    assert!(err.message.contains("adjacent"), "{}", err);
+}
+
+#[test]
+fn update_and_manual_entries_are_flagged() {
+    let conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    let updated = editing::update_session(
+        &conn,
+        &rules,
+        CHROME_ID,
+        SessionPatch { identifier: Some("Zoom.exe".to_string()), ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(updated.identifier.as_deref(), Some("zoom.exe"));
+    assert_eq!(updated.session_key, "category=app;identifier=zoom.exe;user_state=active");
+
+    let entry = ManualSessionEntry {
+        start_time: "2025-01-01T20:00:00+09:00".to_string(),
+        end_time: Some("2025-01-01T21:00:00+09:00".to_string()),
+        duration_seconds: None,
+        identifier: "Reading".to_string(),
+        category: Some("offline".to_string()),
+        user_state: None,
+        window_title: Some("Book".to_string()),
+        category_id: None,
+    };
+    let added = editing::add_manual_session(&conn, &rules, entry.clone()).unwrap();
+    assert_eq!(added.start_time, "2025-01-01T11:00:00.000Z");
+    assert_eq!(added.duration_seconds, 3600);
+    assert!(editing::add_manual_session(&conn, &rules, entry).is_err());
+    assert_eq!(manual_count(&conn), 2);
+
+    let id = editing::add_manual_browsing_session(
+        &conn,
+        &rules,
+        ManualBrowsingEntry {
+            start_time: "2025-01-01T12:00:00Z".to_string(),
+            end_time: None,
+            duration_seconds: Some(300),
+            url: "https://youtube.com/watch?v=1".to_string(),
+            domain: None,
+            title: None,
+            category_id: None,
+        },
+    )
+    .unwrap();
+    let (category_id, is_manual): (i64, bool) = conn
+        .query_row("SELECT category_id, is_manual FROM browsing_sessions WHERE id = ?1", [&id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((category_id, is_manual), (3, true));
+}
+
+#[test]
+fn chosen_categories_override_the_rules() {
+    let conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    // chrome.exe はルール上 1（waste）だが、手で 2（productive）に直す
+    let patch = SessionPatch { category_id: Some(2), ..Default::default() };
+    assert_eq!(editing::update_session(&conn, &rules, CHROME_ID, patch).unwrap().category_id, Some(2));
+    // 識別子もタイトルも変えない編集では選んだ分類を残す
+    let retitled = SessionPatch { user_state: Some("idle".to_string()), ..Default::default() };
+    assert_eq!(editing::update_session(&conn, &rules, CHROME_ID, retitled).unwrap().category_id, Some(2));
+    // 識別子を変えたらルールで分類し直す
+    let renamed = SessionPatch { identifier: Some("Chrome.exe".to_string()), window_title: Some("Docs".to_string()), ..Default::default() };
+    assert_eq!(editing::update_session(&conn, &rules, CHROME_ID, renamed).unwrap().category_id, Some(1));
+
+    let entry = ManualSessionEntry {
+        start_time: "2025-01-01T20:00:00Z".to_string(),
+        end_time: None,
+        duration_seconds: Some(60),
+        identifier: "chrome.exe".to_string(),
+        category: None,
+        user_state: None,
+        window_title: None,
+        category_id: Some(2),
+    };
+    assert_eq!(editing::add_manual_session(&conn, &rules, entry.clone()).unwrap().category_id, Some(2));
+
+    let missing = ManualSessionEntry { start_time: "2025-01-01T21:00:00Z".to_string(), category_id: Some(99), ..entry };
+    assert_eq!(editing::add_manual_session(&conn, &rules, missing).unwrap_err().code, ErrorCode::NotFound);
+    let missing = SessionPatch { category_id: Some(99), ..Default::default() };
+    assert_eq!(editing::update_session(&conn, &rules, CHROME_ID, missing).unwrap_err().code, ErrorCode::NotFound);
+}
@@ -0,0 +1,72 @@
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::fs;
+use wasteday_lib::categories::{self, NewCategory};
+use wasteday_lib::error::{AppError, ErrorCode};
+use wasteday_lib::rules;
+
+mod common;
+use common::migrated_db;
+
+fn new_category(name: &str, color: Option<&str>) -> NewCategory {
+    NewCategory { name: name.to_string(), parent_id: None, color: color.map(str::to_string), waste_weight: None }
+}
+
+#[test]
+fn sqlite_errors_are_mapped_to_codes() {
+    let conn = migrated_db();
+    let no_rows = conn.query_row("SELECT 1 FROM sessions", [], |row| row.get::<_, i64>(0)).unwrap_err();
+    assert_eq!(AppError::from(no_rows).code, ErrorCode::NotFound);
+
+    conn.execute_batch("CREATE TABLE t(k TEXT PRIMARY KEY); INSERT INTO t VALUES('a');").unwrap();
+    let duplicate = AppError::from(conn.execute("INSERT INTO t VALUES('a')", []).unwrap_err());
+    assert_eq!(duplicate.code, ErrorCode::Conflict);
+    assert!(duplicate.details.contains_key("sqlite_code"));
+
+    // SQLite 以外のファイルは DbCorrupt
+    let path = std::env::temp_dir().join(format!("wasteday-errors-test-{}.db", std::process::id()));
+    fs::write(&path, vec![b'x'; 4096]).unwrap();
+    let garbage = Connection::open(&path).unwrap();
+    let err = AppError::from(garbage.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)).unwrap_err());
+    assert_eq!(err.code, ErrorCode::DbCorrupt);
+    drop(garbage);
+    let _ = fs::remove_file(&path);
+
+    let io = AppError::from(fs::read("/definitely/missing/file").unwrap_err());
+    assert_eq!((io.code, io.details.get("kind")), (ErrorCode::Io, Some(&json!("NotFound"))));
+}
+
+#[test]
+fn errors_serialize_with_code_localized_message_and_details() {
+    let err = AppError::not_found("rule", 42);
+    let value: Value = serde_json::to_value(&err).unwrap();
+    assert_eq!(value["code"], "NotFound");
+    assert_eq!(value["message"], "rule not found: 42");
+    assert_eq!(value["details"], json!({ "entity": "rule", "id": 42 }));
+    assert_eq!(value["localized"]["ja"], ErrorCode::NotFound.localized().ja);
+    assert_eq!(value["localized"]["en"], ErrorCode::NotFound.localized().en);
+
+    // 文字列のエラー（設定の validate など）は Validation になる
+    let validation: AppError = "keep must be between 1 and 100".to_string().into();
+    assert_eq!(serde_json::to_value(&validation).unwrap()["code"], "Validation");
+
+    // Mutex の破損は再試行しても直らないので「使用中」とは伝えない
+    assert_eq!(serde_json::to_value(AppError::db_poisoned()).unwrap()["code"], "Internal");
+}
+
+#[test]
+fn commands_report_not_found_conflict_and_validation() {
+    let conn = migrated_db();
+    let missing = rules::get_rule(&conn, 999).unwrap_err();
+    assert_eq!(missing.code, ErrorCode::NotFound);
+    assert_eq!(missing.details.get("id"), Some(&json!(999)));
+    assert_eq!(rules::delete_rule(&conn, 999).unwrap_err().code, ErrorCode::NotFound);
+
+    categories::create_category(&conn, new_category("Reading", None)).unwrap();
+    let duplicate = categories::create_category(&conn, new_category("reading", None)).unwrap_err();
+    assert_eq!(duplicate.code, ErrorCode::Conflict);
+
+    let invalid = categories::create_category(&conn, new_category("Music", Some("blue"))).unwrap_err();
+    assert_eq!(invalid.code, ErrorCode::Validation);
+    assert_eq!(invalid.details.get("field"), Some(&json!("color")));
+}
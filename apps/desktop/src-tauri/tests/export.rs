@@ -0,0 +1,120 @@
+use rusqlite::Connection;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasteday_lib::export::{self, ExportFormat, ExportRequest, ExportTable};
+use wasteday_lib::import::{self, ImportRequest};
+use wasteday_lib::migrations;
+
+mod common;
+use common::fixture_db;
+
+fn out_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wasteday-export-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn request(format: ExportFormat, path: &Path) -> ExportRequest {
+    ExportRequest {
+        format,
+        path: Some(path.to_string_lossy().into_owned()),
+        since: None,
+        until: None,
+        tables: None,
+    }
+}
+
+fn rows(result: &export::ExportResult, table: &str) -> usize {
+    result.tables.iter().find(|t| t.table == table).map(|t| t.rows).unwrap()
+}
+
+#[test]
+fn json_export_contains_all_tables_with_labels() {
+    let conn = fixture_db();
+    let file = out_dir("json").join("export.json");
+    let result = export::export(&conn, &request(ExportFormat::Json, &file)).unwrap();
+    assert_eq!(rows(&result, "sessions"), 3);
+    assert_eq!(rows(&result, "browsing_sessions"), 2);
+
+    let doc: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(doc["format"], "wasteday-export");
+    assert_eq!(doc["schema_version"], migrations::latest_version());
+    let tables = doc["tables"].as_object().unwrap();
+    assert_eq!(tables.len(), ExportTable::ALL.len());
+    let chrome = tables["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["identifier"] == "chrome.exe")
+        .unwrap();
+    assert_eq!(chrome["category_label"], "waste");
+    assert!(chrome["end_time"].is_string());
+}
+
+#[test]
+fn date_range_limits_activity_rows_only() {
+    let conn = fixture_db();
+    let file = out_dir("range").join("export.ndjson");
+    let mut req = request(ExportFormat::Ndjson, &file);
+    req.since = Some("2025-01-01T09:30:00Z".to_string());
+    req.until = Some("2025-01-01T10:00:00Z".to_string());
+    let result = export::export(&conn, &req).unwrap();
+    assert_eq!(rows(&result, "sessions"), 2);
+    assert_eq!(rows(&result, "waste_categories"), 3);
+
+    let content = fs::read_to_string(&file).unwrap();
+    let lines: Vec<Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines[0]["type"], "meta");
+    let total: usize = result.tables.iter().map(|t| t.rows).sum();
+    assert_eq!(lines.len(), total + 1);
+    assert!(lines[1..].iter().all(|l| l["table"].is_string() && l["row"].is_object()));
+}
+
+#[test]
+fn csv_export_writes_one_file_per_table() {
+    let conn = fixture_db();
+    conn.execute(
+        "UPDATE sessions SET window_title = 'Inbox, \"urgent\"' WHERE identifier = 'chrome.exe'",
+        [],
+    )
+    .unwrap();
+    let dir = out_dir("csv");
+    let mut req = request(ExportFormat::Csv, &dir);
+    req.tables = Some(vec![ExportTable::Sessions, ExportTable::Domains]);
+    let result = export::export(&conn, &req).unwrap();
+    assert_eq!(result.files.len(), 2);
+
+    let sessions = fs::read_to_string(dir.join("sessions.csv")).unwrap();
+    let mut lines = sessions.trim_start_matches('\u{feff}').lines();
+    assert!(lines.next().unwrap().starts_with("id,start_time,end_time,duration_seconds"));
+    assert!(sessions.contains("\"Inbox, \"\"urgent\"\"\""));
+    assert_eq!(sessions.lines().count(), 4);
+    assert!(dir.join("domains.csv").exists());
+    assert!(!dir.join("user_settings.csv").exists());
+}
+
+#[test]
+fn csv_export_neutralizes_formulas() {
+    let conn = fixture_db();
+    conn.execute("UPDATE sessions SET window_title = '=HYPERLINK(\"http://evil\")' WHERE identifier = 'chrome.exe'", []).unwrap();
+    conn.execute("UPDATE sessions SET window_title = '@SUM(A1)' WHERE identifier = 'code.exe'", []).unwrap();
+    let dir = out_dir("csv-formula");
+    let mut req = request(ExportFormat::Csv, &dir);
+    req.tables = Some(vec![ExportTable::Sessions]);
+    export::export(&conn, &req).unwrap();
+
+    // 表計算ソフトで開いても数式として評価されない（数値の列はそのまま）
+    let sessions = fs::read_to_string(dir.join("sessions.csv")).unwrap();
+    assert!(sessions.contains("\"'=HYPERLINK(\"\"http://evil\"\")\""), "{}", sessions);
+    assert!(sessions.contains(",'@SUM(A1),"), "{}", sessions);
+    assert!(!sessions.lines().flat_map(|l| l.split(',')).any(|f| f.starts_with(['=', '+', '@'])));
+
+    // 取り込むときは元の値に戻す
+    let mut target = Connection::open_in_memory().unwrap();
+    migrations::migrate(&mut target).unwrap();
+    import::import(&mut target, &ImportRequest { path: dir.to_string_lossy().into_owned(), format: None }).unwrap();
+    let title: String = target.query_row("SELECT window_title FROM sessions WHERE identifier = 'code.exe'", [], |row| row.get(0)).unwrap();
+    assert_eq!(title, "@SUM(A1)");
+}
@@ -0,0 +1,67 @@
+-- 旧 apply_schema（user_version 導入前）で作成されたDBの再現
+PRAGMA foreign_keys = ON;
+CREATE TABLE IF NOT EXISTS sessions (
+  id TEXT PRIMARY KEY,
+  start_time TEXT NOT NULL,
+  duration_seconds INTEGER NOT NULL,
+  session_key TEXT NOT NULL,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+CREATE INDEX IF NOT EXISTS idx_sessions_start_time ON sessions(start_time);
+CREATE TABLE IF NOT EXISTS waste_categories (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  type TEXT NOT NULL,
+  identifier TEXT NOT NULL,
+  label TEXT NOT NULL,
+  is_active INTEGER NOT NULL DEFAULT 1,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  UNIQUE(type, identifier)
+);
+CREATE TABLE IF NOT EXISTS user_settings (
+  key TEXT PRIMARY KEY,
+  value TEXT NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+CREATE TABLE IF NOT EXISTS browsing_sessions (
+  id TEXT PRIMARY KEY,
+  domain TEXT NOT NULL,
+  url TEXT NOT NULL,
+  title TEXT,
+  start_time TEXT NOT NULL,
+  duration_seconds INTEGER DEFAULT 0,
+  category_id INTEGER,
+  tab_id INTEGER,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  FOREIGN KEY (category_id) REFERENCES waste_categories (id)
+);
+CREATE INDEX IF NOT EXISTS idx_browsing_sessions_domain ON browsing_sessions(domain);
+CREATE INDEX IF NOT EXISTS idx_browsing_sessions_start_time ON browsing_sessions(start_time);
+CREATE TABLE IF NOT EXISTS domains (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  domain TEXT UNIQUE NOT NULL,
+  category_id INTEGER,
+  is_active INTEGER NOT NULL DEFAULT 1,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  FOREIGN KEY (category_id) REFERENCES waste_categories (id)
+);
+CREATE INDEX IF NOT EXISTS idx_domains_domain ON domains(domain);
+
+INSERT INTO waste_categories(id, type, identifier, label, is_active) VALUES
+  (1, 'app', 'chrome.exe', 'waste', 1),
+  (2, 'app', 'code.exe', 'productive', 1),
+  (3, 'domain', 'youtube.com', 'waste', 1);
+INSERT INTO domains(domain, category_id, is_active) VALUES
+  ('youtube.com', 3, 1),
+  ('github.com', NULL, 1);
+INSERT INTO sessions(id, start_time, duration_seconds, session_key) VALUES
+  ('2025-01-01T09:00:00.000Z-category=app;identifier=code.exe;user_state=active', '2025-01-01T09:00:00.000Z', 1800, 'category=app;identifier=code.exe;user_state=active'),
+  ('2025-01-01T09:30:00.000Z-category=app;identifier=chrome.exe;user_state=active', '2025-01-01T09:30:00.000Z', 600, 'category=app;identifier=chrome.exe;user_state=active'),
+  ('2025-01-01T09:40:00.000Z-category=app;identifier=chrome.exe;user_state=idle', '2025-01-01T09:40:00.000Z', 120, 'category=app;identifier=chrome.exe;user_state=idle');
+INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, category_id, tab_id) VALUES
+  ('2025-01-01T09:30:00.000Z-youtube.com-12', 'youtube.com', 'https://www.youtube.com/watch?v=x', 'Video', '2025-01-01T09:30:00.000Z', 300, 3, 12),
+  ('2025-01-01T09:35:00.000Z-github.com-13', 'github.com', 'https://github.com/', 'GitHub', '2025-01-01T09:35:00.000Z', 300, NULL, 13);
+INSERT INTO user_settings(key, value) VALUES ('has_run_before', 'true');
@@ -0,0 +1,145 @@
+use rusqlite::Connection;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use wasteday_lib::export::{self, ExportFormat, ExportRequest};
+use wasteday_lib::import::{self, ImportFormat, ImportRequest, ImportResult};
+
+mod common;
+use common::{fixture_db, migrated_db};
+
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wasteday-import-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn import_file(conn: &mut Connection, path: &std::path::Path) -> ImportResult {
+    let request = ImportRequest { path: path.to_string_lossy().into_owned(), format: None };
+    import::import(conn, &request).unwrap()
+}
+
+fn counts(result: &ImportResult) -> (usize, usize, usize) {
+    (result.inserted, result.updated, result.skipped)
+}
+
+#[test]
+fn own_json_export_round_trips_without_duplicates() {
+    let source = fixture_db();
+    let file = work_dir("json").join("export.json");
+    let request = ExportRequest { format: ExportFormat::Json, path: Some(file.to_string_lossy().into_owned()), since: None, until: None, tables: None };
+    export::export(&source, &request).unwrap();
+
+    let mut target = migrated_db();
+    let first = import_file(&mut target, &file);
+    assert_eq!(first.format, ImportFormat::Wasteday);
+    assert_eq!(counts(&first), (5, 0, 0));
+    let identifier: String = target
+        .query_row("SELECT identifier FROM sessions WHERE session_key LIKE '%chrome.exe;user_state=active'", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(identifier, "chrome.exe");
+
+    let again = import_file(&mut target, &file);
+    assert_eq!(counts(&again), (0, 0, 5));
+}
+
+#[test]
+fn ndjson_import_updates_changed_rows() {
+    let source = fixture_db();
+    let file = work_dir("ndjson").join("export.ndjson");
+    let request = ExportRequest { format: ExportFormat::Ndjson, path: Some(file.to_string_lossy().into_owned()), since: None, until: None, tables: None };
+    export::export(&source, &request).unwrap();
+
+    let mut target = fixture_db();
+    target.execute("UPDATE sessions SET duration_seconds = 1 WHERE session_key LIKE '%chrome.exe;user_state=active'", []).unwrap();
+    let result = import_file(&mut target, &file);
+    assert_eq!(counts(&result), (0, 1, 4));
+    assert_eq!(result.tables[0].updated, 1);
+
+    // 手修正済みの行は上書きしない
+    target.execute("UPDATE sessions SET duration_seconds = 1, is_manual = 1 WHERE session_key LIKE '%chrome.exe;user_state=active'", []).unwrap();
+    target.execute("UPDATE browsing_sessions SET title = 'edited', is_manual = 1", []).unwrap();
+    let result = import_file(&mut target, &file);
+    assert_eq!(counts(&result), (0, 0, 5));
+    let (duration, manual): (i64, bool) = target
+        .query_row("SELECT duration_seconds, is_manual FROM sessions WHERE session_key LIKE '%chrome.exe;user_state=active'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((duration, manual), (1, true));
+    let edited: i64 = target.query_row("SELECT COUNT(*) FROM browsing_sessions WHERE title = 'edited' AND is_manual = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(edited, 2);
+}
+
+#[test]
+fn activitywatch_buckets_map_to_sessions_and_browsing() {
+    let doc = json!({
+        "buckets": {
+            "aw-watcher-window_host": {
+                "id": "aw-watcher-window_host",
+                "type": "currentwindow",
+                "events": [
+                    { "timestamp": "2025-01-01T09:00:00.000000+00:00", "duration": 120.4, "data": { "app": "Code.exe", "title": "main.rs" } },
+                    { "timestamp": "2025-01-01T09:02:00.000000+00:00", "duration": 0.0, "data": { "app": "Code.exe", "title": "main.rs" } },
+                    { "timestamp": "2025-01-01T09:03:00.000000+00:00", "duration": 30, "data": { "title": "no app" } }
+                ]
+            },
+            "aw-watcher-web-chrome": {
+                "id": "aw-watcher-web-chrome",
+                "type": "web.tab.current",
+                "events": [
+                    { "timestamp": "2025-01-01T09:05:00Z", "duration": 60, "data": { "url": "https://www.YouTube.com/watch?v=1", "title": "Video" } }
+                ]
+            },
+            "aw-watcher-afk_host": {
+                "id": "aw-watcher-afk_host",
+                "type": "afkstatus",
+                "events": [{ "timestamp": "2025-01-01T09:00:00Z", "duration": 600, "data": { "status": "not-afk" } }]
+            }
+        }
+    });
+    let file = work_dir("aw").join("aw-buckets.json");
+    fs::write(&file, doc.to_string()).unwrap();
+
+    let mut conn = migrated_db();
+    let result = import_file(&mut conn, &file);
+    assert_eq!(result.format, ImportFormat::ActivityWatch);
+    assert_eq!(counts(&result), (2, 0, 2));
+    assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+
+    let (id, duration): (String, i64) = conn
+        .query_row("SELECT id, duration_seconds FROM sessions", [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap();
+    assert_eq!(id, "2025-01-01T09:00:00.000Z-category=app;identifier=code.exe;user_state=active");
+    assert_eq!(duration, 120);
+    let domain: String = conn.query_row("SELECT domain FROM browsing_sessions", [], |row| row.get(0)).unwrap();
+    assert_eq!(domain, "www.youtube.com");
+}
+
+#[test]
+fn generic_csv_accepts_column_aliases() {
+    let file = work_dir("csv").join("toggl.csv");
+    fs::write(
+        &file,
+        "App,Title,Start,End\r\n\
+         chrome.exe,\"Inbox, \"\"urgent\"\"\",2025-01-01T09:00:00Z,2025-01-01T09:10:00Z\r\n\
+         slack.exe,Chat,2025-01-01T09:10:00Z,2025-01-01T09:15:30Z\r\n\
+         broken.exe,Oops,yesterday,2025-01-01T09:20:00Z\r\n",
+    )
+    .unwrap();
+
+    let mut conn = migrated_db();
+    let result = import_file(&mut conn, &file);
+    assert_eq!(result.format, ImportFormat::Csv);
+    assert_eq!(counts(&result), (2, 0, 1));
+    assert!(result.errors[0].contains("row 4"), "{:?}", result.errors);
+
+    let (title, duration): (String, i64) = conn
+        .query_row("SELECT window_title, duration_seconds FROM sessions WHERE identifier = 'chrome.exe'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(title, "Inbox, \"urgent\"");
+    assert_eq!(duration, 600);
+}
@@ -0,0 +1,134 @@
+use rusqlite::{params, Connection};
+use wasteday_lib::error::ErrorCode;
+use wasteday_lib::ingest_server::handlers::{self, BatchItemStatus, BrowserData};
+use wasteday_lib::rules::RuleSet;
+
+mod common;
+use common::migrated_db;
+
+// 2025-01-01T10:00:00Z からの経過秒で開始時刻を指定する
+fn beat(url: &str, offset: i64, duration: u64, tab_id: u32) -> BrowserData {
+    let timestamp = chrono::DateTime::parse_from_rfc3339("2025-01-01T10:00:00.000Z").unwrap() + chrono::Duration::seconds(offset);
+    BrowserData {
+        url: url.to_string(),
+        domain: url.trim_start_matches("https://").split('/').next().unwrap().to_string(),
+        title: format!("{} +{}", url, offset),
+        timestamp: timestamp.to_utc().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        duration: Some(duration),
+        tab_id: Some(tab_id),
+    }
+}
+
+fn rows(conn: &Connection) -> Vec<(String, String, i64)> {
+    let mut stmt = conn.prepare("SELECT url, start_time, duration_seconds FROM browsing_sessions ORDER BY start_time, url").unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .unwrap()
+}
+
+const PAGE: &str = "https://example.com/a";
+
+#[test]
+fn consecutive_heartbeats_extend_the_open_session() {
+    let conn = migrated_db();
+    let first = handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 30, 1)).unwrap();
+    assert_eq!(handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 30, 30, 1)).unwrap(), first);
+    // 送信が少し遅れても pulsetime 以内なら同じセッション
+    assert_eq!(handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 100, 30, 1)).unwrap(), first);
+    assert_eq!(rows(&conn), vec![(PAGE.to_string(), "2025-01-01T10:00:00.000Z".to_string(), 130)]);
+
+    // タブを離れたときの送信（開始時刻 + 全体の経過秒）も同じ行にまとまる
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 140, 1)).unwrap();
+    assert_eq!(rows(&conn)[0].2, 140);
+    let title: String = conn.query_row("SELECT title FROM browsing_sessions", [], |row| row.get(0)).unwrap();
+    assert_eq!(title, format!("{} +0", PAGE));
+}
+
+#[test]
+fn duplicate_heartbeats_are_idempotent() {
+    let conn = migrated_db();
+    for _ in 0..3 {
+        handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 30, 1)).unwrap();
+        handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 30, 30, 1)).unwrap();
+    }
+    assert_eq!(rows(&conn), vec![(PAGE.to_string(), "2025-01-01T10:00:00.000Z".to_string(), 60)]);
+}
+
+#[test]
+fn out_of_order_heartbeats_merge_and_bridge_gaps() {
+    let conn = migrated_db();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 60, 30, 1)).unwrap();
+    // 前の区間が後から届いたら開始を前に広げる（タイトルは新しい方のまま）
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 30, 30, 1)).unwrap();
+    assert_eq!(rows(&conn), vec![(PAGE.to_string(), "2025-01-01T10:00:30.000Z".to_string(), 60)]);
+    let title: String = conn.query_row("SELECT title FROM browsing_sessions", [], |row| row.get(0)).unwrap();
+    assert_eq!(title, format!("{} +60", PAGE));
+
+    // pulsetime を超えて離れた区間は別の行になり、間を埋める区間が届くと1行にまとまる
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 300, 30, 1)).unwrap();
+    assert_eq!(rows(&conn).len(), 2);
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 120, 150, 1)).unwrap();
+    assert_eq!(rows(&conn), vec![(PAGE.to_string(), "2025-01-01T10:00:30.000Z".to_string(), 300)]);
+}
+
+#[test]
+fn other_tabs_urls_and_manual_rows_are_not_merged() {
+    let conn = migrated_db();
+    let first = handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 30, 1)).unwrap();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 30, 30, 2)).unwrap();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat("https://example.com/b", 30, 30, 1)).unwrap();
+    assert_eq!(rows(&conn).len(), 3);
+
+    conn.execute("UPDATE browsing_sessions SET is_manual = 1 WHERE id = ?1", params![first]).unwrap();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 40, 30, 1)).unwrap();
+    let manual: i64 = conn.query_row("SELECT duration_seconds FROM browsing_sessions WHERE id = ?1", params![first], |row| row.get(0)).unwrap();
+    assert_eq!(manual, 30);
+    assert_eq!(rows(&conn).len(), 4);
+}
+
+#[test]
+fn events_colliding_with_a_manual_row_are_rejected() {
+    let mut conn = migrated_db();
+    let id = handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 30, 1)).unwrap();
+    conn.execute("UPDATE browsing_sessions SET is_manual = 1, duration_seconds = 10 WHERE id = ?1", params![id]).unwrap();
+
+    // 同じ開始時刻・ドメイン・タブのイベントは同じ id になるが、手修正済みの行は書き換えない
+    let err = handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 0, 30, 1)).unwrap_err();
+    assert_eq!(err.code, ErrorCode::Conflict);
+
+    let items = [beat(PAGE, 0, 60, 1), beat(PAGE, 0, 30, 2)]
+        .iter()
+        .map(|b| serde_json::to_value(b).unwrap())
+        .collect();
+    let result = handlers::ingest_browser_batch(&mut conn, &RuleSet::default(), items).unwrap();
+    assert_eq!((result.accepted, result.rejected), (1, 1));
+    assert_eq!(result.results[0].status, BatchItemStatus::Rejected);
+    assert_eq!(rows(&conn)[0].2, 10);
+}
+
+#[test]
+fn failed_batch_items_roll_back_their_merges() {
+    let mut conn = migrated_db();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 60, 30, 1)).unwrap();
+    handlers::upsert_browser_data(&conn, &RuleSet::default(), &beat(PAGE, 300, 30, 1)).unwrap();
+    // 2つの行をつなぐ要素だけ、後ろの行を消した後の更新で失敗させる
+    conn.execute_batch(
+        "CREATE TRIGGER fail_bridge BEFORE UPDATE ON browsing_sessions WHEN NEW.title LIKE '%+120'
+         BEGIN SELECT RAISE(ABORT, 'bridge failed'); END;",
+    )
+    .unwrap();
+    let before = rows(&conn);
+
+    let items = [beat(PAGE, 120, 240, 1), beat("https://example.com/b", 0, 30, 1)]
+        .iter()
+        .map(|b| serde_json::to_value(b).unwrap())
+        .collect();
+    let result = handlers::ingest_browser_batch(&mut conn, &RuleSet::default(), items).unwrap();
+    assert_eq!((result.accepted, result.rejected), (1, 1));
+    assert!(result.results[0].error.as_deref().unwrap().contains("bridge failed"));
+
+    let after = rows(&conn);
+    assert_eq!(after.len(), 3);
+    assert!(before.iter().all(|row| after.contains(row)));
+}
@@ -0,0 +1,273 @@
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use wasteday_lib::ingest_auth;
+use wasteday_lib::ingest_server::{self, IngestServerConfig, IngestServerState, RunningServer, ServerState, SharedConnection};
+
+const EXTENSION_ORIGIN: &str = "chrome-extension://abcdefghijklmnop";
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+fn start() -> (RunningServer, SharedConnection) {
+    let mut conn = Connection::open_in_memory().unwrap();
+    wasteday_lib::migrations::migrate(&mut conn).unwrap();
+    let db: SharedConnection = Arc::new(Mutex::new(conn));
+    let config = IngestServerConfig { bind_address: "127.0.0.1".to_string(), port: 0 };
+    let server = ingest_server::start(&config, db.clone()).unwrap();
+    (server, db)
+}
+
+fn request(port: u16, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> Reply {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut raw = format!(
+        "{} {} HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        body.len()
+    );
+    for (k, v) in headers {
+        raw.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    raw.push_str("\r\n");
+    raw.push_str(body);
+    stream.write_all(raw.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines();
+    let status = lines.next().unwrap().split_whitespace().nth(1).unwrap().parse().unwrap();
+    let headers = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    Reply { status, headers, body: body.to_string() }
+}
+
+fn pair(port: u16, db: &SharedConnection) -> String {
+    let code = ingest_auth::start_pairing(&db.lock().unwrap()).unwrap().code;
+    let reply = request(
+        port,
+        "POST",
+        "/api/pair",
+        &[("Origin", EXTENSION_ORIGIN), ("Content-Type", "application/json")],
+        &json!({ "code": code, "name": "Chrome" }).to_string(),
+    );
+    assert_eq!(reply.status, 200, "{}", reply.body);
+    reply.json()["token"].as_str().unwrap().to_string()
+}
+
+fn browsing(timestamp: &str, domain: &str, duration: u64) -> Value {
+    json!({
+        "url": format!("https://{}/", domain),
+        "domain": domain,
+        "title": domain,
+        "timestamp": timestamp,
+        "duration": duration,
+        "tab_id": 1
+    })
+}
+
+fn count_browsing(db: &SharedConnection) -> i64 {
+    db.lock().unwrap().query_row("SELECT COUNT(*) FROM browsing_sessions", [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn health_reports_bound_port() {
+    let (server, _db) = start();
+    let reply = request(server.port, "GET", "/api/health", &[], "");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Type"), Some("application/json"));
+    let body = reply.json();
+    assert_eq!(body["app"], "wasteday");
+    assert_eq!(body["port"], server.port);
+}
+
+#[test]
+fn ingest_requires_token() {
+    let (server, db) = start();
+    let body = browsing("2025-01-01T00:00:00.000Z", "example.com", 30).to_string();
+
+    let missing = request(server.port, "POST", "/api/ingest/browsing", &[], &body);
+    assert_eq!(missing.status, 401);
+    assert_eq!(missing.json()["error"], "missing bearer token");
+
+    let invalid = request(server.port, "POST", "/api/ingest/browsing", &[("Authorization", "Bearer nope")], &body);
+    assert_eq!(invalid.status, 401);
+    assert_eq!(count_browsing(&db), 0);
+}
+
+#[test]
+fn paired_client_can_ingest() {
+    let (server, db) = start();
+    let token = pair(server.port, &db);
+    let auth = format!("Bearer {}", token);
+    let body = browsing("2025-01-01T00:00:00.000Z", "example.com", 30).to_string();
+
+    let reply = request(
+        server.port,
+        "POST",
+        "/api/ingest/browsing",
+        &[("Authorization", &auth), ("Origin", EXTENSION_ORIGIN)],
+        &body,
+    );
+    assert_eq!(reply.status, 200, "{}", reply.body);
+    assert_eq!(reply.json()["id"], "2025-01-01T00:00:00.000Z-example.com-1");
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), Some(EXTENSION_ORIGIN));
+    assert_eq!(count_browsing(&db), 1);
+}
+
+#[test]
+fn web_page_origins_are_rejected() {
+    let (server, db) = start();
+    let token = pair(server.port, &db);
+    let auth = format!("Bearer {}", token);
+    let body = browsing("2025-01-01T00:00:00.000Z", "example.com", 30).to_string();
+
+    let reply = request(
+        server.port,
+        "POST",
+        "/api/ingest/browsing",
+        &[("Authorization", &auth), ("Origin", "https://evil.example")],
+        &body,
+    );
+    assert_eq!(reply.status, 403);
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), None);
+
+    let pairing = request(server.port, "POST", "/api/pair", &[("Origin", "https://evil.example")], r#"{"code":"000000"}"#);
+    assert_eq!(pairing.status, 403);
+    assert_eq!(count_browsing(&db), 0);
+}
+
+#[test]
+fn preflight_allows_only_paired_extension() {
+    let (server, db) = start();
+    let unpaired = request(server.port, "OPTIONS", "/api/ingest/browsing/batch", &[("Origin", EXTENSION_ORIGIN)], "");
+    assert_eq!(unpaired.status, 403);
+
+    pair(server.port, &db);
+    let reply = request(server.port, "OPTIONS", "/api/ingest/browsing/batch", &[("Origin", EXTENSION_ORIGIN)], "");
+    assert_eq!(reply.status, 204);
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), Some(EXTENSION_ORIGIN));
+    assert!(reply.header("Access-Control-Allow-Headers").unwrap().contains("Authorization"));
+}
+
+#[test]
+fn batch_reports_accepted_and_rejected_items() {
+    let (server, db) = start();
+    let auth = format!("Bearer {}", pair(server.port, &db));
+    let items = json!([
+        browsing("2025-01-01T00:00:00.000Z", "a.example", 30),
+        browsing("not a timestamp", "b.example", 30),
+        { "domain": "c.example" },
+        browsing("2025-01-01T00:01:00.000Z", "d.example", 15),
+    ]);
+
+    let reply = request(server.port, "POST", "/api/ingest/browsing/batch", &[("Authorization", &auth)], &items.to_string());
+    assert_eq!(reply.status, 200, "{}", reply.body);
+    let body = reply.json();
+    assert_eq!(body["accepted"], 2);
+    assert_eq!(body["rejected"], 2);
+    let statuses: Vec<&str> = body["results"].as_array().unwrap().iter().map(|r| r["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["accepted", "rejected", "rejected", "accepted"]);
+    assert_eq!(count_browsing(&db), 2);
+
+    let not_array = request(server.port, "POST", "/api/ingest/browsing/batch", &[("Authorization", &auth)], "{}");
+    assert_eq!(not_array.status, 400);
+    assert!(not_array.json()["error"].is_string());
+}
+
+#[test]
+fn policy_returns_decision_for_paired_client() {
+    let (server, db) = start();
+    let body = json!({ "domain": "example.com" }).to_string();
+    assert_eq!(request(server.port, "POST", "/api/policy", &[], &body).status, 401);
+
+    let auth = format!("Bearer {}", pair(server.port, &db));
+    let reply = request(server.port, "POST", "/api/policy", &[("Authorization", &auth), ("Origin", EXTENSION_ORIGIN)], &body);
+    assert_eq!(reply.status, 200, "{}", reply.body);
+    assert_eq!(reply.json()["domain"], "example.com");
+    assert_eq!(reply.json()["action"], "allow");
+
+    let empty = request(server.port, "POST", "/api/policy", &[("Authorization", &auth)], r#"{"domain":""}"#);
+    assert_eq!(empty.status, 400);
+}
+
+#[test]
+fn unknown_routes_return_json_errors() {
+    let (server, _db) = start();
+    let missing = request(server.port, "GET", "/api/nope", &[], "");
+    assert_eq!(missing.status, 404);
+    assert_eq!(missing.json()["error"], "not found");
+
+    let wrong_method = request(server.port, "GET", "/api/ingest/browsing", &[], "");
+    assert_eq!(wrong_method.status, 405);
+    assert_eq!(wrong_method.json()["error"], "method not allowed");
+}
+
+#[test]
+fn stopped_server_releases_port() {
+    let (server, db) = start();
+    let port = server.port;
+    server.stop();
+    let config = IngestServerConfig { bind_address: "127.0.0.1".to_string(), port };
+    let restarted = ingest_server::start(&config, db).unwrap();
+    assert_eq!(restarted.port, port);
+}
+
+#[test]
+fn occupied_port_falls_back_to_the_next_free_one() {
+    // 指定ポートを先に使っておく
+    let occupied = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let requested = occupied.local_addr().unwrap().port();
+    let mut conn = Connection::open_in_memory().unwrap();
+    wasteday_lib::migrations::migrate(&mut conn).unwrap();
+    let db: SharedConnection = Arc::new(Mutex::new(conn));
+
+    let state = IngestServerState::new(IngestServerConfig { bind_address: "127.0.0.1".to_string(), port: requested });
+    let status = state.restart(db);
+    assert_eq!(status.state, ServerState::Running, "{:?}", status.error);
+    assert_eq!(status.requested_port, requested);
+    let port = status.port.unwrap();
+    assert_ne!(port, requested);
+    assert!(port > requested);
+    assert_eq!(state.status().port, Some(port));
+
+    // 拡張機能はフォールバック先のポートで見つけられる
+    let reply = request(port, "GET", "/api/health", &[], "");
+    assert_eq!(reply.json()["port"], port);
+    drop(occupied);
+}
+
+#[test]
+fn slow_request_bodies_do_not_hold_the_db_lock() {
+    let (server, db) = start();
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+    stream
+        .write_all(b"POST /api/pair HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\nContent-Length: 20\r\n\r\n{\"code\":")
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    // ボディを待っている間もアプリ側は DB を使える
+    assert!(db.try_lock().is_ok());
+
+    stream.write_all(b"\"000000\"}   ").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 4"), "{}", response);
+}
@@ -0,0 +1,129 @@
+use rusqlite::Connection;
+use wasteday_lib::migrations::{self, Migration, MigrationError};
+
+mod common;
+use common::{count, BASELINE_FIXTURE};
+
+fn baseline_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(BASELINE_FIXTURE).unwrap();
+    conn
+}
+
+#[test]
+fn fresh_database_is_migrated_to_latest() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let version = migrations::migrate(&mut conn).unwrap();
+    assert_eq!(version, migrations::latest_version());
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version());
+    assert_eq!(count(&conn, "sessions"), 0);
+}
+
+#[test]
+fn baseline_database_upgrades_and_keeps_rows() {
+    let mut conn = baseline_db();
+    assert_eq!(migrations::current_version(&conn).unwrap(), 0);
+
+    let version = migrations::migrate(&mut conn).unwrap();
+    assert_eq!(version, migrations::latest_version());
+    assert_eq!(count(&conn, "sessions"), 3);
+    assert_eq!(count(&conn, "browsing_sessions"), 2);
+    assert_eq!(count(&conn, "waste_categories"), 3);
+    assert_eq!(count(&conn, "domains"), 2);
+    assert_eq!(count(&conn, "user_settings"), 1);
+}
+
+#[test]
+fn migrate_is_idempotent() {
+    let mut conn = baseline_db();
+    let first = migrations::migrate(&mut conn).unwrap();
+    let second = migrations::migrate(&mut conn).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(count(&conn, "sessions"), 3);
+}
+
+#[test]
+fn newer_database_is_refused() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let newer = migrations::latest_version() + 1;
+    conn.pragma_update(None, "user_version", newer).unwrap();
+
+    match migrations::migrate(&mut conn) {
+        Err(MigrationError::NewerSchema { found, supported }) => {
+            assert_eq!(found, newer);
+            assert_eq!(supported, migrations::latest_version());
+        }
+        other => panic!("expected NewerSchema, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn failed_step_is_rolled_back() {
+    let mut conn = baseline_db();
+    migrations::migrate(&mut conn).unwrap();
+    let before = migrations::current_version(&conn).unwrap();
+
+    let mut steps: Vec<Migration> = Vec::new();
+    for m in migrations::MIGRATIONS {
+        steps.push(Migration { version: m.version, name: m.name, sql: m.sql });
+    }
+    steps.push(Migration {
+        version: before + 1,
+        name: "broken",
+        sql: "ALTER TABLE sessions ADD COLUMN note TEXT; INSERT INTO no_such_table VALUES (1);",
+    });
+
+    assert!(matches!(migrations::apply_migrations(&mut conn, &steps), Err(MigrationError::Sqlite(_))));
+    assert_eq!(migrations::current_version(&conn).unwrap(), before);
+    let has_note: i64 = conn
+        .query_row("SELECT COUNT(*) FROM pragma_table_info('sessions') WHERE name = 'note'", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(has_note, 0);
+}
+
+#[test]
+fn out_of_order_steps_are_rejected() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let steps = [Migration { version: 2, name: "skip", sql: "SELECT 1;" }];
+    assert!(matches!(
+        migrations::apply_migrations(&mut conn, &steps),
+        Err(MigrationError::InvalidOrder { version: 2 })
+    ));
+}
+
+#[test]
+fn session_key_columns_are_backfilled() {
+    let mut conn = baseline_db();
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key) VALUES('odd', '2025-01-02T00:00:00.000Z', 5, 'user_state=idle;identifier=Foo.EXE')",
+        [],
+    )
+    .unwrap();
+    migrations::migrate(&mut conn).unwrap();
+
+    let row: (Option<String>, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT category, identifier, user_state FROM sessions WHERE start_time = '2025-01-01T09:40:00.000Z'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(row, (Some("app".into()), Some("chrome.exe".into()), Some("idle".into())));
+
+    // 順序が異なる/欠けている session_key も扱える
+    let odd: (Option<String>, Option<String>, Option<String>) = conn
+        .query_row("SELECT category, identifier, user_state FROM sessions WHERE id = 'odd'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap();
+    assert_eq!(odd, (None, Some("foo.exe".into()), Some("idle".into())));
+
+    let active_chrome: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sessions WHERE identifier = 'chrome.exe' AND user_state = 'active'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(active_chrome, 1);
+}
@@ -0,0 +1,75 @@
+#![cfg(target_os = "linux")]
+use std::ffi::CString;
+use std::os::raw::{c_int, c_long, c_uchar, c_ulong};
+use std::path::Path;
+use std::ptr;
+use wasteday_lib::platform::x11::{cardinal_from_property, exe_name_from_link, title_from_property, X11Provider};
+use wasteday_lib::platform::ActivityProvider;
+use x11_dl::xlib::{self, Xlib};
+
+#[test]
+fn exe_names_come_from_the_proc_link_target() {
+    assert_eq!(exe_name_from_link(Path::new("/usr/lib/firefox/firefox")).as_deref(), Some("firefox"));
+    // 実行中に更新されたバイナリ
+    assert_eq!(exe_name_from_link(Path::new("/opt/code/code (deleted)")).as_deref(), Some("code"));
+    assert_eq!(exe_name_from_link(Path::new("/")), None);
+}
+
+#[test]
+fn titles_and_cardinals_are_decoded_from_property_bytes() {
+    assert_eq!(title_from_property("main.rs - エディタ".as_bytes()), "main.rs - エディタ");
+    assert_eq!(title_from_property(b"Terminal\0"), "Terminal");
+    assert_eq!(title_from_property(b"bad \xff byte"), "bad \u{fffd} byte");
+
+    let bytes = (4242 as c_long).to_ne_bytes();
+    assert_eq!(cardinal_from_property(32, &bytes, 1), Some(4242));
+    assert_eq!(cardinal_from_property(8, &bytes, 1), None);
+    assert_eq!(cardinal_from_property(32, &bytes, 0), None);
+    assert_eq!(cardinal_from_property(32, &bytes[..2], 1), None);
+}
+
+// 実サーバでの確認は DISPLAY があるときだけ（CI では xvfb-run の下で動かす）
+// WM のいない Xvfb では誰も設定しないので、ウィンドウを作って _NET_ACTIVE_WINDOW などを自分で書く
+#[test]
+fn provider_reads_the_active_window_from_a_live_x_server() {
+    if std::env::var_os("DISPLAY").is_none() {
+        eprintln!("DISPLAY is not set; skipping the X11 provider test");
+        return;
+    }
+    let title = "main.rs - エディタ";
+    let xlib = Xlib::open().unwrap();
+    unsafe {
+        let display = (xlib.XOpenDisplay)(ptr::null());
+        assert!(!display.is_null(), "cannot open DISPLAY");
+        let atom = |name: &str| {
+            let cname = CString::new(name).unwrap();
+            (xlib.XInternAtom)(display, cname.as_ptr(), xlib::False)
+        };
+        let set_property = |window: c_ulong, property: &str, kind: c_ulong, format: c_int, data: *const c_uchar, len: usize| {
+            (xlib.XChangeProperty)(display, window, atom(property), kind, format, xlib::PropModeReplace, data, len as c_int);
+        };
+        let root = (xlib.XDefaultRootWindow)(display);
+        let window = (xlib.XCreateSimpleWindow)(display, root, 0, 0, 10, 10, 0, 0, 0);
+        set_property(window, "_NET_WM_NAME", atom("UTF8_STRING"), 8, title.as_ptr(), title.len());
+        let pid = std::process::id() as c_long;
+        set_property(window, "_NET_WM_PID", xlib::XA_CARDINAL, 32, &pid as *const c_long as *const c_uchar, 1);
+        let active = window as c_long;
+        set_property(root, "_NET_ACTIVE_WINDOW", xlib::XA_WINDOW, 32, &active as *const c_long as *const c_uchar, 1);
+        (xlib.XSync)(display, xlib::False);
+
+        let provider = X11Provider::new().unwrap();
+        let info = provider.foreground_info();
+        let idle = provider.idle_seconds();
+
+        // 片付けは検証の前に（失敗してもルートに壊れた値を残さない）
+        (xlib.XDeleteProperty)(display, root, atom("_NET_ACTIVE_WINDOW"));
+        (xlib.XDestroyWindow)(display, window);
+        (xlib.XCloseDisplay)(display);
+
+        assert_eq!(info.process_id, std::process::id());
+        assert_eq!(info.window_title, title);
+        assert_eq!(Some(info.exe), exe_name_from_link(&std::env::current_exe().unwrap()));
+        // 入力のない Xvfb ではサーバ起動からの秒数になる
+        assert!(idle < 24 * 60 * 60, "idle_seconds = {}", idle);
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Duration, Local, SecondsFormat, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use wasteday_lib::budgets::{self, BudgetTarget, NewBudget};
+use wasteday_lib::policy::{self, FocusSchedule, PolicyAction, PolicyConfig, PolicyConfigPatch, PolicyQuery, PolicyReason};
+use wasteday_lib::rules::RuleSet;
+
+mod common;
+use common::fixture_db;
+
+// マイグレーションで作られる既定カテゴリ（youtube.com はここに割り当てられる）
+const WASTE: i64 = 2;
+
+// 2025-03-10 は月曜日
+fn at(hour: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(2025, 3, 10, hour, 0, 0).unwrap()
+}
+
+fn insert_browsing(conn: &Connection, id: &str, domain: &str, minutes_ago: i64, seconds: i64) {
+    let start = (at(15) - Duration::minutes(minutes_ago)).with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true);
+    conn.execute(
+        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds) VALUES(?1, ?2, 'https://' || ?2 || '/', NULL, ?3, ?4)",
+        params![id, domain, start, seconds],
+    )
+    .unwrap();
+}
+
+fn query(domain: &str) -> PolicyQuery {
+    PolicyQuery { domain: domain.to_string(), url: None, title: None }
+}
+
+fn schedule(days: &[u32], start: &str, end: &str) -> FocusSchedule {
+    FocusSchedule { days: days.to_vec(), start: start.to_string(), end: end.to_string() }
+}
+
+#[test]
+fn category_budget_warns_then_blocks() {
+    let conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    let config = PolicyConfig::default();
+    budgets::create_budget(
+        &conn,
+        NewBudget { target_type: BudgetTarget::Category, target: WASTE.to_string(), daily_limit_seconds: 600, is_active: None },
+    )
+    .unwrap();
+
+    insert_browsing(&conn, "b1", "youtube.com", 20, 510);
+    let d = policy::decide(&conn, &rules, &config, &query("YouTube.com"), at(15)).unwrap();
+    assert_eq!((d.action, d.reason), (PolicyAction::Warn, Some(PolicyReason::Budget)));
+    assert_eq!(d.label.as_deref(), Some("waste"));
+    assert_eq!(d.budget.as_ref().unwrap().remaining_seconds, 90);
+    assert!(d.message.unwrap().contains("残り1分"));
+
+    // 未分類のドメインにはカテゴリの上限は掛からない
+    let d = policy::decide(&conn, &rules, &config, &query("github.com"), at(15)).unwrap();
+    assert_eq!((d.action, d.budget), (PolicyAction::Allow, None));
+
+    insert_browsing(&conn, "b2", "youtube.com", 5, 120);
+    let d = policy::decide(&conn, &rules, &config, &query("youtube.com"), at(15)).unwrap();
+    assert_eq!((d.action, d.reason), (PolicyAction::Block, Some(PolicyReason::Budget)));
+
+    let warn_only = config.apply(PolicyConfigPatch { budget_action: Some(PolicyAction::Warn), ..Default::default() }).unwrap();
+    assert_eq!(policy::decide(&conn, &rules, &warn_only, &query("youtube.com"), at(15)).unwrap().action, PolicyAction::Warn);
+}
+
+#[test]
+fn domain_budget_covers_subdomains() {
+    let conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    budgets::create_budget(
+        &conn,
+        NewBudget { target_type: BudgetTarget::Domain, target: "twitter.com".to_string(), daily_limit_seconds: 600, is_active: None },
+    )
+    .unwrap();
+    insert_browsing(&conn, "b1", "mobile.twitter.com", 30, 600);
+
+    let d = policy::decide(&conn, &rules, &PolicyConfig::default(), &query("twitter.com"), at(15)).unwrap();
+    assert_eq!(d.action, PolicyAction::Block);
+    assert!(d.budget.unwrap().exceeded);
+    assert_eq!(policy::decide(&conn, &rules, &PolicyConfig::default(), &query("nottwitter.com"), at(15)).unwrap().action, PolicyAction::Allow);
+
+    let disabled = PolicyConfig::default().apply(PolicyConfigPatch { enabled: Some(false), ..Default::default() }).unwrap();
+    assert_eq!(policy::decide(&conn, &rules, &disabled, &query("twitter.com"), at(15)).unwrap().action, PolicyAction::Allow);
+}
+
+#[test]
+fn focus_schedule_applies_to_waste_domains_only() {
+    let conn = fixture_db();
+    let rules = RuleSet::load(&conn).unwrap();
+    let config = PolicyConfig::default()
+        .apply(PolicyConfigPatch {
+            focus_schedules: Some(vec![schedule(&[1], "14:00", "16:00"), schedule(&[7], "22:00", "07:00")]),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let d = policy::decide(&conn, &rules, &config, &query("youtube.com"), at(15)).unwrap();
+    assert_eq!((d.action, d.reason), (PolicyAction::Block, Some(PolicyReason::Focus)));
+    assert_eq!(d.focus_until, Some(at(16).to_rfc3339_opts(SecondsFormat::Secs, false)));
+    assert_eq!(policy::decide(&conn, &rules, &config, &query("github.com"), at(15)).unwrap().action, PolicyAction::Allow);
+
+    // 日曜 22:00 からの枠は月曜 07:00 まで続く
+    assert_eq!(policy::decide(&conn, &rules, &config, &query("youtube.com"), at(6)).unwrap().action, PolicyAction::Block);
+    assert_eq!(policy::decide(&conn, &rules, &config, &query("youtube.com"), at(8)).unwrap().action, PolicyAction::Allow);
+    assert_eq!(policy::decide(&conn, &rules, &config, &query("youtube.com"), at(23)).unwrap().action, PolicyAction::Allow);
+}
+
+#[test]
+fn invalid_policy_config_is_rejected() {
+    let base = PolicyConfig::default();
+    let with = |s: FocusSchedule| base.apply(PolicyConfigPatch { focus_schedules: Some(vec![s]), ..Default::default() });
+    assert!(with(schedule(&[], "09:00", "09:00")).is_err());
+    assert!(with(schedule(&[8], "09:00", "10:00")).is_err());
+    assert!(with(schedule(&[], "9am", "10:00")).is_err());
+    assert!(base.apply(PolicyConfigPatch { warn_percent: Some(101), ..Default::default() }).is_err());
+
+    let conn = fixture_db();
+
+    let rules = RuleSet::load(&conn).unwrap();
+    let config = with(schedule(&[1, 2], "09:00", "12:00")).unwrap();
+    config.save(&conn).unwrap();
+    assert_eq!(PolicyConfig::load(&conn), config);
+    assert!(policy::decide(&conn, &rules, &config, &query(" "), at(15)).is_err());
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use wasteday_lib::repository::{
+    load_json_setting, save_json_setting, BrowsingSession, BrowsingSessionsQuery, Domain, Repository, Session, SessionsQuery,
+    SqliteRepository, WasteCategory,
+};
+
+mod common;
+use common::migrated_db;
+
+fn session(start_time: &str, identifier: &str, duration_seconds: i64) -> Session {
+    let session_key = format!("category=app;identifier={};user_state=active", identifier);
+    Session {
+        id: format!("{}-{}", start_time, session_key),
+        start_time: start_time.to_string(),
+        duration_seconds,
+        session_key,
+        category: None,
+        identifier: None,
+        user_state: None,
+        window_title: None,
+        is_manual: false,
+        category_id: None,
+    }
+}
+
+fn browsing(id: &str, domain: &str, start_time: &str, tab_id: i32) -> BrowsingSession {
+    BrowsingSession {
+        id: id.to_string(),
+        domain: domain.to_string(),
+        url: format!("https://{}/", domain),
+        title: Some("page".to_string()),
+        start_time: start_time.to_string(),
+        duration_seconds: 30,
+        category_id: None,
+        tab_id: Some(tab_id),
+        is_manual: false,
+    }
+}
+
+fn app_category(identifier: &str, label: &str) -> WasteCategory {
+    WasteCategory { id: None, r#type: "app".to_string(), identifier: identifier.to_string(), label: label.to_string(), is_active: true, category_id: None }
+}
+
+// トレイト越しにだけ触る（Tauri コマンドや取り込みAPIと同じ使い方）
+fn session_ids(repo: &impl Repository, query: &SessionsQuery) -> Vec<String> {
+    repo.list_sessions(query).unwrap().into_iter().map(|s| s.id).collect()
+}
+
+#[test]
+fn sessions_are_upserted_filtered_and_deleted() {
+    let conn = migrated_db();
+    let repo = SqliteRepository::new(&conn);
+    let code = session("2025-01-01T10:00:00.000Z", "Code.exe", 60);
+    repo.upsert_session(&code).unwrap();
+    repo.upsert_session(&session("2025-01-01T11:00:00.000Z", "chrome.exe", 30)).unwrap();
+    // 同じ id なら上書き
+    repo.upsert_session(&Session { duration_seconds: 90, ..code.clone() }).unwrap();
+
+    let all = repo.list_sessions(&SessionsQuery::default()).unwrap();
+    assert_eq!(all.len(), 2);
+    // session_key から列を補完する（識別子は小文字）
+    assert_eq!((all[0].identifier.as_deref(), all[0].user_state.as_deref(), all[0].duration_seconds), (Some("code.exe"), Some("active"), 90));
+
+    let by_identifier = SessionsQuery { identifier: Some("CODE.EXE".to_string()), ..Default::default() };
+    assert_eq!(session_ids(&repo, &by_identifier), vec![code.id.clone()]);
+    let by_range = SessionsQuery { since: Some("2025-01-01T10:30:00Z".to_string()), until: Some("2025-01-02T00:00:00Z".to_string()), ..Default::default() };
+    assert_eq!(session_ids(&repo, &by_range).len(), 1);
+
+    // 手修正済みの行は計測値で上書きしない
+    conn.execute("UPDATE sessions SET is_manual = 1 WHERE id = ?1", [&code.id]).unwrap();
+    repo.upsert_session(&Session { duration_seconds: 5, ..code.clone() }).unwrap();
+    assert_eq!(repo.list_sessions(&by_identifier).unwrap()[0].duration_seconds, 90);
+
+    repo.delete_session(&code.id).unwrap();
+    assert!(session_ids(&repo, &by_identifier).is_empty());
+}
+
+#[test]
+fn browsing_sessions_support_listing_and_heartbeat_merging() {
+    let conn = migrated_db();
+    let repo = SqliteRepository::new(&conn);
+    repo.upsert_browsing_session(&browsing("a", "youtube.com", "2025-01-01T10:00:00.000Z", 1)).unwrap();
+    repo.upsert_browsing_session(&browsing("b", "github.com", "2025-01-01T10:05:00.000Z", 1)).unwrap();
+    repo.upsert_browsing_session(&browsing("c", "youtube.com", "2025-01-01T10:10:00.000Z", 2)).unwrap();
+
+    let ids = |query: &BrowsingSessionsQuery| repo.list_browsing_sessions(query).unwrap().into_iter().map(|s| s.id).collect::<Vec<_>>();
+    // 新しい順
+    assert_eq!(ids(&BrowsingSessionsQuery::default()), vec!["c", "b", "a"]);
+    assert_eq!(ids(&BrowsingSessionsQuery { domain: Some("youtube.com".to_string()), ..Default::default() }), vec!["c", "a"]);
+
+    let tab = |tab_id| repo.list_tab_browsing_sessions(tab_id, "https://youtube.com/", "2025-01-01T00:00:00Z", "2025-01-02T00:00:00Z").unwrap();
+    assert_eq!(tab(1).len(), 1);
+
+    // latest なしなら区間だけ、ありならタイトル・分類も更新する
+    repo.extend_browsing_session("a", "2025-01-01T09:59:00.000Z", 120, None).unwrap();
+    let a = &tab(1)[0];
+    assert_eq!((a.start_time.as_str(), a.duration_seconds, a.title.as_deref()), ("2025-01-01T09:59:00.000Z", 120, Some("page")));
+    repo.upsert_waste_category(&app_category("video.exe", "waste")).unwrap();
+    let video = repo.list_waste_categories().unwrap().into_iter().find(|c| c.identifier == "video.exe").unwrap().id;
+    repo.extend_browsing_session("a", &a.start_time, 150, Some(("next", video))).unwrap();
+    let a = &tab(1)[0];
+    assert_eq!((a.title.as_deref(), a.category_id), (Some("next"), video));
+
+    // 手修正済みの行は結合候補にしない
+    conn.execute("UPDATE browsing_sessions SET is_manual = 1 WHERE id = 'c'", []).unwrap();
+    assert!(tab(2).is_empty());
+
+    repo.delete_browsing_session("a").unwrap();
+    assert_eq!(ids(&BrowsingSessionsQuery::default()), vec!["c", "b"]);
+}
+
+#[test]
+fn waste_categories_and_domains_are_master_data() {
+    let conn = migrated_db();
+    let repo = SqliteRepository::new(&conn);
+    // 登録前に記録された未分類のセッションにも反映する
+    repo.upsert_session(&session("2025-01-01T10:00:00.000Z", "game.exe", 60)).unwrap();
+    assert_eq!(repo.list_sessions(&SessionsQuery::default()).unwrap()[0].category_id, None);
+
+    repo.upsert_waste_category(&app_category("game.exe", "waste")).unwrap();
+    let categories = repo.list_waste_categories().unwrap();
+    let game = categories.iter().find(|c| c.identifier == "game.exe").unwrap();
+    assert_eq!(repo.list_sessions(&SessionsQuery::default()).unwrap()[0].category_id, game.id);
+
+    repo.delete_waste_category(game.id.unwrap()).unwrap();
+    assert!(repo.list_waste_categories().unwrap().iter().all(|c| c.identifier != "game.exe"));
+
+    repo.upsert_domain(&Domain { id: None, domain: "youtube.com".to_string(), category_id: None, is_active: true }).unwrap();
+    repo.upsert_domain(&Domain { id: None, domain: "youtube.com".to_string(), category_id: None, is_active: false }).unwrap();
+    let domains: Vec<Domain> = repo.list_domains().unwrap().into_iter().filter(|d| d.domain == "youtube.com").collect();
+    assert_eq!(domains.len(), 1);
+    assert!(!domains[0].is_active);
+}
+
+#[test]
+fn settings_round_trip_and_follow_the_transaction() {
+    let mut conn = migrated_db();
+    {
+        let repo = SqliteRepository::new(&conn);
+        assert_eq!(repo.get_setting("theme").unwrap(), None);
+        repo.set_setting("theme", "dark").unwrap();
+        repo.set_setting("theme", "light").unwrap();
+        assert_eq!(repo.get_setting("theme").unwrap().as_deref(), Some("light"));
+    }
+
+    // Transaction の上でも同じように使え、ロールバックすれば残らない
+    let tx = conn.transaction().unwrap();
+    SqliteRepository::new(&tx).set_setting("theme", "sepia").unwrap();
+    assert_eq!(SqliteRepository::new(&tx).get_setting("theme").unwrap().as_deref(), Some("sepia"));
+    drop(tx);
+    assert_eq!(SqliteRepository::new(&conn).get_setting("theme").unwrap().as_deref(), Some("light"));
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct Limits {
+    max: i64,
+}
+
+fn positive(limits: &Limits) -> Result<(), String> {
+    if limits.max > 0 { Ok(()) } else { Err("max must be positive".to_string()) }
+}
+
+#[test]
+fn json_settings_fall_back_to_the_default() {
+    let conn = migrated_db();
+    let repo = SqliteRepository::new(&conn);
+    assert_eq!(load_json_setting(&repo, "limits", positive), Limits::default());
+
+    save_json_setting(&repo, "limits", &Limits { max: 3 }).unwrap();
+    assert_eq!(repo.get_setting("limits").unwrap().as_deref(), Some(r#"{"max":3}"#));
+    assert_eq!(load_json_setting(&repo, "limits", positive), Limits { max: 3 });
+
+    // 壊れた JSON や validate に通らない値は既定値
+    repo.set_setting("limits", "{").unwrap();
+    assert_eq!(load_json_setting(&repo, "limits", positive), Limits::default());
+    save_json_setting(&repo, "limits", &Limits { max: -1 }).unwrap();
+    assert_eq!(load_json_setting(&repo, "limits", positive), Limits::default());
+}
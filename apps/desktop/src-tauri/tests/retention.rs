@@ -0,0 +1,122 @@
+use chrono::{Local, TimeZone};
+use rusqlite::Connection;
+use wasteday_lib::aggregate::{self, AggregateQuery, DataSource, Granularity};
+use wasteday_lib::export::{self, ExportFormat, ExportRequest};
+use wasteday_lib::import::{self, ImportRequest};
+use wasteday_lib::retention::{self, RetentionConfig};
+
+mod common;
+use common::{count, fixture_db};
+
+fn daily_totals(conn: &Connection, source: DataSource) -> Vec<(String, i64, i64, i64)> {
+    let query = AggregateQuery {
+        since: "2024-12-30T00:00:00Z".to_string(),
+        until: "2025-01-03T00:00:00Z".to_string(),
+        granularity: Granularity::Day,
+        source,
+        include_idle: None,
+        week_start: None,
+        utc_offset_minutes: None,
+    };
+    aggregate::aggregate(conn, &query)
+        .unwrap()
+        .into_iter()
+        .map(|b| (b.bucket, b.waste_seconds, b.productive_seconds, b.unclassified_seconds))
+        .collect()
+}
+
+fn config() -> RetentionConfig {
+    RetentionConfig { enabled: true, raw_days: 90 }
+}
+
+#[test]
+fn dry_run_reports_without_deleting() {
+    let mut conn = fixture_db();
+    let now = Local.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+    let report = retention::run(&mut conn, &config(), now, true).unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.sessions_pruned, 3);
+    assert_eq!(report.browsing_sessions_pruned, 2);
+    assert_eq!(report.daily_domain_rows, 2);
+    assert_eq!(count(&conn, "sessions"), 3);
+    assert_eq!(count(&conn, "daily_app_summaries"), 0);
+}
+
+#[test]
+fn pruning_keeps_daily_aggregates() {
+    let mut conn = fixture_db();
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state)
+         VALUES('recent', '2025-05-30T09:00:00.000Z', 60, 'category=app;identifier=code.exe;user_state=active', 'app', 'code.exe', 'active')",
+        [],
+    )
+    .unwrap();
+    let app_before = daily_totals(&conn, DataSource::App);
+    let all_before = daily_totals(&conn, DataSource::All);
+    assert!(!app_before.is_empty());
+
+    let now = Local.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+    let report = retention::run(&mut conn, &config(), now, false).unwrap();
+    assert_eq!(report.sessions_pruned, 3);
+    assert_eq!(count(&conn, "sessions"), 1);
+    assert_eq!(count(&conn, "browsing_sessions"), 0);
+
+    assert_eq!(daily_totals(&conn, DataSource::App), app_before);
+    assert_eq!(daily_totals(&conn, DataSource::All), all_before);
+
+    // 2回目は対象がない
+    let again = retention::run(&mut conn, &config(), now, false).unwrap();
+    assert_eq!(again.sessions_pruned + again.browsing_sessions_pruned, 0);
+    assert_eq!(daily_totals(&conn, DataSource::App), app_before);
+}
+
+#[test]
+fn raw_days_is_validated() {
+    let config = RetentionConfig::default();
+    let err = config.apply(retention::RetentionConfigPatch { raw_days: Some(1), ..Default::default() }).unwrap_err();
+    assert!(err.contains("raw_days"));
+}
+
+#[test]
+fn reimporting_rolled_up_rows_does_not_double_count() {
+    let mut conn = fixture_db();
+    let file = std::env::temp_dir().join(format!("wasteday-retention-test-{}.json", std::process::id()));
+    let request = ExportRequest { format: ExportFormat::Json, path: Some(file.to_string_lossy().into_owned()), since: None, until: None, tables: None };
+    export::export(&conn, &request).unwrap();
+
+    let now = Local.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+    let report = retention::run(&mut conn, &config(), now, false).unwrap();
+    assert_eq!(retention::watermark(&conn).unwrap(), Some(report.cutoff.clone()));
+    let rolled_up = daily_totals(&conn, DataSource::All);
+
+    // 集計済みの期間の行は取り込まない
+    let result = import::import(&mut conn, &ImportRequest { path: file.to_string_lossy().into_owned(), format: None }).unwrap();
+    assert_eq!((result.inserted, result.skipped), (0, 5));
+    assert!(result.errors[0].contains("rolled up"), "{:?}", result.errors);
+    retention::run(&mut conn, &config(), now, false).unwrap();
+    assert_eq!(daily_totals(&conn, DataSource::All), rolled_up);
+
+    // 保持期間を延ばしても集計済みの範囲は戻らない
+    let longer = RetentionConfig { raw_days: 365, ..config() };
+    retention::run(&mut conn, &longer, now, false).unwrap();
+    assert_eq!(retention::watermark(&conn).unwrap(), Some(report.cutoff));
+    let _ = std::fs::remove_file(&file);
+}
+
+#[test]
+fn full_vacuum_only_happens_when_enabling_incremental_mode() {
+    let mut conn = fixture_db();
+    let auto_vacuum = |conn: &Connection| conn.query_row("PRAGMA auto_vacuum", [], |row| row.get::<_, i64>(0)).unwrap();
+    assert_eq!(auto_vacuum(&conn), 0);
+
+    // 保持ポリシーの実行ではフル VACUUM をしない
+    let now = Local.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+    let report = retention::run(&mut conn, &config(), now, false).unwrap();
+    assert_eq!(report.vacuumed_pages, 0);
+    assert_eq!(auto_vacuum(&conn), 0);
+
+    // 起動時に一度だけ切り替える
+    assert!(retention::enable_incremental_vacuum(&conn).unwrap());
+    assert_eq!(auto_vacuum(&conn), 2);
+    assert!(!retention::enable_incremental_vacuum(&conn).unwrap());
+}
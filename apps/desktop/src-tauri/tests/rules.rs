@@ -0,0 +1,216 @@
+use rusqlite::{params, Connection};
+use wasteday_lib::aggregate::{self, AggregateQuery, DataSource, Granularity};
+use wasteday_lib::editing::{self, ManualBrowsingEntry, ManualSessionEntry, SessionPatch};
+use wasteday_lib::ingest_server::handlers::{self, BrowserData};
+use wasteday_lib::rules::{self, NewRule, RulePatch, RuleSet, RuleTarget};
+
+mod common;
+use common::fixture_db;
+
+fn add_category(conn: &Connection, identifier: &str, label: &str) -> i64 {
+    conn.execute(
+        "INSERT INTO waste_categories(type, identifier, label) VALUES('domain', ?1, ?2)",
+        params![identifier, label],
+    )
+    .unwrap();
+    conn.last_insert_rowid()
+}
+
+fn rule(target: RuleTarget, pattern: &str, title_regex: Option<&str>, category_id: i64, priority: i64) -> NewRule {
+    NewRule {
+        target,
+        pattern: pattern.to_string(),
+        title_regex: title_regex.map(str::to_string),
+        category_id,
+        priority,
+        is_active: None,
+    }
+}
+
+const CHROME_ID: &str = "2025-01-01T09:30:00.000Z-category=app;identifier=chrome.exe;user_state=active";
+
+fn session_category_of(conn: &Connection, id: &str) -> Option<i64> {
+    conn.query_row("SELECT category_id FROM sessions WHERE id = ?1", params![id], |row| row.get(0)).unwrap()
+}
+
+fn app_waste_seconds(conn: &Connection) -> i64 {
+    let query = AggregateQuery {
+        since: "2025-01-01T00:00:00Z".to_string(),
+        until: "2025-01-02T00:00:00Z".to_string(),
+        granularity: Granularity::Day,
+        source: DataSource::App,
+        include_idle: Some(false),
+        week_start: None,
+        utc_offset_minutes: Some(0),
+    };
+    aggregate::aggregate(conn, &query).unwrap().iter().map(|b| b.waste_seconds).sum()
+}
+
+fn category_of(conn: &Connection, id: &str) -> Option<i64> {
+    conn.query_row("SELECT category_id FROM browsing_sessions WHERE id = ?1", params![id], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn rules_match_wildcards_paths_and_titles_by_priority() {
+    let conn = fixture_db();
+    let shorts = add_category(&conn, "youtube shorts", "waste");
+    let docs = add_category(&conn, "google docs", "productive");
+    let streaming = add_category(&conn, "streaming", "waste");
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "*.google.com", None, docs, 0)).unwrap();
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "youtube.com/shorts", None, shorts, 10)).unwrap();
+    rules::create_rule(&conn, rule(RuleTarget::App, "chrome.exe", Some("netflix"), streaming, 5)).unwrap();
+    rules::create_rule(&conn, rule(RuleTarget::App, "*.exe", None, docs, 0)).unwrap();
+
+    let set = RuleSet::load(&conn).unwrap();
+    assert_eq!(set.classify_browsing("docs.google.com", "https://docs.google.com/d/1", None), Some(docs));
+    assert_eq!(set.classify_browsing("google.com", "https://google.com/", None), Some(docs));
+    assert_eq!(set.classify_browsing("notgoogle.com", "https://notgoogle.com/", None), None);
+    assert_eq!(set.classify_browsing("youtube.com", "https://www.youtube.com/shorts/abc?x=1", None), Some(shorts));
+    assert_eq!(set.classify_browsing("youtube.com", "https://www.youtube.com/watch?v=x", None), None);
+    assert_eq!(set.classify_app("Chrome.exe", Some("Stranger Things | Netflix")), Some(streaming));
+    assert_eq!(set.classify_app("chrome.exe", Some("GitHub")), Some(docs));
+
+    // 優先度を下げると汎用ルールが先に一致する
+    let netflix = rules::list_rules(&conn).unwrap().into_iter().find(|r| r.target == RuleTarget::App && r.title_regex.is_some()).unwrap();
+    rules::update_rule(&conn, netflix.id, RulePatch { priority: Some(-1), ..Default::default() }).unwrap();
+    let set = RuleSet::load(&conn).unwrap();
+    assert_eq!(set.classify_app("chrome.exe", Some("Netflix")), Some(docs));
+}
+
+#[test]
+fn ingest_and_reclassify_apply_rules_before_domains() {
+    let mut conn = fixture_db();
+    let shorts = add_category(&conn, "youtube shorts", "waste");
+    let data = BrowserData {
+        domain: "youtube.com".to_string(),
+        url: "https://www.youtube.com/shorts/abc".to_string(),
+        title: "Shorts".to_string(),
+        timestamp: "2025-01-01T10:00:00.000Z".to_string(),
+        duration: Some(60),
+        tab_id: Some(20),
+    };
+    let id = handlers::upsert_browser_data(&conn, &RuleSet::load(&conn).unwrap(), &data).unwrap();
+    // ルールがなければ domains の完全一致
+    assert_eq!(category_of(&conn, &id), Some(3));
+
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "youtube.com/shorts", None, shorts, 0)).unwrap();
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "*.github.com", None, shorts, 0)).unwrap();
+    let changed = rules::reclassify_browsing_sessions(&mut conn, None, None).unwrap();
+    assert_eq!(changed, 2);
+    assert_eq!(category_of(&conn, &id), Some(shorts));
+    assert_eq!(category_of(&conn, "2025-01-01T09:30:00.000Z-youtube.com-12"), Some(3));
+    assert_eq!(category_of(&conn, "2025-01-01T09:35:00.000Z-github.com-13"), Some(shorts));
+
+    // 期間外の行は変えない
+    rules::delete_rule(&conn, rules::list_rules(&conn).unwrap()[0].id).unwrap();
+    assert_eq!(rules::reclassify_browsing_sessions(&mut conn, Some("2025-01-02T00:00:00Z"), None).unwrap(), 0);
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let conn = fixture_db();
+    assert!(rules::create_rule(&conn, rule(RuleTarget::App, "chrome.exe", Some("(unclosed"), 1, 0)).is_err());
+    assert!(rules::create_rule(&conn, rule(RuleTarget::Domain, "  ", None, 1, 0)).is_err());
+    assert!(rules::create_rule(&conn, rule(RuleTarget::Domain, "/shorts", None, 1, 0)).is_err());
+    assert!(rules::create_rule(&conn, rule(RuleTarget::Domain, "example.com", None, 999, 0)).is_err());
+    assert!(rules::update_rule(&conn, 42, RulePatch::default()).is_err());
+    assert!(rules::list_rules(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn sessions_store_classification_and_reclassify_by_range() {
+    let mut conn = fixture_db();
+    // 既存行は識別子の完全一致でバックフィル済み（chrome.exe = 1）
+    assert_eq!(session_category_of(&conn, CHROME_ID), Some(1));
+    assert_eq!(app_waste_seconds(&conn), 600);
+
+    conn.execute("INSERT INTO waste_categories(type, identifier, label) VALUES('app', 'chrome docs', 'productive')", []).unwrap();
+    let docs = conn.last_insert_rowid();
+    rules::create_rule(&conn, rule(RuleTarget::App, "chrome.exe", Some("google docs"), docs, 0)).unwrap();
+    // タイトルがなければルールは一致しない
+    assert_eq!(rules::reclassify_sessions(&mut conn, None, None).unwrap(), 0);
+
+    conn.execute("UPDATE sessions SET window_title = 'Report - Google Docs' WHERE id = ?1", params![CHROME_ID]).unwrap();
+    assert_eq!(rules::reclassify_sessions(&mut conn, Some("2025-01-02T00:00:00Z"), None).unwrap(), 0);
+    assert_eq!(rules::reclassify_sessions(&mut conn, Some("2025-01-01T00:00:00Z"), Some("2025-01-02T00:00:00Z")).unwrap(), 1);
+    assert_eq!(session_category_of(&conn, CHROME_ID), Some(docs));
+    // 集計は保存された分類を使う
+    assert_eq!(app_waste_seconds(&conn), 0);
+
+    // 手入力も保存時に分類される
+    let manual = editing::add_manual_session(
+        &conn,
+        &RuleSet::load(&conn).unwrap(),
+        ManualSessionEntry {
+            start_time: "2025-01-01T12:00:00Z".to_string(),
+            end_time: None,
+            duration_seconds: Some(60),
+            identifier: "chrome.exe".to_string(),
+            category: None,
+            user_state: None,
+            window_title: Some("Google Docs".to_string()),
+            category_id: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(manual.category_id, Some(docs));
+
+    // 後から登録したアプリは未分類のセッションに反映
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, category, identifier, user_state)
+         VALUES('s1', '2025-01-01T13:00:00.000Z', 60, 'category=app;identifier=slack.exe;user_state=active', 'app', 'slack.exe', 'active')",
+        [],
+    )
+    .unwrap();
+    conn.execute("INSERT INTO waste_categories(type, identifier, label) VALUES('app', 'slack.exe', 'waste')", []).unwrap();
+    assert_eq!(rules::fill_unclassified_sessions(&conn, "slack.exe").unwrap(), 1);
+    assert_eq!(session_category_of(&conn, "s1"), Some(conn.last_insert_rowid()));
+}
+
+#[test]
+fn manually_categorized_rows_survive_reclassify() {
+    let mut conn = fixture_db();
+    // 編集で選んだ分類は、ルールを変えて分類し直しても残す
+    let patch = SessionPatch { category_id: Some(2), ..Default::default() };
+    editing::update_session(&conn, &RuleSet::load(&conn).unwrap(), CHROME_ID, patch).unwrap();
+    let entry = ManualBrowsingEntry {
+        start_time: "2025-01-01T12:00:00Z".to_string(),
+        end_time: None,
+        duration_seconds: Some(60),
+        url: "https://notes.example/today".to_string(),
+        domain: None,
+        title: Some("Today".to_string()),
+        category_id: Some(2),
+    };
+    let browsing = editing::add_manual_browsing_session(&conn, &RuleSet::load(&conn).unwrap(), entry).unwrap();
+
+    let notes = add_category(&conn, "notes", "waste");
+    rules::create_rule(&conn, rule(RuleTarget::App, "chrome.exe", None, notes, 0)).unwrap();
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "notes.example", None, notes, 0)).unwrap();
+    // 計測したままの chrome.exe の行だけが変わる
+    assert_eq!(rules::reclassify_sessions(&mut conn, None, None).unwrap(), 1);
+    assert_eq!(rules::reclassify_browsing_sessions(&mut conn, None, None).unwrap(), 0);
+    assert_eq!(session_category_of(&conn, CHROME_ID), Some(2));
+    assert_eq!(category_of(&conn, &browsing), Some(2));
+}
+
+#[test]
+fn rule_cache_reloads_only_after_changes() {
+    let conn = fixture_db();
+    let cache = rules::RuleCache::default();
+    let first = cache.get(&conn).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&first, &cache.get(&conn).unwrap()));
+    // 記録の書き込みでは読み直さない
+    conn.execute("UPDATE sessions SET duration_seconds = duration_seconds + 1", []).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&first, &cache.get(&conn).unwrap()));
+
+    let shorts = add_category(&conn, "youtube shorts", "waste");
+    rules::create_rule(&conn, rule(RuleTarget::Domain, "youtube.com/shorts", None, shorts, 0)).unwrap();
+    let second = cache.get(&conn).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&first, &second));
+    assert_eq!(second.classify_browsing("youtube.com", "https://youtube.com/shorts/1", None), Some(shorts));
+
+    // domains の変更も拾う（タイトルからのサイト照合に使う）
+    conn.execute("INSERT INTO domains(domain, category_id, is_active) VALUES('twitch.tv', ?1, 1)", params![shorts]).unwrap();
+    assert!(!std::sync::Arc::ptr_eq(&second, &cache.get(&conn).unwrap()));
+}
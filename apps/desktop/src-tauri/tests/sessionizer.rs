@@ -0,0 +1,144 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use wasteday_lib::sampler::{Sample, SamplerConfig, SessionEvent, SessionEventType, Sessionizer, UserState};
+
+use SessionEventType::{Ended, Started, Updated};
+
+// 既定値と同じ（5秒間隔・アイドルギャップ20秒・猶予5秒）
+fn config() -> SamplerConfig {
+    SamplerConfig {
+        sampling_interval_ms: 5000,
+        idle_gap_threshold_seconds: 20,
+        session_switch_grace_period_seconds: 5,
+        idle_threshold_seconds: 60,
+    }
+}
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap()
+}
+
+// (t0 からの秒, 識別子, 状態, サイト)
+type Input = (i64, &'static str, UserState, Option<&'static str>);
+// (種類, 識別子, 開始秒, 終了秒, 秒数)
+type Expected = (SessionEventType, &'static str, i64, i64, i64);
+
+fn sample(&(sec, identifier, user_state, site): &Input) -> Sample {
+    Sample {
+        timestamp: t0() + Duration::seconds(sec),
+        category: "app".to_string(),
+        identifier: identifier.to_string(),
+        window_title: Some("title".to_string()),
+        site: site.map(str::to_string),
+        user_state,
+    }
+}
+
+fn offset(timestamp: &str) -> i64 {
+    (DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc) - t0()).num_seconds()
+}
+
+fn summary(event: &SessionEvent) -> (SessionEventType, String, i64, i64, i64) {
+    let identifier = event.session_key.split(';').find_map(|p| p.strip_prefix("identifier=")).unwrap().to_string();
+    (event.kind, identifier, offset(&event.start_time), offset(&event.end_time), event.duration_seconds)
+}
+
+// 最後に flush_all まで流したイベント列
+fn run(inputs: &[Input]) -> Vec<SessionEvent> {
+    let mut sessionizer = Sessionizer::new();
+    let mut events: Vec<SessionEvent> = inputs.iter().flat_map(|input| sessionizer.process_sample(&sample(input), &config())).collect();
+    events.extend(sessionizer.flush_all());
+    events
+}
+
+#[test]
+fn samples_are_grouped_into_session_events() {
+    use UserState::{Active, Idle};
+    let cases: Vec<(&str, Vec<Input>, Vec<Expected>)> = vec![
+        (
+            "同じアプリが続けば更新",
+            vec![(0, "a.exe", Active, None), (5, "a.exe", Active, None), (10, "a.exe", Active, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Updated, "a.exe", 0, 5, 5), (Updated, "a.exe", 0, 10, 10), (Ended, "a.exe", 0, 10, 10)],
+        ),
+        (
+            "アイドルギャップを超えたら最終サンプル時刻で閉じる",
+            vec![(0, "a.exe", Active, None), (5, "a.exe", Active, None), (40, "a.exe", Active, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Updated, "a.exe", 0, 5, 5), (Ended, "a.exe", 0, 5, 5), (Started, "a.exe", 40, 40, 0), (Ended, "a.exe", 40, 40, 0)],
+        ),
+        (
+            "ギャップがちょうど閾値なら続ける",
+            vec![(0, "a.exe", Active, None), (20, "a.exe", Active, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Updated, "a.exe", 0, 20, 20), (Ended, "a.exe", 0, 20, 20)],
+        ),
+        (
+            "アクティブ→アイドルは別セッション",
+            vec![(0, "a.exe", Active, None), (5, "a.exe", Active, None), (10, "a.exe", Idle, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Updated, "a.exe", 0, 5, 5), (Ended, "a.exe", 0, 10, 10), (Started, "a.exe", 10, 10, 0), (Ended, "a.exe", 10, 10, 0)],
+        ),
+        (
+            "切り替えたら前のアプリをその時刻で閉じる",
+            vec![(0, "a.exe", Active, None), (5, "a.exe", Active, None), (10, "b.exe", Active, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Updated, "a.exe", 0, 5, 5), (Ended, "a.exe", 0, 10, 10), (Started, "b.exe", 10, 10, 0), (Ended, "b.exe", 10, 10, 0)],
+        ),
+        (
+            "猶予期間内の短い切り替えは閉じずに残す",
+            vec![(0, "a.exe", Active, None), (3, "b.exe", Active, None), (6, "a.exe", Active, None)],
+            vec![(Started, "a.exe", 0, 0, 0), (Started, "b.exe", 3, 3, 0), (Updated, "a.exe", 0, 6, 6), (Ended, "a.exe", 0, 6, 6), (Ended, "b.exe", 3, 3, 0)],
+        ),
+        (
+            "識別子の大文字小文字は区別しない",
+            vec![(0, "Code.exe", Active, None), (5, "code.exe", Active, None)],
+            vec![(Started, "code.exe", 0, 0, 0), (Updated, "code.exe", 0, 5, 5), (Ended, "code.exe", 0, 5, 5)],
+        ),
+        (
+            "ブラウザでサイトが変わったら分ける",
+            vec![(0, "chrome.exe", Active, Some("youtube.com")), (5, "chrome.exe", Active, Some("youtube.com")), (10, "chrome.exe", Active, Some("github.com")), (15, "chrome.exe", Active, None)],
+            vec![
+                (Started, "chrome.exe", 0, 0, 0),
+                (Updated, "chrome.exe", 0, 5, 5),
+                (Ended, "chrome.exe", 0, 10, 10),
+                (Started, "chrome.exe", 10, 10, 0),
+                (Ended, "chrome.exe", 10, 15, 5),
+                (Started, "chrome.exe", 15, 15, 0),
+                (Ended, "chrome.exe", 15, 15, 0),
+            ],
+        ),
+    ];
+
+    for (name, inputs, expected) in cases {
+        let actual: Vec<_> = run(&inputs).iter().map(summary).collect();
+        let expected: Vec<_> = expected.into_iter().map(|(kind, id, start, end, duration)| (kind, id.to_string(), start, end, duration)).collect();
+        assert_eq!(actual, expected, "{}", name);
+    }
+}
+
+#[test]
+fn events_carry_idle_state_and_site_url() {
+    let events = run(&[(0, "chrome.exe", UserState::Active, Some("youtube.com")), (5, "chrome.exe", UserState::Active, Some("github.com")), (10, "chrome.exe", UserState::Idle, None)]);
+    let urls: Vec<_> = events.iter().map(|e| (e.kind, e.url.as_deref(), e.is_idle)).collect();
+    assert_eq!(
+        urls,
+        vec![
+            (Started, Some("https://youtube.com/"), false),
+            (Ended, Some("https://youtube.com/"), false),
+            (Started, Some("https://github.com/"), false),
+            (Ended, Some("https://github.com/"), false),
+            (Started, None, true),
+            (Ended, None, true),
+        ]
+    );
+    assert!(events.iter().all(|e| e.session_key.starts_with("category=app;identifier=chrome.exe;")));
+    // JS の toISOString() と同じ形式
+    assert_eq!(events[0].start_time, "2025-01-01T10:00:00.000Z");
+}
+
+#[test]
+fn empty_titles_do_not_overwrite_the_last_one() {
+    let mut sessionizer = Sessionizer::new();
+    sessionizer.process_sample(&sample(&(0, "a.exe", UserState::Active, None)), &config());
+    let untitled = Sample { window_title: Some(String::new()), ..sample(&(5, "a.exe", UserState::Active, None)) };
+    let updated = sessionizer.process_sample(&untitled, &config());
+    assert_eq!(updated[0].window_title.as_deref(), Some("title"));
+    assert_eq!(sessionizer.open_session_keys(), vec!["category=app;identifier=a.exe;user_state=active"]);
+    assert_eq!(sessionizer.flush_all().len(), 1);
+    assert!(sessionizer.open_session_keys().is_empty());
+}
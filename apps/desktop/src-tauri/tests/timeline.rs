@@ -0,0 +1,158 @@
+use rusqlite::{params, Connection};
+use wasteday_lib::aggregate::{self, AggregateQuery, CategoryTotalsQuery, DataSource, Granularity};
+use wasteday_lib::categories;
+use wasteday_lib::timeline::{self, TimeClass, TimelineConfig, TimelineConfigPatch, TimelineQuery, TimelineSource, UNKNOWN_TAB_LABEL};
+
+mod common;
+use common::fixture_db;
+
+// マイグレーションで作られる既定カテゴリ
+const WASTE: i64 = 2;
+
+fn query() -> TimelineQuery {
+    TimelineQuery { since: "2025-01-01T09:00:00Z".to_string(), until: "2025-01-01T12:00:00Z".to_string(), include_idle: None }
+}
+
+fn insert_browsing(conn: &Connection, id: &str, domain: &str, start: &str, seconds: i64, tab_id: i64) {
+    conn.execute(
+        "INSERT INTO browsing_sessions(id, domain, url, title, start_time, duration_seconds, tab_id) VALUES(?1, ?2, 'https://' || ?2 || '/', ?2, ?3, ?4, ?5)",
+        params![id, domain, start, seconds, tab_id],
+    )
+    .unwrap();
+}
+
+#[test]
+fn browser_time_is_replaced_by_domains_and_unknown_tab() {
+    let conn = fixture_db();
+    let t = timeline::unified_timeline(&conn, &query()).unwrap();
+
+    let labels: Vec<(&str, i64, TimeClass)> = t.entries.iter().map(|e| (e.label.as_str(), e.duration_seconds, e.class)).collect();
+    assert_eq!(
+        labels,
+        vec![
+            ("code.exe", 1800, TimeClass::Productive),
+            ("youtube.com", 300, TimeClass::Waste),
+            ("github.com", 300, TimeClass::Unclassified),
+            // 閲覧記録のない chrome.exe の時間はブラウザのアプリの分類のまま
+            (UNKNOWN_TAB_LABEL, 120, TimeClass::Waste),
+        ]
+    );
+    assert!(t.entries[1..].iter().all(|e| e.source == TimelineSource::Browser && e.identifier.as_deref() == Some("chrome.exe")));
+    assert_eq!(t.entries[3].user_state.as_deref(), Some("idle"));
+
+    assert_eq!((t.totals.waste_seconds, t.totals.productive_seconds, t.totals.unclassified_seconds), (420, 1800, 300));
+    assert_eq!((t.totals.browser_unknown_seconds, t.totals.total_seconds), (120, 2520));
+
+    let active = TimelineQuery { include_idle: Some(false), ..query() };
+    assert_eq!(timeline::unified_timeline(&conn, &active).unwrap().totals.total_seconds, 2400);
+}
+
+#[test]
+fn browsing_outside_the_browser_app_is_not_double_counted() {
+    let conn = fixture_db();
+    // code.exe が前面だった時間の閲覧記録は数えない
+    insert_browsing(&conn, "b1", "news.example", "2025-01-01T09:10:00.000Z", 60, 1);
+    // アプリの記録がない時間帯はそのまま使い、同時に記録された別タブは重ならないように切り詰める
+    insert_browsing(&conn, "b2", "docs.example", "2025-01-01T11:00:00.000Z", 120, 1);
+    insert_browsing(&conn, "b3", "blog.example", "2025-01-01T11:01:00.000Z", 120, 2);
+
+    let t = timeline::unified_timeline(&conn, &query()).unwrap();
+    assert!(t.entries.iter().all(|e| e.domain.as_deref() != Some("news.example")));
+    let tail: Vec<(&str, &str, i64, Option<&str>)> = t.entries[4..]
+        .iter()
+        .map(|e| (e.label.as_str(), e.start_time.as_str(), e.duration_seconds, e.identifier.as_deref()))
+        .collect();
+    assert_eq!(
+        tail,
+        vec![
+            ("docs.example", "2025-01-01T11:00:00.000Z", 120, None),
+            ("blog.example", "2025-01-01T11:02:00.000Z", 60, None),
+        ]
+    );
+    assert_eq!(t.totals.total_seconds, 2520 + 180);
+}
+
+#[test]
+fn overlapping_app_sessions_are_counted_once() {
+    let conn = fixture_db();
+    // 09:30 からの chrome.exe と重なる行（別のセッションキーで同時に記録された分）
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key, identifier, user_state) \
+         VALUES('dup', '2025-01-01T09:33:00.000Z', 480, 'category=app;identifier=chrome.exe;user_state=active', 'chrome.exe', 'active')",
+        [],
+    )
+    .unwrap();
+    // 切り詰めた2つの chrome.exe の区間にまたがり、アプリの記録がない時間帯まで続く閲覧記録
+    insert_browsing(&conn, "b1", "docs.example", "2025-01-01T09:40:00.000Z", 300, 1);
+
+    let t = timeline::unified_timeline(&conn, &query()).unwrap();
+    let labels: Vec<(&str, &str, i64)> = t.entries.iter().map(|e| (e.label.as_str(), e.start_time.as_str(), e.duration_seconds)).collect();
+    assert_eq!(
+        labels,
+        vec![
+            ("code.exe", "2025-01-01T09:00:00.000Z", 1800),
+            ("youtube.com", "2025-01-01T09:30:00.000Z", 300),
+            ("github.com", "2025-01-01T09:35:00.000Z", 300),
+            ("docs.example", "2025-01-01T09:40:00.000Z", 60),
+            ("docs.example", "2025-01-01T09:41:00.000Z", 60),
+            ("docs.example", "2025-01-01T09:42:00.000Z", 180),
+        ]
+    );
+    let states: Vec<Option<&str>> = t.entries[3..].iter().map(|e| e.user_state.as_deref()).collect();
+    assert_eq!(states, vec![Some("active"), Some("idle"), None]);
+    assert_eq!(t.totals.total_seconds, 2520 + 180);
+}
+
+#[test]
+fn unified_source_feeds_aggregates_without_double_counting() {
+    let conn = fixture_db();
+    let buckets = aggregate::aggregate(
+        &conn,
+        &AggregateQuery {
+            since: "2025-01-01T00:00:00Z".to_string(),
+            until: "2025-01-02T00:00:00Z".to_string(),
+            granularity: Granularity::Day,
+            source: DataSource::Unified,
+            include_idle: None,
+            week_start: None,
+            utc_offset_minutes: Some(0),
+        },
+    )
+    .unwrap();
+    assert_eq!(buckets.len(), 1);
+    assert_eq!((buckets[0].waste_seconds, buckets[0].productive_seconds, buckets[0].unclassified_seconds), (420, 1800, 300));
+
+    let rollup_for = |source| {
+        let query = CategoryTotalsQuery {
+            since: "2025-01-01T00:00:00Z".to_string(),
+            until: "2025-01-02T00:00:00Z".to_string(),
+            source,
+            include_idle: None,
+            utc_offset_minutes: Some(0),
+        };
+        categories::rollup(&conn, &query).unwrap().into_iter().find(|r| r.category_id == Some(WASTE)).unwrap().total_seconds
+    };
+    // all は chrome.exe（720秒）と youtube.com（300秒）を両方数える
+    assert_eq!(rollup_for(DataSource::All), 1020);
+    assert_eq!(rollup_for(DataSource::Unified), 420);
+}
+
+#[test]
+fn browser_identifiers_are_configurable() {
+    let conn = fixture_db();
+    let config = TimelineConfig::default()
+        .apply(TimelineConfigPatch { browser_identifiers: Some(vec![" Firefox ".to_string(), "firefox".to_string()]) })
+        .unwrap();
+    assert_eq!(config.browser_identifiers, vec!["firefox".to_string()]);
+    config.save(&conn).unwrap();
+    assert_eq!(TimelineConfig::load(&conn), config);
+
+    // chrome.exe がブラウザでなくなると、その時間の閲覧記録は数えない
+    let t = timeline::unified_timeline(&conn, &query()).unwrap();
+    assert!(t.entries.iter().all(|e| e.source == TimelineSource::App));
+    assert_eq!(t.totals.total_seconds, 2520);
+
+    assert!(TimelineConfig::default().apply(TimelineConfigPatch { browser_identifiers: Some(vec![" ".to_string()]) }).is_err());
+    let bad = TimelineQuery { until: "2025-01-01T08:00:00Z".to_string(), ..query() };
+    assert!(timeline::unified_timeline(&conn, &bad).is_err());
+}
@@ -0,0 +1,123 @@
+use chrono::{Duration, Utc};
+use wasteday_lib::trash::{self, BulkDeleteRequest, TrashConfig};
+
+mod common;
+use common::{count, fixture_db};
+
+#[test]
+fn range_delete_by_identifier_moves_rows_to_trash() {
+    let mut conn = fixture_db();
+    let request = BulkDeleteRequest {
+        since: Some("2025-01-01T09:00:00Z".to_string()),
+        until: Some("2025-01-01T10:00:00Z".to_string()),
+        identifier: Some("Chrome.exe".to_string()),
+        description: Some("meeting video".to_string()),
+        ..Default::default()
+    };
+
+    let preview = trash::bulk_delete(&mut conn, &BulkDeleteRequest { dry_run: true, ..request.clone() }).unwrap();
+    assert_eq!((preview.sessions, preview.browsing_sessions, preview.batch_id), (2, 0, None));
+    assert_eq!(count(&conn, "sessions"), 3);
+
+    let result = trash::bulk_delete(&mut conn, &request).unwrap();
+    assert_eq!(result.sessions, 2);
+    assert_eq!(count(&conn, "sessions"), 1);
+    assert_eq!(count(&conn, "browsing_sessions"), 2);
+
+    let batches = trash::list_batches(&conn, &TrashConfig::default()).unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].description.as_deref(), Some("meeting video"));
+    assert_eq!(batches[0].sessions, 2);
+}
+
+#[test]
+fn restore_puts_rows_back_and_reports_conflicts() {
+    let mut conn = fixture_db();
+    let request = BulkDeleteRequest { domain: Some("youtube.com".to_string()), ..Default::default() };
+    let deleted = trash::bulk_delete(&mut conn, &request).unwrap();
+    assert_eq!((deleted.sessions, deleted.browsing_sessions), (0, 1));
+    let time_only = BulkDeleteRequest { until: Some("2025-01-01T09:31:00Z".to_string()), ..Default::default() };
+    let second = trash::bulk_delete(&mut conn, &time_only).unwrap();
+    assert_eq!((second.sessions, second.browsing_sessions), (2, 0));
+
+    // 削除後に同じ id が記録し直された場合は上書きしない
+    conn.execute(
+        "INSERT INTO sessions(id, start_time, duration_seconds, session_key) \
+         SELECT row_id, '2025-01-01T09:00:00.000Z', 5, 'category=app;identifier=code.exe;user_state=active' \
+         FROM trash_rows WHERE batch_id = ?1 AND row_id LIKE '%code.exe%'",
+        [second.batch_id.unwrap()],
+    )
+    .unwrap();
+    let restored = trash::restore_batch(&mut conn, second.batch_id.unwrap()).unwrap();
+    assert_eq!((restored.restored, restored.conflicts), (1, 1));
+    assert_eq!(count(&conn, "sessions"), 3);
+
+    let restored = trash::restore_batch(&mut conn, deleted.batch_id.unwrap()).unwrap();
+    assert_eq!(restored.restored, 1);
+    let (title, category_id): (String, i64) = conn
+        .query_row("SELECT title, category_id FROM browsing_sessions WHERE domain = 'youtube.com'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!((title.as_str(), category_id), ("Video", 3));
+    assert_eq!(count(&conn, "trash_batches"), 0);
+    assert_eq!(count(&conn, "trash_rows"), 0);
+}
+
+#[test]
+fn rows_whose_category_was_deleted_are_reclassified_on_restore() {
+    let mut conn = fixture_db();
+    let request = BulkDeleteRequest { identifier: Some("chrome.exe".to_string()), ..Default::default() };
+    let deleted = trash::bulk_delete(&mut conn, &request).unwrap();
+    assert_eq!(deleted.sessions, 2);
+    let youtube = BulkDeleteRequest { domain: Some("youtube.com".to_string()), ..Default::default() };
+    let deleted_browsing = trash::bulk_delete(&mut conn, &youtube).unwrap();
+
+    // 退避中に分類を消す（chrome.exe は分類なし、youtube.com は domains 経由で別の分類へ）
+    conn.execute("DELETE FROM waste_categories WHERE id = 1", []).unwrap();
+    conn.execute("INSERT INTO waste_categories(id, type, identifier, label) VALUES(4, 'domain', 'video', 'waste')", []).unwrap();
+    conn.execute("UPDATE domains SET category_id = 4 WHERE domain = 'youtube.com'", []).unwrap();
+    conn.execute("DELETE FROM waste_categories WHERE id = 3", []).unwrap();
+
+    let restored = trash::restore_batch(&mut conn, deleted.batch_id.unwrap()).unwrap();
+    assert_eq!((restored.restored, restored.conflicts), (2, 0));
+    let unclassified: i64 =
+        conn.query_row("SELECT COUNT(*) FROM sessions WHERE identifier = 'chrome.exe' AND category_id IS NULL", [], |row| row.get(0)).unwrap();
+    assert_eq!(unclassified, 2);
+
+    assert_eq!(trash::restore_batch(&mut conn, deleted_browsing.batch_id.unwrap()).unwrap().restored, 1);
+    let category_id: i64 = conn.query_row("SELECT category_id FROM browsing_sessions WHERE domain = 'youtube.com'", [], |row| row.get(0)).unwrap();
+    assert_eq!(category_id, 4);
+}
+
+#[test]
+fn domain_filter_matches_only_the_domain_and_its_subdomains() {
+    let mut conn = fixture_db();
+    let insert = |id: &str, domain: &str| {
+        conn.execute(
+            "INSERT INTO browsing_sessions(id, domain, url, start_time, duration_seconds) VALUES(?1, ?2, 'https://' || ?2 || '/', '2025-01-02T10:00:00.000Z', 10)",
+            [id, domain],
+        )
+        .unwrap();
+    };
+    insert("sub", "m.my_site.com");
+    insert("wildcard", "x.myXsite.com");
+    insert("suffix", "notmy_site.com");
+    insert("case", "m.MY_SITE.COM");
+
+    let request = BulkDeleteRequest { domain: Some("My_Site.com".to_string()), dry_run: true, ..Default::default() };
+    assert_eq!(trash::bulk_delete(&mut conn, &request).unwrap().browsing_sessions, 1);
+}
+
+#[test]
+fn unfiltered_delete_is_rejected_and_expired_batches_are_purged() {
+    let mut conn = fixture_db();
+    assert!(trash::bulk_delete(&mut conn, &BulkDeleteRequest::default()).is_err());
+
+    let request = BulkDeleteRequest { ids: Some(vec!["2025-01-01T09:35:00.000Z-github.com-13".to_string()]), ..Default::default() };
+    trash::bulk_delete(&mut conn, &request).unwrap();
+    let config = TrashConfig { retention_days: 30 };
+    assert_eq!(trash::purge(&conn, &config, Utc::now(), None).unwrap(), 0);
+    assert_eq!(trash::purge(&conn, &config, Utc::now() + Duration::days(31), None).unwrap(), 1);
+    assert_eq!(count(&conn, "trash_rows"), 0);
+}
@@ -0,0 +1,173 @@
+import React, { useCallback, useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { errorMessage } from '../utils/errors';
+
+interface PairingCode {
+  code: string;
+  expires_at: string;
+}
+
+interface IngestServerStatus {
+  state: 'starting' | 'running' | 'failed';
+  bind_address: string;
+  requested_port: number;
+  port: number | null;
+  error: string | null;
+}
+
+interface IngestClient {
+  id: number;
+  name: string;
+  origin: string | null;
+  created_at: string;
+  last_used_at: string | null;
+}
+
+// ブラウザ拡張のペアリング: コードを発行して拡張機能のポップアップに入力してもらう
+export const ExtensionPairing: React.FC = () => {
+  const [pairing, setPairing] = useState<PairingCode | null>(null);
+  const [clients, setClients] = useState<IngestClient[]>([]);
+  const [error, setError] = useState<string | null>(null);
+  const [server, setServer] = useState<IngestServerStatus | null>(null);
+  const [portInput, setPortInput] = useState('');
+
+  // 取り込みサーバの状態（起動/再起動のたびに ingest:server_status が届く）
+  useEffect(() => {
+    invoke<IngestServerStatus>('ingest_get_server_status')
+      .then((s) => {
+        setServer(s);
+        setPortInput(String(s.requested_port));
+      })
+      .catch((e) => setError(errorMessage(e)));
+    const unlisten = listen<IngestServerStatus>('ingest:server_status', (event) => setServer(event.payload));
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const savePort = async () => {
+    const port = Number(portInput);
+    if (!Number.isInteger(port)) return;
+    try {
+      setError(null);
+      setServer(await invoke<IngestServerStatus>('ingest_update_server_config', { config: { port } }));
+    } catch (e) {
+      setError(errorMessage(e));
+    }
+  };
+
+  const loadClients = useCallback(async () => {
+    try {
+      setClients(await invoke<IngestClient[]>('ingest_list_clients'));
+    } catch (e) {
+      setError(errorMessage(e));
+    }
+  }, []);
+
+  useEffect(() => {
+    loadClients();
+    // ペアリング完了を拾うため、コード表示中は一覧を更新
+    if (!pairing) return;
+    const timer = setInterval(loadClients, 3000);
+    return () => clearInterval(timer);
+  }, [pairing, loadClients]);
+
+  const startPairing = async () => {
+    try {
+      setError(null);
+      setPairing(await invoke<PairingCode>('ingest_start_pairing'));
+    } catch (e) {
+      setError(errorMessage(e));
+    }
+  };
+
+  const revoke = async (id: number) => {
+    try {
+      await invoke('ingest_revoke_client', { id });
+      await loadClients();
+    } catch (e) {
+      setError(errorMessage(e));
+    }
+  };
+
+  return (
+    <div className="p-4 border rounded-lg bg-gray-50 dark:bg-gray-800">
+      <h3 className="text-lg font-semibold mb-4">ブラウザ拡張機能</h3>
+
+      <div className="mb-4">
+        <h4 className="font-medium text-sm text-gray-600 dark:text-gray-400 mb-2">取り込みサーバ</h4>
+        <p className={`text-sm ${server?.state === 'running' ? 'text-green-600' : 'text-red-600'}`}>
+          {server?.state === 'running'
+            ? `待ち受け中: ${server.bind_address}:${server.port}`
+            : server?.state === 'failed'
+              ? `起動失敗: ${server.error ?? ''}`
+              : '起動中'}
+        </p>
+        {server?.state === 'running' && server.port !== server.requested_port && (
+          <p className="text-xs text-orange-600">
+            ポート {server.requested_port} が使用中のため {server.port} で起動しました
+          </p>
+        )}
+        <div className="mt-2 flex items-center gap-2">
+          <input
+            type="number"
+            min={1024}
+            max={65535}
+            value={portInput}
+            onChange={(e) => setPortInput(e.target.value)}
+            className="w-28 px-2 py-1 text-sm rounded border bg-white dark:bg-gray-700"
+          />
+          <button onClick={savePort} className="px-3 py-1 bg-blue-600 text-white text-sm rounded">
+            ポートを変更
+          </button>
+        </div>
+      </div>
+
+      <div className="flex items-center gap-4">
+        <button
+          onClick={startPairing}
+          className="px-3 py-1 bg-blue-600 text-white text-sm rounded"
+        >
+          ペアリングコードを発行
+        </button>
+        {pairing && (
+          <div>
+            <p className="text-2xl font-mono tracking-widest">{pairing.code}</p>
+            <p className="text-xs text-gray-600 dark:text-gray-400">
+              {new Date(pairing.expires_at).toLocaleTimeString()} まで有効
+            </p>
+          </div>
+        )}
+      </div>
+
+      {error && <p className="mt-2 text-sm text-red-600">{error}</p>}
+
+      <div className="mt-4">
+        <h4 className="font-medium text-sm text-gray-600 dark:text-gray-400 mb-2">接続済みクライアント</h4>
+        {clients.length === 0 ? (
+          <p className="text-sm text-gray-600 dark:text-gray-400">なし</p>
+        ) : (
+          <ul className="space-y-2">
+            {clients.map((c) => (
+              <li key={c.id} className="flex items-center justify-between text-sm">
+                <span className="truncate">
+                  {c.name}
+                  <span className="ml-2 text-xs text-gray-600 dark:text-gray-400">
+                    最終利用: {c.last_used_at ? new Date(c.last_used_at).toLocaleString() : 'なし'}
+                  </span>
+                </span>
+                <button
+                  onClick={() => revoke(c.id)}
+                  className="px-3 py-1 bg-red-600 text-white text-sm rounded"
+                >
+                  解除
+                </button>
+              </li>
+            ))}
+          </ul>
+        )}
+      </div>
+    </div>
+  );
+};
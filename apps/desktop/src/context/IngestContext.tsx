@@ -1,5 +1,4 @@
 import React, { createContext, useContext, useEffect, useState } from 'react';
-import { invoke } from "@tauri-apps/api/core";
 import { isEnabled, enable } from "@tauri-apps/plugin-autostart";
 import { useIngestUI } from '../hooks/ui';
 import { getSamplingService } from '../services/SamplingService';
@@ -62,7 +61,6 @@ const IngestContext = createContext<IngestContextValue | undefined>(undefined);
 export const IngestProvider: React.FC<{ children: React.ReactNode }> = ({ children }) => {
   const ingestUI = useIngestUI();
   const [autostartEnabled, setAutostartEnabled] = useState<boolean>(false);
-  const [processedEvents, setProcessedEvents] = useState<Set<string>>(new Set());
 
   // 自動起動は常時有効にする（トグル不可）
   const toggleAutostart = async () => {
@@ -109,47 +107,6 @@ export const IngestProvider: React.FC<{ children: React.ReactNode }> = ({ childr
     }
   };
 
-  // セッション終了時のローカルDB保存処理
-  useEffect(() => {
-    const handleSessionEnd = async (event: SessionEvent) => {
-      if (event.type === 'session_ended' && event.duration_seconds > 0) {
-        try {
-          const id = `${event.start_time}-${event.session_key}`;
-          const session = { 
-            id, 
-            start_time: event.start_time, 
-            duration_seconds: event.duration_seconds, 
-            session_key: event.session_key 
-          };
-          await invoke('db_upsert_session', { session });
-          console.log('[IngestContext] セッションをローカルDBに保存:', session);
-        } catch (err) {
-          console.error('[IngestContext] ローカルDB保存エラー:', err);
-        }
-      }
-    };
-
-    // 新しく追加されたセッション終了イベントのみを処理（重複防止）
-    const sessionEndEvents = ingestUI.events.filter(event => 
-      event.type === 'session_ended' && 
-      !processedEvents.has(`${event.start_time}-${event.session_key}`)
-    );
-    
-    console.log(`[IngestContext] 処理対象のセッション終了イベント数: ${sessionEndEvents.length}`);
-    console.log(`[IngestContext] 総イベント数: ${ingestUI.events.length}, 処理済みイベント数: ${processedEvents.size}`);
-    
-    sessionEndEvents.forEach(event => {
-      const eventKey = `${event.start_time}-${event.session_key}`;
-      console.log(`[IngestContext] セッション終了イベントを処理中:`, {
-        eventKey,
-        duration: event.duration_seconds,
-        sessionKey: event.session_key
-      });
-      handleSessionEnd(event);
-      setProcessedEvents(prev => new Set([...prev, eventKey]));
-    });
-  }, [ingestUI.events, processedEvents]);
-
   // コンテキスト値の構築
   const contextValue: IngestContextValue = {
     // サンプリングデータ
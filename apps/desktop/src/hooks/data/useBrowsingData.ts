@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '../../utils/errors';
 
 export const useBrowsingData = () => {
   const [sessions, setSessions] = useState<any[]>([]);
@@ -14,7 +15,7 @@ export const useBrowsingData = () => {
       const result = await invoke<any[]>('db_get_browsing_sessions', { query });
       setSessions(result);
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to fetch browsing sessions:', err);
     } finally {
       setLoading(false);
@@ -28,7 +29,7 @@ export const useBrowsingData = () => {
       const result = await invoke<any[]>('db_get_domains');
       setDomains(result);
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to fetch domains:', err);
     } finally {
       setLoading(false);
@@ -40,7 +41,7 @@ export const useBrowsingData = () => {
       await invoke('db_upsert_browsing_session', { session });
       await fetchBrowsingSessions();
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to upsert browsing session:', err);
       throw err;
     }
@@ -57,7 +58,7 @@ export const useBrowsingData = () => {
       }
       await Promise.all([fetchDomains(), fetchBrowsingSessions()]);
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to upsert domain:', err);
       throw err;
     }
@@ -68,7 +69,7 @@ export const useBrowsingData = () => {
       await invoke('db_delete_browsing_session', { id });
       await fetchBrowsingSessions();
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to delete browsing session:', err);
       throw err;
     }
@@ -79,7 +80,7 @@ export const useBrowsingData = () => {
       const categoryId = await invoke<number | null>('db_classify_domain', { domainName });
       return categoryId;
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to classify domain:', err);
       throw err;
     }
@@ -189,7 +190,7 @@ export const useBrowsingStats = (_query: any = {}) => {
 
       setStats(result);
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to calculate browsing stats:', err);
     } finally {
       setLoading(false);
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '../../utils/errors';
 
 export interface UpdateInfo {
   available: boolean;
@@ -23,7 +24,7 @@ export function useUpdater() {
     } catch (error) {
       setUpdateInfo({
         available: false,
-        error: error as string,
+        error: errorMessage(error),
       });
     } finally {
       setIsChecking(false);
@@ -37,7 +38,7 @@ export function useUpdater() {
     } catch (error) {
       setUpdateInfo(prev => ({
         ...prev,
-        error: error as string,
+        error: errorMessage(error),
       }));
     } finally {
       setIsInstalling(false);
@@ -2,6 +2,7 @@ import React, { useEffect, useState } from 'react';
 import { useIngest } from '../context/IngestContext';
 // Dark-only: theme hook no longer needed here
 import { SettingsPage as SharedSettingsPage } from '@wasteday/ui';
+import { ExtensionPairing } from '../components/ExtensionPairing';
 
 export const SettingsPage: React.FC = () => {
   const { updateGapThreshold } = useIngest();
@@ -36,10 +37,15 @@ export const SettingsPage: React.FC = () => {
   
 
   return (
-    <SharedSettingsPage
-      onSaveSettings={handleSaveSettings}
-      showDatabaseStatus={false}
-      appVersion={appVersion}
-    />
+    <>
+      <SharedSettingsPage
+        onSaveSettings={handleSaveSettings}
+        showDatabaseStatus={false}
+        appVersion={appVersion}
+      />
+      <div className="mt-6">
+        <ExtensionPairing />
+      </div>
+    </>
   );
 };
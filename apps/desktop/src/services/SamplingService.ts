@@ -1,11 +1,8 @@
 import { invoke } from "@tauri-apps/api/core";
-import { IngestService, type SampleEvent, type IngestOptions } from "@wasteday/ingest";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 
-type ForegroundInfo = {
-  process_id: number;
-  exe: string;
-  window_title: string;
-};
+// サンプリングとセッション化は Rust 側のバックグラウンドサンプラーが行う。
+// このサービスは Tauri イベントを受け取って UI に流すだけ（DB への書き込みは行わない）。
 
 type SamplingConfig = {
   samplingIntervalMs: number;
@@ -13,152 +10,131 @@ type SamplingConfig = {
   idleThresholdSeconds: number;
 };
 
+type SamplePayload = {
+  timestamp: string;
+  process_id: number;
+  exe: string;
+  identifier: string;
+  window_title: string;
+  user_state: 'active' | 'idle';
+  idle_sec: number;
+};
+
+type SessionPayload = {
+  type: 'session_started' | 'session_updated' | 'session_ended';
+  session_key: string;
+  start_time: string;
+  end_time: string;
+  duration_seconds: number;
+  is_idle: boolean;
+  is_media_playing: boolean;
+  window_title?: string | null;
+  url?: string | null;
+};
+
+type SamplerStatus = {
+  is_running: boolean;
+  last_sample_time: string | null;
+  error_count: number;
+  open_sessions: string[];
+  config: {
+    sampling_interval_ms: number;
+    idle_gap_threshold_seconds: number;
+    session_switch_grace_period_seconds: number;
+    idle_threshold_seconds: number;
+  };
+};
+
 export type SamplingEvent = {
   type: 'sample' | 'session_started' | 'session_updated' | 'session_ended';
   data: any;
   timestamp: string;
 };
 
+const SAMPLE_EVENT = 'sampling:sample';
+const SESSION_EVENT = 'sampling:session';
+
 export class SamplingService {
-  private ingest: IngestService;
   private config: SamplingConfig;
-  private intervalId: NodeJS.Timeout | null = null;
+  private unlisteners: UnlistenFn[] = [];
   private isRunning = false;
   private listeners: Array<(event: SamplingEvent) => void> = [];
   private lastSampleTime = 0;
   private errorCount = 0;
-  private maxRetries = 3;
+  private pending = new Map<string, any>();
 
   constructor(config?: Partial<SamplingConfig>) {
-    // Load gap threshold from localStorage
-    const savedGapThreshold = localStorage.getItem('wasteday-gap-threshold');
-    const gapThreshold = savedGapThreshold ? parseInt(savedGapThreshold, 10) : 20;
-    
     this.config = {
       samplingIntervalMs: 5000,
-      idleGapThresholdSeconds: gapThreshold,
+      idleGapThresholdSeconds: 20,
       idleThresholdSeconds: 60,
       ...config,
     };
-
-    const ingestOptions: IngestOptions = {
-      samplingIntervalMs: this.config.samplingIntervalMs,
-      idleGapThresholdSeconds: this.config.idleGapThresholdSeconds,
-      sessionSwitchGracePeriodSeconds: 5, // アプリ切り替え時の猶予期間（秒）- サンプリング間隔と同じ
-    };
-    
-    this.ingest = new IngestService(ingestOptions);
-
-    this.setupIngestListeners();
-    this.setupVisibilityHandlers();
-  }
-
-  private setupIngestListeners() {
-    this.ingest.onEvent((e) => {
-      const event: SamplingEvent = {
-        type: e.type as any,
-        data: e,
-        timestamp: new Date().toISOString(),
-      };
-      this.emit(event);
-    });
   }
 
   public getPendingInserts(): Map<string, any> {
-    return this.ingest.getPendingSamples();
+    return new Map(this.pending);
   }
 
-  private setupVisibilityHandlers() {
-    if (typeof document !== 'undefined') {
-      document.addEventListener('visibilitychange', () => {
-        if (document.hidden) {
-          console.log('[SamplingService] アプリがバックグラウンドに移行');
-          this.handleBackgroundMode();
-        } else {
-          console.log('[SamplingService] アプリがフォアグラウンドに復帰');
-          this.handleForegroundMode();
-        }
+  private async subscribe(): Promise<void> {
+    const unlistenSample = await listen<SamplePayload>(SAMPLE_EVENT, ({ payload }) => {
+      this.lastSampleTime = Date.now();
+      this.emit({
+        type: 'sample',
+        data: payload,
+        timestamp: payload.timestamp,
       });
-    }
-  }
-
-  private handleBackgroundMode() {
-    // バックグラウンド時はサンプリング間隔を延長
-    if (this.isRunning && this.intervalId) {
-      clearInterval(this.intervalId);
-      this.intervalId = setInterval(() => this.sampleWithRetry(), this.config.samplingIntervalMs * 2);
-    }
-  }
+    });
 
-  private handleForegroundMode() {
-    // フォアグラウンド復帰時は即座にサンプリング実行
-    if (this.isRunning) {
-      this.sampleWithRetry();
-      // 通常の間隔に戻す
-      if (this.intervalId) {
-        clearInterval(this.intervalId);
-        this.intervalId = setInterval(() => this.sampleWithRetry(), this.config.samplingIntervalMs);
+    const unlistenSession = await listen<SessionPayload>(SESSION_EVENT, ({ payload }) => {
+      if (payload.type === 'session_ended') {
+        this.pending.delete(payload.session_key);
+      } else {
+        this.pending.set(payload.session_key, payload);
       }
-    }
+      this.emit({
+        type: payload.type,
+        data: {
+          ...payload,
+          window_title: payload.window_title ?? undefined,
+          url: payload.url ?? undefined,
+        },
+        timestamp: new Date().toISOString(),
+      });
+    });
+
+    this.unlisteners.push(unlistenSample, unlistenSession);
   }
 
-  private async sampleWithRetry(retryCount = 0): Promise<void> {
+  private async syncStatus(): Promise<void> {
     try {
-      await this.performSample();
-      this.errorCount = 0; // 成功時はエラーカウントをリセット
+      const status = await invoke<SamplerStatus>('sampler_get_status');
+      this.errorCount = status.error_count;
+      this.config = {
+        samplingIntervalMs: status.config.sampling_interval_ms,
+        idleGapThresholdSeconds: status.config.idle_gap_threshold_seconds,
+        idleThresholdSeconds: status.config.idle_threshold_seconds,
+      };
     } catch (error) {
-      this.errorCount++;
-      console.error(`[SamplingService] サンプリング失敗 (試行${retryCount + 1}/${this.maxRetries}):`, error);
-      
-      if (retryCount < this.maxRetries - 1) {
-        // 指数バックオフで再試行
-        const delay = Math.min(1000 * Math.pow(2, retryCount), 10000);
-        setTimeout(() => this.sampleWithRetry(retryCount + 1), delay);
-      } else {
-        console.error('[SamplingService] 最大再試行回数に達しました');
-      }
+      console.error('[SamplingService] サンプラー状態の取得に失敗:', error);
     }
   }
 
-  private async performSample(): Promise<void> {
-    const info = await invoke<ForegroundInfo>("get_foreground_info");
-    const idleSec = await invoke<number>("get_idle_seconds");
-    const user_state: 'active' | 'idle' = idleSec >= this.config.idleThresholdSeconds ? 'idle' : 'active';
-    
-    const sample: SampleEvent = {
-      timestamp: new Date().toISOString(),
-      category: 'app',
-      identifier: (info.exe || 'unknown.exe').toLowerCase(),
-      window_title: info.window_title,
-      user_state,
-    };
-
-    console.log('[SamplingService] サンプル実行:', { info, idleSec, user_state, sample });
-    this.ingest.handleSample(sample).catch(error => {
-      console.error('[SamplingService] Sample processing error:', error);
-    });
-    this.lastSampleTime = Date.now();
-
-    const event: SamplingEvent = {
-      type: 'sample',
-      data: { ...info, user_state, idle_sec: idleSec, identifier: sample.identifier },
-      timestamp: sample.timestamp,
-    };
-    this.emit(event);
-  }
-
   public start(): void {
     if (this.isRunning) {
       console.warn('[SamplingService] 既に実行中です');
       return;
     }
 
-    console.log('[SamplingService] サンプリングを開始');
+    console.log('[SamplingService] サンプラーのイベント購読を開始');
     this.isRunning = true;
-    this.intervalId = setInterval(() => this.sampleWithRetry(), this.config.samplingIntervalMs);
-    
-    // 即座に最初のサンプリングを実行
-    this.sampleWithRetry();
+    invoke('sampler_set_running', { running: true }).catch(error => {
+      console.error('[SamplingService] サンプラーの開始に失敗:', error);
+    });
+    this.subscribe().catch(error => {
+      console.error('[SamplingService] イベント購読に失敗:', error);
+    });
+    this.syncStatus();
   }
 
   public stop(): void {
@@ -167,18 +143,15 @@ export class SamplingService {
       return;
     }
 
-    console.log('[SamplingService] サンプリングを停止');
+    console.log('[SamplingService] サンプラーを停止');
     this.isRunning = false;
-    
-    if (this.intervalId) {
-      clearInterval(this.intervalId);
-      this.intervalId = null;
-    }
-
-    // 開いているセッションをすべて終了
-    this.ingest.flushAll().catch(error => {
-      console.error('[SamplingService] Flush error:', error);
+    // Rust 側で開いているセッションは確定される
+    invoke('sampler_set_running', { running: false }).catch(error => {
+      console.error('[SamplingService] サンプラーの停止に失敗:', error);
     });
+    this.unlisteners.forEach(unlisten => unlisten());
+    this.unlisteners = [];
+    this.pending.clear();
   }
 
   public onEvent(listener: (event: SamplingEvent) => void): () => void {
@@ -209,20 +182,16 @@ export class SamplingService {
 
   public updateConfig(newConfig: Partial<SamplingConfig>): void {
     this.config = { ...this.config, ...newConfig };
-    
-    // IngestServiceのインスタンスも更新
-    this.ingest = new IngestService({
-      samplingIntervalMs: this.config.samplingIntervalMs,
-      idleGapThresholdSeconds: this.config.idleGapThresholdSeconds,
-      sessionSwitchGracePeriodSeconds: 5, // サンプリング間隔と同じ
+
+    invoke('sampler_update_config', {
+      config: {
+        sampling_interval_ms: newConfig.samplingIntervalMs,
+        idle_gap_threshold_seconds: newConfig.idleGapThresholdSeconds,
+        idle_threshold_seconds: newConfig.idleThresholdSeconds,
+      },
+    }).catch(error => {
+      console.error('[SamplingService] サンプラー設定の更新に失敗:', error);
     });
-    this.setupIngestListeners();
-    
-    // 実行中の場合、新しい設定で再開
-    if (this.isRunning) {
-      this.stop();
-      this.start();
-    }
   }
 }
 
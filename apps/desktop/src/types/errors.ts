@@ -0,0 +1,20 @@
+// Error type returned by Tauri commands (see src-tauri/src/error.rs)
+
+export type AppErrorCode =
+  | 'NotFound'
+  | 'Conflict'
+  | 'Validation'
+  | 'DbLocked'
+  | 'DbCorrupt'
+  | 'Io'
+  | 'Internal';
+
+export interface AppError {
+  code: AppErrorCode;
+  /** 英語の詳細（ログ用） */
+  message: string;
+  /** code ごとの表示用メッセージ */
+  localized: { ja: string; en: string };
+  /** 対象の種類・id・項目名など */
+  details: Record<string, unknown>;
+}
@@ -0,0 +1,53 @@
+/**
+ * コマンドのエラー（AppError）を扱うユーティリティ
+ */
+import type { AppError, AppErrorCode } from '../types/errors';
+
+/**
+ * invoke が reject した値が AppError かどうか
+ */
+export function isAppError(value: unknown): value is AppError {
+  return (
+    typeof value === 'object' &&
+    value !== null &&
+    typeof (value as AppError).code === 'string' &&
+    typeof (value as AppError).message === 'string'
+  );
+}
+
+/**
+ * 指定した種類のエラーかどうか（NotFound なら一覧を再取得する、など）
+ */
+export function hasErrorCode(value: unknown, code: AppErrorCode): boolean {
+  return isAppError(value) && value.code === code;
+}
+
+export type ErrorLocale = keyof AppError['localized'];
+
+/**
+ * 表示言語: ブラウザ（webview）の言語が日本語なら ja、それ以外は en
+ */
+export function currentLocale(): ErrorLocale {
+  const language = typeof navigator !== 'undefined' ? navigator.language : '';
+  return language.toLowerCase().startsWith('ja') ? 'ja' : 'en';
+}
+
+/**
+ * 画面に出すメッセージ: 表示用メッセージに英語の詳細を添える
+ * @param value catch した値（AppError / Error / 文字列）
+ * @param locale 表示言語（既定は currentLocale()）
+ */
+export function errorMessage(value: unknown, locale: ErrorLocale = currentLocale()): string {
+  if (isAppError(value)) {
+    if (!value.localized) {
+      return value.message;
+    }
+    return locale === 'ja'
+      ? `${value.localized.ja}（${value.message}）`
+      : `${value.localized.en} (${value.message})`;
+  }
+  if (value instanceof Error) {
+    return value.message;
+  }
+  return String(value);
+}
@@ -7,11 +7,18 @@ CREATE TABLE IF NOT EXISTS sessions (
   start_time TEXT NOT NULL, -- ISO8601
   duration_seconds INTEGER NOT NULL,
   session_key TEXT NOT NULL, -- semicolon key=value pairs
+  category TEXT, -- parsed from session_key
+  identifier TEXT, -- parsed from session_key (lowercase)
+  user_state TEXT, -- parsed from session_key ('active' | 'idle')
+  window_title TEXT,
   created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
   updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
 );
 
 CREATE INDEX IF NOT EXISTS idx_sessions_start_time ON sessions(start_time);
+CREATE INDEX IF NOT EXISTS idx_sessions_identifier_start_time ON sessions(identifier, start_time);
+CREATE INDEX IF NOT EXISTS idx_sessions_user_state_start_time ON sessions(user_state, start_time);
+CREATE INDEX IF NOT EXISTS idx_sessions_category_start_time ON sessions(category, start_time);
 
 -- waste_categories: classification rules
 CREATE TABLE IF NOT EXISTS waste_categories (
@@ -12,6 +12,10 @@ export interface Session {
   start_time: IsoTimestamp;
   duration_seconds: number;
   session_key: string;
+  category?: 'app' | 'browser' | 'system' | null;
+  identifier?: string | null;
+  user_state?: 'active' | 'idle' | null;
+  window_title?: string | null;
   created_at?: IsoTimestamp;
   updated_at?: IsoTimestamp;
 }
@@ -51,6 +55,9 @@ export interface UserSettings {
 export interface SessionQueryParams {
   since?: IsoTimestamp;
   until?: IsoTimestamp;
+  category?: 'app' | 'browser' | 'system';
+  identifier?: string;
+  user_state?: 'active' | 'idle';
   limit?: number;
   offset?: number;
 }